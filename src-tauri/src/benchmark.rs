@@ -0,0 +1,347 @@
+use crate::build_info::is_hardware;
+use crate::cache::cache_subdir;
+use crate::capabilities;
+use crate::scratch;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::process::Command;
+
+/// The test clip: moving synthetic 720p, the same frames every run so quality scores
+/// compare across encoders and machines
+const CLIP_SOURCE: &str = "testsrc2=size=1280x720:rate=30:duration=4,format=yuv420p";
+const CLIP_FRAMES: f64 = 120.0;
+const CLIP_PIXELS: f64 = 1280.0 * 720.0;
+
+/// Every encoder gets the same budget, so quality differences are the encoder's
+const CLIP_BITRATE: &str = "2500k";
+
+/// An encode that hasn't finished by then is stopped and reported as failed
+const ENCODE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Encoders within this much SSIM of a codec's best count as equal quality; the
+/// fastest of them is recommended
+const SSIM_TOLERANCE: f64 = 0.005;
+
+/// Codecs the app encodes at a target bitrate, with the encoders it can use for each
+const CODECS: &[(&str, &[&str])] = &[
+    ("h264", &["libx264", "h264_nvenc", "h264_vaapi"]),
+    ("hevc", &["libx265", "hevc_nvenc", "hevc_vaapi"]),
+    ("av1", &["libsvtav1", "av1_nvenc"]),
+    ("vp9", &["libvpx-vp9"]),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncoderBenchmark {
+    pub encoder: String,
+    /// "h264", "hevc", "av1" or "vp9"
+    pub codec: String,
+    pub hardware: bool,
+    /// Encoded frames per second
+    pub fps: Option<f64>,
+    /// SSIM against the source (1.0 is identical)
+    pub ssim: Option<f64>,
+    /// Why the encoder couldn't be benchmarked
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub results: Vec<EncoderBenchmark>,
+    /// Best default encoder per codec: the fastest at (near) the best quality.
+    /// convert_file uses it in place of its NVENC, VAAPI, CPU order.
+    pub recommended: BTreeMap<String, String>,
+    /// What the figures were measured with; an update to either makes them stale
+    #[serde(flatten)]
+    pub environment: BenchmarkEnvironment,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkEnvironment {
+    /// First line of `ffmpeg -version`
+    #[serde(rename = "ffmpegVersion", default)]
+    pub ffmpeg_version: String,
+    /// NVIDIA driver version, from nvidia-smi
+    #[serde(rename = "gpuDriver", default)]
+    pub gpu_driver: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BenchmarkProgress {
+    encoder: String,
+    /// Encoders finished so far, of `total`
+    done: usize,
+    total: usize,
+}
+
+fn command(ffmpeg_path: &Path) -> Command {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.kill_on_drop(true);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    cmd
+}
+
+/// Run an ffmpeg command under ENCODE_TIMEOUT and return its stderr
+async fn run(mut cmd: Command, what: &str) -> Result<String, String> {
+    let output = tokio::time::timeout(ENCODE_TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| format!("{} timed out", what))?
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", what, stderr.lines().last().unwrap_or_default()));
+    }
+    Ok(stderr)
+}
+
+/// First line of a command's stdout, if it ran and succeeded
+async fn first_line(mut cmd: Command) -> Option<String> {
+    let output = cmd.output().await.ok().filter(|o| o.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+}
+
+/// Environment of each ffmpeg binary seen this session
+static ENVIRONMENTS: OnceLock<Mutex<HashMap<capabilities::BinaryKey, BenchmarkEnvironment>>> = OnceLock::new();
+
+/// The ffmpeg and NVIDIA driver versions a benchmark run now would measure
+async fn environment(ffmpeg_path: &Path) -> BenchmarkEnvironment {
+    let cache = ENVIRONMENTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = capabilities::binary_key(ffmpeg_path);
+    if let Some(environment) = cache.lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return environment;
+    }
+
+    let mut version = command(ffmpeg_path);
+    version.args(["-hide_banner", "-version"]);
+    let mut driver = command(Path::new("nvidia-smi"));
+    driver.args(["--query-gpu=driver_version", "--format=csv,noheader"]);
+    let environment = BenchmarkEnvironment {
+        ffmpeg_version: first_line(version).await.unwrap_or_default(),
+        gpu_driver: first_line(driver).await,
+    };
+    if let Ok(mut cache) = cache.lock() {
+        cache.insert(key, environment.clone());
+    }
+    environment
+}
+
+/// Encoder settings matching what convert_file uses
+fn encoder_args(encoder: &str) -> &'static [&'static str] {
+    match encoder {
+        "libx264" => &["-preset", "slow"],
+        "libx265" => &["-preset", "medium"],
+        "libsvtav1" => &["-preset", "6"],
+        "libvpx-vp9" => &["-deadline", "good", "-cpu-used", "2", "-row-mt", "1"],
+        "h264_nvenc" | "hevc_nvenc" | "av1_nvenc" => {
+            &["-preset", "p7", "-tune", "hq", "-rc", "vbr", "-multipass", "fullres", "-rc-lookahead", "20"]
+        }
+        "h264_vaapi" | "hevc_vaapi" => &["-rc_mode", "VBR"],
+        _ => &[],
+    }
+}
+
+/// Whether the encoder can run here; hardware encoders must pass a test encode
+async fn usable(ffmpeg_path: &PathBuf, encoder: &str) -> bool {
+    if encoder.ends_with("_vaapi") {
+        capabilities::vaapi_device(ffmpeg_path, encoder).await.is_some()
+    } else if is_hardware(encoder) {
+        capabilities::encoder_works(ffmpeg_path, encoder).await
+    } else {
+        capabilities::has_encoder(ffmpeg_path, encoder).await
+    }
+}
+
+/// "bench: utime=1.234s stime=0.056s rtime=0.789s" from -benchmark: the wall time of the
+/// transcode itself, without process startup or opening the input
+fn parse_rtime(stderr: &str) -> Option<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.starts_with("bench:"))
+        .find_map(|line| line.split_whitespace().find_map(|field| field.strip_prefix("rtime=")))
+        .and_then(|value| value.trim_end_matches('s').parse().ok())
+        .filter(|seconds: &f64| *seconds > 0.0)
+}
+
+/// "All:0.987" from the ssim filter's summary line
+fn parse_ssim(stderr: &str) -> Option<f64> {
+    stderr
+        .lines()
+        .filter(|line| line.contains("SSIM"))
+        .find_map(|line| line.split_whitespace().find_map(|field| field.strip_prefix("All:")))
+        .and_then(|value| value.parse().ok())
+}
+
+/// Render the test clip once, uncompressed, so generating it isn't part of any
+/// encoder's time and decoding it costs next to nothing
+async fn render_clip(ffmpeg_path: &Path, clip: &Path) -> Result<(), String> {
+    let mut cmd = command(ffmpeg_path);
+    cmd.args(["-hide_banner", "-y", "-f", "lavfi", "-i", CLIP_SOURCE, "-c:v", "rawvideo", "-f", "nut"]);
+    cmd.arg(clip);
+    run(cmd, "Rendering the test clip").await.map(|_| ())
+}
+
+/// Encode the test clip with `encoder`, timing it, then score the result against the clip
+async fn benchmark_encoder(ffmpeg_path: &PathBuf, encoder: &str, clip: &Path, output: &Path) -> Result<(f64, f64), String> {
+    let mut cmd = command(ffmpeg_path);
+    cmd.args(["-hide_banner", "-y", "-benchmark"]);
+    if encoder.ends_with("_vaapi") {
+        if let Some(device) = capabilities::vaapi_device(ffmpeg_path, encoder).await {
+            cmd.arg("-vaapi_device").arg(device);
+        }
+    }
+    cmd.arg("-i").arg(clip);
+    if encoder.ends_with("_vaapi") {
+        cmd.args(["-vf", "format=nv12,hwupload"]);
+    }
+    cmd.args(["-c:v", encoder]);
+    cmd.args(encoder_args(encoder));
+    cmd.args(["-b:v", CLIP_BITRATE]);
+    cmd.arg(output);
+
+    let stderr = run(cmd, "Encoding").await?;
+    let seconds = parse_rtime(&stderr).ok_or_else(|| "Encoding failed: no timing in ffmpeg output".to_string())?;
+    let fps = CLIP_FRAMES / seconds;
+
+    let mut cmd = command(ffmpeg_path);
+    cmd.args(["-hide_banner"]);
+    cmd.arg("-i").arg(output);
+    cmd.arg("-i").arg(clip);
+    cmd.args(["-lavfi", "[0:v][1:v]ssim", "-f", "null", "-"]);
+    let stderr = run(cmd, "Scoring").await?;
+    let ssim = parse_ssim(&stderr).ok_or_else(|| "Scoring failed: no SSIM in ffmpeg output".to_string())?;
+
+    Ok((fps, ssim))
+}
+
+/// Per codec, the fastest encoder whose quality is within SSIM_TOLERANCE of the best
+fn recommend(results: &[EncoderBenchmark]) -> BTreeMap<String, String> {
+    let mut recommended = BTreeMap::new();
+    for (codec, _) in CODECS {
+        let scored: Vec<(&EncoderBenchmark, f64, f64)> = results
+            .iter()
+            .filter(|r| r.codec == *codec)
+            .filter_map(|r| Some((r, r.fps?, r.ssim?)))
+            .collect();
+        let Some(best_ssim) = scored.iter().map(|(_, _, ssim)| *ssim).reduce(f64::max) else {
+            continue;
+        };
+        if let Some((fastest, _, _)) = scored
+            .iter()
+            .filter(|(_, _, ssim)| *ssim >= best_ssim - SSIM_TOLERANCE)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+        {
+            recommended.insert(codec.to_string(), fastest.encoder.clone());
+        }
+    }
+    recommended
+}
+
+fn report_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(cache_subdir(app, "benchmark")?.join("report.json"))
+}
+
+/// The last benchmark run on this machine, if it was run with this ffmpeg and driver
+pub async fn cached_report(app: &tauri::AppHandle, ffmpeg_path: &Path) -> Option<BenchmarkReport> {
+    let path = report_file(app).ok()?;
+    let report: BenchmarkReport = fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok())?;
+    (report.environment == environment(ffmpeg_path).await).then_some(report)
+}
+
+/// The encoder the benchmark recommends for `codec`, if a current one was run
+pub async fn recommended_encoder(app: &tauri::AppHandle, ffmpeg_path: &Path, codec: &str) -> Option<String> {
+    cached_report(app, ffmpeg_path).await?.recommended.remove(codec)
+}
+
+/// The encoder convert_file would pick for `codec` (the recommended one, else NVENC,
+/// then VAAPI, then the CPU) and its expected speed at `width`x`height`, scaled from its
+/// benchmark by pixel count
+pub fn expected_encode_fps(report: &BenchmarkReport, codec: &str, width: u32, height: u32) -> Option<(String, f64)> {
+    let measured: Vec<(&EncoderBenchmark, f64)> = report
+        .results
+        .iter()
+        .filter(|r| r.codec == codec)
+        .filter_map(|r| Some((r, r.fps?)))
+        .collect();
+    let recommended = report.recommended.get(codec);
+    let (encoder, fps) = measured
+        .iter()
+        .find(|(r, _)| Some(&r.encoder) == recommended)
+        .or_else(|| {
+            ["_nvenc", "_vaapi"]
+                .iter()
+                .find_map(|suffix| measured.iter().find(|(r, _)| r.encoder.ends_with(suffix)))
+        })
+        .or_else(|| measured.iter().find(|(r, _)| !r.hardware))?;
+    let pixels = (width as f64 * height as f64).max(1.0);
+    Some((encoder.encoder.clone(), fps * CLIP_PIXELS / pixels))
+}
+
+/// Encode a short synthetic clip with every usable encoder, one at a time so they
+/// don't compete for the CPU, reporting speed and quality at the same bitrate.
+/// Progress comes as "benchmark-progress" events. The report is saved for encode
+/// time estimates and encoder selection.
+pub async fn benchmark_encoders(app: &tauri::AppHandle, ffmpeg_path: &PathBuf) -> Result<BenchmarkReport, String> {
+    let mut candidates = Vec::new();
+    for (codec, encoders) in CODECS {
+        for encoder in encoders.iter() {
+            if usable(ffmpeg_path, encoder).await {
+                candidates.push((*codec, *encoder));
+            }
+        }
+    }
+
+    let workspace = scratch::JobWorkspace::new(app, "benchmark")?;
+    let clip = workspace.file("clip.nut");
+    render_clip(ffmpeg_path, &clip).await?;
+    let total = candidates.len();
+    let mut results = Vec::new();
+    for (done, (codec, encoder)) in candidates.into_iter().enumerate() {
+        let _ = app.emit(
+            "benchmark-progress",
+            BenchmarkProgress {
+                encoder: encoder.to_string(),
+                done,
+                total,
+            },
+        );
+        let output = workspace.file(&format!("{}.mkv", encoder));
+        let outcome = benchmark_encoder(ffmpeg_path, encoder, &clip, &output).await;
+        let _ = std::fs::remove_file(&output);
+        let (fps, ssim, error) = match outcome {
+            Ok((fps, ssim)) => (Some(fps), Some(ssim), None),
+            Err(e) => (None, None, Some(e)),
+        };
+        results.push(EncoderBenchmark {
+            encoder: encoder.to_string(),
+            codec: codec.to_string(),
+            hardware: is_hardware(encoder),
+            fps,
+            ssim,
+            error,
+        });
+    }
+
+    let report = BenchmarkReport {
+        recommended: recommend(&results),
+        results,
+        environment: environment(ffmpeg_path).await,
+    };
+    if let (Ok(path), Ok(json)) = (report_file(app), serde_json::to_string(&report)) {
+        let _ = fs::write(path, json);
+    }
+    Ok(report)
+}
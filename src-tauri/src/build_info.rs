@@ -0,0 +1,221 @@
+use crate::capabilities;
+use crate::converter::{required_encoders, CONVERSION_TYPES};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Whether the bundled ffmpeg can produce one conversion type
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatSupport {
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    pub supported: bool,
+    /// Encoder groups with no available member, e.g. ["libx265 or hevc_nvenc"]
+    pub missing: Vec<String>,
+    /// Explanation for the UI when unsupported
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FfmpegBuildReport {
+    /// First line of `ffmpeg -version`
+    pub version: String,
+    /// "GPL v3", "GPL v2+", "LGPL v3", "LGPL v2.1+", with "nonfree" appended when enabled
+    pub license: String,
+    /// External libraries enabled at configure time (from --enable-lib* flags)
+    pub libraries: Vec<String>,
+    pub encoders: Vec<String>,
+    pub formats: Vec<FormatSupport>,
+}
+
+async fn ffmpeg_stdout(ffmpeg_path: &PathBuf, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffmpeg {} failed", args.join(" ")));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Configure flags, one per entry, from `ffmpeg -buildconf`
+fn configure_flags(buildconf: &str) -> Vec<String> {
+    buildconf
+        .split_whitespace()
+        .filter(|token| token.starts_with("--"))
+        .map(String::from)
+        .collect()
+}
+
+fn license(flags: &[String]) -> String {
+    let has = |flag: &str| flags.iter().any(|f| f == flag);
+    let mut license = match (has("--enable-gpl"), has("--enable-version3")) {
+        (true, true) => "GPL v3",
+        (true, false) => "GPL v2+",
+        (false, true) => "LGPL v3",
+        (false, false) => "LGPL v2.1+",
+    }
+    .to_string();
+    if has("--enable-nonfree") {
+        license.push_str(", nonfree");
+    }
+    license
+}
+
+fn format_support(conversion_type: &str, encoders: &[String]) -> FormatSupport {
+    let missing: Vec<String> = required_encoders(conversion_type)
+        .iter()
+        .filter(|group| !group.iter().any(|name| encoders.iter().any(|e| e == name)))
+        .map(|group| group.join(" or "))
+        .collect();
+
+    let reason = if missing.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "This ffmpeg build doesn't include {}, needed for {}",
+            missing.join(" and "),
+            conversion_type
+        ))
+    };
+
+    FormatSupport {
+        conversion_type: conversion_type.to_string(),
+        supported: missing.is_empty(),
+        missing,
+        reason,
+    }
+}
+
+/// Describe the bundled ffmpeg: version, license, enabled libraries, and which conversion
+/// types it can actually produce
+pub async fn build_report(ffmpeg_path: &PathBuf) -> Result<FfmpegBuildReport, String> {
+    let version = ffmpeg_stdout(ffmpeg_path, &["-hide_banner", "-version"]).await?;
+    let buildconf = ffmpeg_stdout(ffmpeg_path, &["-hide_banner", "-buildconf"]).await?;
+    let encoders = capabilities::encoders(ffmpeg_path).await;
+
+    let flags = configure_flags(&buildconf);
+    let libraries = flags
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("--enable-lib"))
+        .map(|lib| format!("lib{}", lib))
+        .collect();
+
+    let formats = CONVERSION_TYPES
+        .iter()
+        .map(|conversion_type| format_support(conversion_type, &encoders))
+        .collect();
+
+    Ok(FfmpegBuildReport {
+        version: version.lines().next().unwrap_or_default().to_string(),
+        license: license(&flags),
+        libraries,
+        encoders,
+        formats,
+    })
+}
+
+/// One encoder the app can use, as this machine runs it
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderCapability {
+    pub name: String,
+    /// "video" or "audio"
+    pub kind: String,
+    /// GPU encoders (NVENC, VAAPI)
+    pub hardware: bool,
+    /// Listed by ffmpeg and, for hardware encoders, passing a test encode
+    pub usable: bool,
+    /// Input pixel formats the encoder takes (video only)
+    #[serde(rename = "pixelFormats")]
+    pub pixel_formats: Vec<String>,
+    /// Largest frame the encoder accepts, where it's limited below what the app produces
+    #[serde(rename = "maxWidth")]
+    pub max_width: Option<u32>,
+    #[serde(rename = "maxHeight")]
+    pub max_height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncoderCapabilities {
+    pub encoders: Vec<EncoderCapability>,
+    /// Conversion types counting only usable encoders, so listed-but-broken GPU
+    /// encoders don't make a type look available
+    #[serde(rename = "conversionTypes")]
+    pub conversion_types: Vec<FormatSupport>,
+}
+
+pub(crate) fn is_hardware(encoder: &str) -> bool {
+    encoder.ends_with("_nvenc") || encoder.ends_with("_vaapi")
+}
+
+/// Frame size limits of encoders that have them (hardware limits are for current GPUs)
+fn max_resolution(encoder: &str) -> Option<(u32, u32)> {
+    match encoder {
+        "h264_nvenc" | "h264_vaapi" => Some((4096, 4096)),
+        "hevc_nvenc" | "av1_nvenc" | "hevc_vaapi" => Some((8192, 8192)),
+        "mpeg2video" => Some((4095, 4095)),
+        "libwebp" | "libwebp_anim" => Some((16383, 16383)),
+        "gif" | "apng" => Some((65535, 65535)),
+        _ => None,
+    }
+}
+
+/// Encoders behind the app's conversion types (plus VAAPI) with whether they work here,
+/// their pixel formats and size limits, and which conversion types that leaves available.
+/// Everything comes from the per-binary caches in capabilities, so only the first call
+/// (or one after refresh_capabilities) runs ffmpeg.
+pub async fn encoder_capabilities(ffmpeg_path: &PathBuf) -> Result<EncoderCapabilities, String> {
+    let listed = capabilities::encoders(ffmpeg_path).await;
+
+    let mut names: BTreeSet<&str> = CONVERSION_TYPES
+        .iter()
+        .flat_map(|conversion_type| required_encoders(conversion_type).iter().flat_map(|group| group.iter().copied()))
+        .collect();
+    names.extend(["h264_vaapi", "hevc_vaapi"]);
+
+    let mut encoders = Vec::new();
+    for name in names {
+        // Unlisted encoders aren't worth a row; the conversion types report them missing
+        if !listed.iter().any(|listed| listed == name) {
+            continue;
+        }
+        let hardware = is_hardware(name);
+        let usable = if name.ends_with("_vaapi") {
+            capabilities::vaapi_device(ffmpeg_path, name).await.is_some()
+        } else if hardware {
+            capabilities::encoder_works(ffmpeg_path, name).await
+        } else {
+            true
+        };
+        let help = capabilities::encoder_help(ffmpeg_path, name).await;
+        let max = max_resolution(name);
+        encoders.push(EncoderCapability {
+            name: name.to_string(),
+            kind: if help.audio { "audio" } else { "video" }.to_string(),
+            hardware,
+            usable,
+            pixel_formats: help.pixel_formats,
+            max_width: max.map(|(w, _)| w),
+            max_height: max.map(|(_, h)| h),
+        });
+    }
+
+    let usable: Vec<String> = encoders.iter().filter(|e| e.usable).map(|e| e.name.clone()).collect();
+    let conversion_types = CONVERSION_TYPES
+        .iter()
+        .map(|conversion_type| format_support(conversion_type, &usable))
+        .collect();
+
+    Ok(EncoderCapabilities {
+        encoders,
+        conversion_types,
+    })
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::Manager;
+
+/// 64-bit FNV-1a hash (stable across builds, unlike std's DefaultHasher)
+pub fn fnv1a_hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Cache key for a source file: changes whenever the file is replaced or modified
+pub fn file_cache_key(path: &str) -> String {
+    let (size, mtime) = fs::metadata(path)
+        .map(|m| {
+            let mtime = m
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            (m.len(), mtime)
+        })
+        .unwrap_or((0, 0));
+
+    let key = format!("{}|{}|{}", path, size, mtime);
+    format!("{:016x}", fnv1a_hash(key.as_bytes()))
+}
+
+/// Subdirectory of the app cache dir (falls back to the system temp dir)
+pub fn cache_subdir(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    let base = app
+        .path()
+        .app_cache_dir()
+        .unwrap_or_else(|_| std::env::temp_dir().join("torchio"));
+    let dir = base.join(name);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    Ok(dir)
+}
+
+/// File in the app data dir (for state that must survive cache cleanup)
+pub fn app_data_file(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    Ok(dir.join(name))
+}
+
+/// Write `contents` to a temp file beside `path` and rename it over `path`, so a crash
+/// mid-write leaves the previous file instead of a truncated one
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Whether a cached file exists and is non-empty
+pub fn is_cached(path: &Path) -> bool {
+    fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
@@ -0,0 +1,295 @@
+use crate::ffmpeg::pci_device_order;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+
+/// An ffmpeg binary as it was when probed; replacing or updating it changes the mtime
+pub type BinaryKey = (PathBuf, Option<SystemTime>);
+
+/// Encoder lists of the ffmpeg binaries seen this session
+static ENCODERS: OnceLock<Mutex<HashMap<BinaryKey, Vec<String>>>> = OnceLock::new();
+
+fn cache() -> &'static Mutex<HashMap<BinaryKey, Vec<String>>> {
+    ENCODERS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Results of test encodes, by binary and encoder
+static WORKING: OnceLock<Mutex<HashMap<(BinaryKey, String), bool>>> = OnceLock::new();
+
+fn working_cache() -> &'static Mutex<HashMap<(BinaryKey, String), bool>> {
+    WORKING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Render node each binary's VAAPI encoders passed a test encode on, by binary and encoder
+type VaapiDevices = HashMap<(BinaryKey, String), Option<PathBuf>>;
+
+static VAAPI_DEVICES: OnceLock<Mutex<VaapiDevices>> = OnceLock::new();
+
+fn vaapi_cache() -> &'static Mutex<VaapiDevices> {
+    VAAPI_DEVICES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// What `ffmpeg -h encoder=NAME` says about an encoder
+#[derive(Debug, Clone, Default)]
+pub struct EncoderHelp {
+    /// Audio encoders list sample formats where video encoders list pixel formats
+    pub audio: bool,
+    /// Input pixel formats the encoder takes (video only)
+    pub pixel_formats: Vec<String>,
+}
+
+/// Encoder help of the ffmpeg binaries seen this session, by binary and encoder
+static HELP: OnceLock<Mutex<HashMap<(BinaryKey, String), EncoderHelp>>> = OnceLock::new();
+
+fn help_cache() -> &'static Mutex<HashMap<(BinaryKey, String), EncoderHelp>> {
+    HELP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// A test encode that hasn't finished by then counts as failed (e.g. a hung driver)
+const TEST_ENCODE_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub fn binary_key(ffmpeg_path: &Path) -> BinaryKey {
+    let modified = fs::metadata(ffmpeg_path).and_then(|m| m.modified()).ok();
+    (ffmpeg_path.to_path_buf(), modified)
+}
+
+/// Encoder names from `ffmpeg -encoders`; entries follow a " ------" separator line
+/// and look like " V....D libx264   libx264 H.264 / AVC ..."
+pub fn parse_encoders(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1).map(String::from))
+        .collect()
+}
+
+async fn probe_encoders(ffmpeg_path: &PathBuf) -> Vec<String> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-encoders"]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    match cmd.output().await {
+        Ok(out) => parse_encoders(&String::from_utf8_lossy(&out.stdout)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Encoders the ffmpeg binary lists, probed once per binary path and modification time
+pub async fn encoders(ffmpeg_path: &PathBuf) -> Vec<String> {
+    let key = binary_key(ffmpeg_path);
+    if let Some(encoders) = cache().lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return encoders;
+    }
+
+    let encoders = probe_encoders(ffmpeg_path).await;
+    if let Ok(mut cache) = cache().lock() {
+        cache.insert(key, encoders.clone());
+    }
+    encoders
+}
+
+pub async fn has_encoder(ffmpeg_path: &PathBuf, name: &str) -> bool {
+    encoders(ffmpeg_path).await.iter().any(|e| e == name)
+}
+
+/// "Supported pixel formats: yuv420p yuvj420p ..." and "Supported sample formats: ..."
+/// lines of `ffmpeg -h encoder=NAME`
+fn parse_encoder_help(help: &str) -> EncoderHelp {
+    let field = |prefix: &str| help.lines().find_map(|line| line.trim().strip_prefix(prefix).map(str::to_string));
+    EncoderHelp {
+        audio: field("Supported sample formats:").is_some(),
+        pixel_formats: field("Supported pixel formats:")
+            .map(|formats| formats.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// The encoder's help, read once per binary
+pub async fn encoder_help(ffmpeg_path: &PathBuf, encoder: &str) -> EncoderHelp {
+    let key = (binary_key(ffmpeg_path), encoder.to_string());
+    if let Some(help) = help_cache().lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return help;
+    }
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-h", &format!("encoder={}", encoder)]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let help = match cmd.output().await {
+        Ok(out) => parse_encoder_help(&String::from_utf8_lossy(&out.stdout)),
+        Err(_) => EncoderHelp::default(),
+    };
+    if let Ok(mut cache) = help_cache().lock() {
+        cache.insert(key, help.clone());
+    }
+    help
+}
+
+/// Encode one second of a generated source with `encoder`, through `filter` if given,
+/// on `vaapi_device` or NVIDIA GPU `gpu_index` if given
+async fn test_encode(
+    ffmpeg_path: &PathBuf,
+    encoder: &str,
+    filter: Option<&str>,
+    vaapi_device: Option<&Path>,
+    gpu_index: Option<u32>,
+) -> bool {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-loglevel", "error"]);
+    if let Some(device) = vaapi_device {
+        cmd.arg("-vaapi_device").arg(device);
+    }
+    cmd.args(["-f", "lavfi", "-i", "nullsrc=s=256x256:d=1"]);
+    if let Some(filter) = filter {
+        cmd.args(["-vf", filter]);
+    }
+    cmd.args(["-c:v", encoder]);
+    if let Some(index) = gpu_index {
+        cmd.args(["-gpu", &index.to_string()]);
+    }
+    cmd.args(["-f", "null", "-"]);
+    cmd.kill_on_drop(true);
+    pci_device_order(&mut cmd);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    matches!(
+        tokio::time::timeout(TEST_ENCODE_TIMEOUT, cmd.output()).await,
+        Ok(Ok(output)) if output.status.success()
+    )
+}
+
+/// Whether `encoder` actually runs here, not just whether it's listed: hardware encoders
+/// are built into ffmpeg whether or not a GPU and a recent enough driver are present.
+/// Tested once per binary.
+pub async fn encoder_works(ffmpeg_path: &PathBuf, encoder: &str) -> bool {
+    let key = (binary_key(ffmpeg_path), encoder.to_string());
+    if let Some(works) = working_cache().lock().ok().and_then(|c| c.get(&key).copied()) {
+        return works;
+    }
+
+    let works = has_encoder(ffmpeg_path, encoder).await && test_encode(ffmpeg_path, encoder, None, None, None).await;
+    if let Ok(mut cache) = working_cache().lock() {
+        cache.insert(key, works);
+    }
+    works
+}
+
+/// Whether NVENC `encoder` runs on the GPU numbered `gpu_index` by nvidia-smi; the
+/// default GPU passing says nothing about the others. Tested once per binary and GPU.
+pub async fn nvenc_works(ffmpeg_path: &PathBuf, encoder: &str, gpu_index: Option<u32>) -> bool {
+    let Some(index) = gpu_index else {
+        return encoder_works(ffmpeg_path, encoder).await;
+    };
+    let key = (binary_key(ffmpeg_path), format!("{}@gpu{}", encoder, index));
+    if let Some(works) = working_cache().lock().ok().and_then(|c| c.get(&key).copied()) {
+        return works;
+    }
+
+    let works = has_encoder(ffmpeg_path, encoder).await && test_encode(ffmpeg_path, encoder, None, None, Some(index)).await;
+    if let Ok(mut cache) = working_cache().lock() {
+        cache.insert(key, works);
+    }
+    works
+}
+
+/// Whether frames can be uploaded to GPU `gpu_index`, scaled with scale_cuda and fed to
+/// NVENC without leaving it; needs an ffmpeg built with CUDA filters. Tested once per
+/// binary and GPU.
+pub async fn cuda_scaling_works(ffmpeg_path: &PathBuf, gpu_index: Option<u32>) -> bool {
+    let key = (binary_key(ffmpeg_path), format!("scale_cuda@gpu{}", gpu_index.map_or("default".to_string(), |i| i.to_string())));
+    if let Some(works) = working_cache().lock().ok().and_then(|c| c.get(&key).copied()) {
+        return works;
+    }
+
+    let upload = gpu_index.map_or("hwupload_cuda".to_string(), |index| format!("hwupload_cuda=device={}", index));
+    let filter = format!("format=nv12,{},scale_cuda=w=256:h=256:interp_algo=lanczos:format=nv12", upload);
+    let works = nvenc_works(ffmpeg_path, "h264_nvenc", gpu_index).await
+        && test_encode(ffmpeg_path, "h264_nvenc", Some(&filter), None, gpu_index).await;
+    if let Ok(mut cache) = working_cache().lock() {
+        cache.insert(key, works);
+    }
+    works
+}
+
+/// Drop every cached answer and probe `ffmpeg_path` again
+pub async fn refresh(ffmpeg_path: &PathBuf) -> Vec<String> {
+    if let Ok(mut cache) = cache().lock() {
+        cache.clear();
+    }
+    // e.g. after a driver update
+    if let Ok(mut cache) = working_cache().lock() {
+        cache.clear();
+    }
+    if let Ok(mut cache) = vaapi_cache().lock() {
+        cache.clear();
+    }
+    if let Ok(mut cache) = help_cache().lock() {
+        cache.clear();
+    }
+    encoders(ffmpeg_path).await
+}
+
+/// The /dev/dri/renderD* devices, in order. Intel and AMD drivers expose VAAPI there;
+/// Linux only.
+#[cfg(target_os = "linux")]
+fn render_nodes() -> Vec<PathBuf> {
+    let mut nodes: Vec<PathBuf> = fs::read_dir("/dev/dri")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("renderD"))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    nodes.sort();
+    nodes
+}
+
+#[cfg(not(target_os = "linux"))]
+fn render_nodes() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// Render node `encoder` (h264_vaapi, hevc_vaapi) runs on: the first that passes a test
+/// encode uploading frames to it. A node can exist without working VAAPI (e.g. an NVIDIA
+/// card next to the Intel iGPU, or a missing driver). Tested once per binary.
+pub async fn vaapi_device(ffmpeg_path: &PathBuf, encoder: &str) -> Option<PathBuf> {
+    let key = (binary_key(ffmpeg_path), encoder.to_string());
+    if let Some(device) = vaapi_cache().lock().ok().and_then(|c| c.get(&key).cloned()) {
+        return device;
+    }
+
+    let mut device = None;
+    if has_encoder(ffmpeg_path, encoder).await {
+        for node in render_nodes() {
+            if test_encode(ffmpeg_path, encoder, Some("format=nv12,hwupload"), Some(&node), None).await {
+                device = Some(node);
+                break;
+            }
+        }
+    }
+    if let Ok(mut cache) = vaapi_cache().lock() {
+        cache.insert(key, device.clone());
+    }
+    device
+}
@@ -0,0 +1,242 @@
+use crate::cache::app_data_file;
+use crate::output::resolve_output_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// A local whisper.cpp install used to transcribe clips
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WhisperConfig {
+    /// whisper.cpp command line binary (whisper-cli, or main in older releases)
+    pub binary: Option<String>,
+    /// ggml model file, e.g. ggml-base.en.bin
+    pub model: Option<String>,
+    /// Spoken language code; unset lets whisper detect it
+    pub language: Option<String>,
+}
+
+/// An extra subtitle file muxed as its own track, e.g. a translation of the captions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleTrack {
+    /// Subtitle file (.srt, .vtt or .ass) on the source's timeline
+    pub path: String,
+    /// ISO 639-2 language code, e.g. "eng" or "spa"
+    pub language: Option<String>,
+    /// Track name shown in players' subtitle menus
+    pub title: Option<String>,
+    /// Shown without the viewer picking it
+    pub default: bool,
+    /// Shown even when subtitles are off, for foreign-language dialogue
+    pub forced: bool,
+}
+
+impl SubtitleTrack {
+    fn disposition(&self) -> &'static str {
+        match (self.default, self.forced) {
+            (true, true) => "default+forced",
+            (true, false) => "default",
+            (false, true) => "forced",
+            (false, false) => "0",
+        }
+    }
+}
+
+/// Reject language tags the MP4 and MKV muxers would drop or mangle
+pub fn validate_tracks(tracks: &[SubtitleTrack]) -> Result<(), String> {
+    for track in tracks {
+        if let Some(ref language) = track.language {
+            if language.len() != 3 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+                return Err(format!("Subtitle language must be a three-letter ISO 639-2 code such as \"eng\": {}", language));
+            }
+        }
+    }
+    if tracks.iter().filter(|t| t.default).count() > 1 {
+        return Err("Only one subtitle track can be the default".to_string());
+    }
+    Ok(())
+}
+
+fn config_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "whisper.json")
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> WhisperConfig {
+    config_file(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_config(app: &tauri::AppHandle, config: &WhisperConfig) -> Result<(), String> {
+    if let Some(ref model) = config.model {
+        if !Path::new(model).is_file() {
+            return Err(format!("Model file not found: {}", model));
+        }
+    }
+    let json = serde_json::to_string(config).map_err(|e| format!("Failed to serialize whisper settings: {}", e))?;
+    fs::write(config_file(app)?, json).map_err(|e| format!("Failed to save whisper settings: {}", e))
+}
+
+fn command(path: &Path) -> Command {
+    #[allow(unused_mut)]
+    let mut cmd = Command::new(path);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    cmd
+}
+
+async fn run(mut cmd: Command, what: &str) -> Result<(), String> {
+    let output = cmd.output().await.map_err(|e| format!("Failed to run {}: {}", what, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or_default();
+        return Err(format!("{} failed: {}", what, reason));
+    }
+    Ok(())
+}
+
+/// Transcribe the file's first audio track with whisper.cpp and save the captions as an
+/// .srt next to it. Returns the .srt path.
+pub async fn transcribe(app: &tauri::AppHandle, ffmpeg_path: &Path, scratch_dir: &Path, path: &str) -> Result<String, String> {
+    let config = load_config(app);
+    let (binary, model) = match (config.binary, config.model) {
+        (Some(binary), Some(model)) => (binary, model),
+        _ => return Err("Set the whisper.cpp binary and model first".to_string()),
+    };
+
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "captions".to_string());
+    let location = resolve_output_path(app, path, &format!("{}.srt", stem), true)?;
+
+    // whisper.cpp only reads 16 kHz mono WAV
+    let unique_id = format!("{}_{}", std::process::id(), std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos());
+    let wav = scratch_dir.join(format!("whisper_{}.wav", unique_id));
+    let wav_str = wav.to_string_lossy().to_string();
+    let mut extract = command(ffmpeg_path);
+    extract.args(["-y", "-i", path, "-map", "0:a:0", "-vn", "-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le", &wav_str]);
+    run(extract, "Audio extraction").await?;
+
+    // -of takes the output path without extension; -osrt adds ".srt"
+    let prefix = scratch_dir.join(format!("whisper_{}", unique_id));
+    let prefix_str = prefix.to_string_lossy().to_string();
+    let mut whisper = command(Path::new(&binary));
+    whisper.args(["-m", &model, "-f", &wav_str, "-osrt", "-of", &prefix_str]);
+    whisper.args(["-l", config.language.as_deref().unwrap_or("auto")]);
+    let result = run(whisper, "whisper.cpp").await;
+    let _ = fs::remove_file(&wav);
+    result?;
+
+    let srt = prefix.with_extension("srt");
+    fs::copy(&srt, &location.path).map_err(|e| format!("Failed to save captions: {}", e))?;
+    let _ = fs::remove_file(&srt);
+    Ok(location.path.to_string_lossy().to_string())
+}
+
+/// Copy of a caption file cut to the exported range and shifted so it starts at zero,
+/// as SRT (or ASS, which keeps its styling)
+pub async fn prepare(
+    ffmpeg_path: &Path,
+    scratch_dir: &Path,
+    name: &str,
+    captions: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+) -> Result<PathBuf, String> {
+    let extension = Path::new(captions)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let extension = match extension.as_str() {
+        "ass" | "ssa" => "ass",
+        "srt" | "vtt" => "srt",
+        _ => return Err("Captions must be an .srt, .vtt or .ass file".to_string()),
+    };
+
+    let prepared = scratch_dir.join(format!("{}.{}", name, extension));
+    let prepared_str = prepared.to_string_lossy().to_string();
+
+    let mut cmd = command(ffmpeg_path);
+    cmd.arg("-y");
+    if let Some(start) = trim_start {
+        cmd.args(["-ss", &format!("{:.3}", start)]);
+    }
+    cmd.args(["-i", captions]);
+    if let Some(duration) = trim_duration {
+        cmd.args(["-t", &format!("{:.3}", duration)]);
+    }
+    cmd.arg(&prepared_str);
+    run(cmd, "Caption conversion").await?;
+    Ok(prepared)
+}
+
+/// Temporary file mux_tracks writes next to the export before replacing it
+pub fn muxed_path(output_path: &Path) -> PathBuf {
+    let extension = output_path.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default();
+    output_path.with_extension(format!("captions.{}", extension))
+}
+
+/// Add subtitle tracks to a finished export, without re-encoding. `tracks` point at
+/// prepared files; they come right after the video and audio, ahead of any subtitle
+/// streams the export already has.
+pub async fn mux_tracks(ffmpeg_path: &Path, output_path: &Path, tracks: &[SubtitleTrack]) -> Result<(), String> {
+    let extension = output_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let codec = |track: &SubtitleTrack| match extension.as_str() {
+        "mp4" | "mov" => Ok("mov_text"),
+        "webm" => Ok("webvtt"),
+        "mkv" if Path::new(&track.path).extension().is_some_and(|ext| ext == "ass") => Ok("ass"),
+        "mkv" => Ok("srt"),
+        _ => Err(format!("Can't add a subtitle track to .{} files", extension)),
+    };
+
+    let muxed = muxed_path(output_path);
+    let muxed_str = muxed.to_string_lossy().to_string();
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let mut cmd = command(ffmpeg_path);
+    cmd.args(["-y", "-i", &output_str]);
+    for track in tracks {
+        cmd.args(["-i", &track.path]);
+    }
+    cmd.args(["-map", "0:v?", "-map", "0:a?"]);
+    for input in 1..=tracks.len() {
+        cmd.args(["-map", &format!("{}:s", input)]);
+    }
+    cmd.args(["-map", "0:s?", "-map", "0:d?", "-map", "0:t?", "-c", "copy"]);
+    for (i, track) in tracks.iter().enumerate() {
+        cmd.args([&format!("-c:s:{}", i), codec(track)?]);
+        cmd.args([&format!("-disposition:s:{}", i), track.disposition()]);
+        if let Some(ref language) = track.language {
+            cmd.args([&format!("-metadata:s:s:{}", i), &format!("language={}", language)]);
+        }
+        if let Some(ref title) = track.title {
+            cmd.args([&format!("-metadata:s:s:{}", i), &format!("title={}", title)]);
+        }
+    }
+    cmd.args(["-map_metadata", "0"]);
+    if matches!(extension.as_str(), "mp4" | "mov") {
+        cmd.args(["-movflags", "+faststart"]);
+    }
+    cmd.arg(&muxed_str);
+    if let Err(e) = run(cmd, "Adding captions").await {
+        let _ = fs::remove_file(&muxed);
+        return Err(e);
+    }
+    fs::rename(&muxed, output_path).map_err(|e| format!("Failed to replace output: {}", e))
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// `file://` URI with everything but unreserved characters and slashes percent-encoded
+#[cfg(target_os = "linux")]
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'/' | b'-' | b'_' | b'.' | b'~') {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// The platform's clipboard tool, given the file the way file managers copy it
+fn clipboard_command(path: &Path) -> Result<(Command, Option<String>), String> {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = Command::new("powershell");
+        // -LiteralPath so brackets in file names aren't wildcards
+        let quoted = path.to_string_lossy().replace('\'', "''");
+        cmd.args(["-NoProfile", "-NonInteractive", "-Command", &format!("Set-Clipboard -LiteralPath '{}'", quoted)]);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        Ok((cmd, None))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // The path goes in as an argument, so it needs no AppleScript quoting
+        let mut cmd = Command::new("osascript");
+        cmd.args(["-e", "on run argv", "-e", "set the clipboard to (POSIX file (item 1 of argv))", "-e", "end run"]);
+        cmd.arg(path);
+        Ok((cmd, None))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let cmd = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut cmd = Command::new("wl-copy");
+            cmd.args(["--type", "text/uri-list"]);
+            cmd
+        } else {
+            let mut cmd = Command::new("xclip");
+            cmd.args(["-selection", "clipboard", "-target", "text/uri-list"]);
+            cmd
+        };
+        Ok((cmd, Some(format!("{}\r\n", file_uri(path)))))
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = path;
+        Err("Copying files to the clipboard isn't supported on this platform".to_string())
+    }
+}
+
+/// Put a file on the clipboard so it can be pasted into a chat or mail app. Linux
+/// needs wl-copy (Wayland) or xclip (X11) installed.
+pub async fn copy_file(path: &Path) -> Result<(), String> {
+    let (mut cmd, input) = clipboard_command(path)?;
+    cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() });
+    // wl-copy and xclip fork to keep serving the clipboard; a pipe they inherit would
+    // never close, so only the exit status is read
+    cmd.stdout(Stdio::null()).stderr(Stdio::null());
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to run the clipboard tool: {}", e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write to the clipboard tool: {}", e))?;
+    }
+    let status = child.wait().await.map_err(|e| format!("Failed to run the clipboard tool: {}", e))?;
+    if !status.success() {
+        return Err(format!("Copying to the clipboard failed ({})", status));
+    }
+    Ok(())
+}
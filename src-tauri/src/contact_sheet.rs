@@ -0,0 +1,147 @@
+use crate::ffmpeg::{get_ffmpeg_path, get_ffprobe_path, get_media_metadata, MediaMetadata};
+use crate::filters::escape_drawtext;
+use crate::output::resolve_output_path;
+use std::path::Path;
+use tokio::process::Command;
+
+const DEFAULT_COLUMNS: u32 = 5;
+const DEFAULT_ROWS: u32 = 6;
+
+/// Width of each thumbnail in the grid (pixels)
+const THUMB_WIDTH: u32 = 320;
+
+/// Height of the metadata header above the grid (pixels)
+const HEADER_HEIGHT: u32 = 96;
+
+/// Spacing between and around thumbnails (pixels)
+const TILE_PADDING: u32 = 4;
+
+/// Format seconds as h:mm:ss for thumbnail labels
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{}:{:02}:{:02}", total / 3600, (total / 60) % 60, total % 60)
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1024 * 1024 * 1024 {
+        format!("{:.2} GB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Header lines: file name, then duration / resolution / codecs / size
+fn header_lines(path: &str, meta: &MediaMetadata) -> Vec<String> {
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+
+    let mut details = vec![
+        format_timestamp(meta.duration),
+        format!("{}x{}", meta.width, meta.height),
+    ];
+    if let Some(ref codec) = meta.video_codec {
+        details.push(codec.clone());
+    }
+    if let Some(fps) = meta.frame_rate_decimal {
+        details.push(format!("{:.2} fps", fps));
+    }
+    if let Some(ref codec) = meta.audio_codec {
+        details.push(codec.clone());
+    }
+    if let Ok(m) = std::fs::metadata(path) {
+        details.push(format_size(m.len()));
+    }
+
+    vec![name, details.join("  |  ")]
+}
+
+/// Render a grid of evenly spaced thumbnails with timestamps under a metadata header.
+/// Each thumbnail is a separate fast-seeked input, so long files don't have to be decoded fully.
+pub async fn generate_contact_sheet(
+    app: &tauri::AppHandle,
+    path: &str,
+    output_name: &str,
+    columns: Option<u32>,
+    rows: Option<u32>,
+) -> Result<String, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+    let meta = get_media_metadata(&ffprobe, path).await?;
+
+    let columns = columns.unwrap_or(DEFAULT_COLUMNS).clamp(1, 12);
+    let rows = rows.unwrap_or(DEFAULT_ROWS).clamp(1, 12);
+    let count = columns * rows;
+
+    let location = resolve_output_path(app, path, output_name, true)?;
+    let output_str = location.path.to_string_lossy().to_string();
+
+    // Sample the middle of each slot so the first/last tiles aren't black fades
+    let interval = meta.duration / count as f64;
+    let timestamps: Vec<f64> = (0..count).map(|i| (i as f64 + 0.5) * interval).collect();
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+    for t in &timestamps {
+        args.extend([
+            "-ss".to_string(), format!("{:.3}", t),
+            "-i".to_string(), path.to_string(),
+        ]);
+    }
+
+    let mut graph: Vec<String> = timestamps
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            format!(
+                "[{}:v:0]trim=end_frame=1,setpts=PTS-STARTPTS,scale={}:-2,setsar=1,drawtext=text={}:expansion=none:fontsize=h/10:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=4:x=w-text_w-6:y=h-text_h-6[t{}]",
+                i,
+                THUMB_WIDTH,
+                escape_drawtext(&format_timestamp(*t)),
+                i
+            )
+        })
+        .collect();
+
+    let inputs: String = (0..count).map(|i| format!("[t{}]", i)).collect();
+    let lines = header_lines(path, &meta);
+    graph.push(format!(
+        "{}concat=n={}:v=1:a=0,tile={}x{}:padding={}:margin={}:color=0x202020,pad=iw:ih+{}:0:{}:color=0x202020,drawtext=text={}:expansion=none:fontsize=28:fontcolor=white:x={}:y=16,drawtext=text={}:expansion=none:fontsize=20:fontcolor=0xc0c0c0:x={}:y=56",
+        inputs,
+        count,
+        columns,
+        rows,
+        TILE_PADDING,
+        TILE_PADDING,
+        HEADER_HEIGHT,
+        HEADER_HEIGHT,
+        escape_drawtext(&lines[0]),
+        TILE_PADDING * 3,
+        escape_drawtext(&lines[1]),
+        TILE_PADDING * 3,
+    ));
+
+    args.extend([
+        "-filter_complex".to_string(), graph.join(";"),
+        "-frames:v".to_string(), "1".to_string(),
+        "-q:v".to_string(), "3".to_string(),
+        output_str.clone(),
+    ]);
+
+    let mut cmd = Command::new(&ffmpeg);
+    cmd.args(&args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to generate contact sheet: {}", stderr.lines().last().unwrap_or("").trim()));
+    }
+
+    Ok(output_str)
+}
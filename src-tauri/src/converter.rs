@@ -1,14 +1,35 @@
 #![allow(unused_imports)]
 
-use crate::ffmpeg::{get_ffmpeg_path, get_ffprobe_path, get_video_info, run_ffmpeg_with_progress};
+use crate::benchmark;
+use crate::cache::{file_cache_key, fnv1a_hash};
+use crate::capabilities;
+use crate::captions::{self, SubtitleTrack};
+use crate::ffmpeg::{get_audio_streams, get_chapter_starts, get_ffmpeg_path, get_ffprobe_path, get_format_tags, get_gifski_path, get_hdr_metadata, get_media_metadata, get_video_info, keyframe_before, parallel_slot, run_ffmpeg_parallel, run_ffmpeg_with_progress, AudioStreamInfo, HdrMetadata, MediaMetadata, VideoInfo};
+use crate::destinations::{self, BaseOutput, ConstraintCheck, PlatformFixups};
+use crate::filters::{self, SpeedRamp};
+use crate::gifski;
+use crate::history;
+use crate::output::{move_file, remove_partial_output, resolve_output_path, ERROR_OUTPUT_NOT_WRITABLE};
+use crate::job_journal::{self, JournaledJob};
+use crate::job_log;
+use crate::resources::{self, JobResources};
+use crate::multitrack;
+use crate::poster;
+use crate::power;
+use crate::scratch;
+use crate::tier_hints;
+use crate::undo;
+use crate::vmaf;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
 use tauri::Emitter;
 use tokio::process::Command;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ConversionResult {
     pub success: bool,
     #[serde(rename = "outputPath")]
@@ -16,6 +37,41 @@ pub struct ConversionResult {
     #[serde(rename = "outputSize")]
     pub output_size: Option<u64>,
     pub error: Option<String>,
+    /// Machine-readable reason for failures the UI can act on (e.g. "output_not_writable")
+    #[serde(rename = "errorCode")]
+    pub error_code: Option<String>,
+    /// Non-fatal notes about how the conversion was carried out
+    pub warnings: Vec<String>,
+    /// Number of encodes it took to get under the target (strict mode or overshoot retries)
+    pub attempts: Option<u32>,
+    /// Pass/fail of each upload constraint when a platform was selected
+    #[serde(rename = "platformChecks")]
+    pub platform_checks: Option<Vec<ConstraintCheck>>,
+    /// Changes made to meet the platform's format requirements (e.g. "pixel format yuv420p")
+    pub fixups: Vec<String>,
+    /// Chapters found in the output compared with the markers requested
+    pub chapters: Option<ChapterVerification>,
+    /// Every file written, for jobs with more than one output (outputPath is the first)
+    #[serde(rename = "outputPaths")]
+    pub output_paths: Vec<String>,
+    /// CPU time, peak memory and GPU load of the job's ffmpeg runs
+    pub resources: Option<JobResources>,
+    /// Set when the target leaves too few bits per pixel for watchable video
+    #[serde(rename = "qualityWarning")]
+    pub quality_warning: Option<QualityWarning>,
+    /// Size tier the output was encoded at (webp, gif, apng)
+    #[serde(skip)]
+    pub tier: Option<usize>,
+}
+
+/// Outcome of probing an output's chapters after muxing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChapterVerification {
+    pub requested: usize,
+    pub written: usize,
+    /// Requested chapter starts (seconds) with no chapter in the output
+    pub missing: Vec<f64>,
+    pub ok: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +81,275 @@ pub struct Marker {
     pub name: Option<String>,
 }
 
+/// Optional per-job settings that don't warrant their own command parameter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversionOptions {
+    /// Render each marker's name on screen for a few seconds (for platforms that strip chapters)
+    #[serde(rename = "burnInTitles")]
+    pub burn_in_titles: bool,
+    /// Limit input reading to this multiple of realtime (ffmpeg -readrate), to spare NAS links / busy HDDs
+    #[serde(rename = "readRate")]
+    pub read_rate: Option<f64>,
+    /// Write to the fallback folder instead of failing when the source folder is read-only
+    #[serde(rename = "outputFallback")]
+    pub output_fallback: bool,
+    /// Strip timestamps/encoder tags so identical inputs produce byte-identical outputs
+    pub deterministic: bool,
+    /// MKV only: keep every audio and subtitle stream (with language/disposition) instead of one track
+    #[serde(rename = "preserveAllStreams")]
+    pub preserve_all_streams: bool,
+    /// Default/forced flags for output audio/subtitle streams, so players pick the intended track
+    pub dispositions: Vec<StreamDisposition>,
+    /// Guarantee the output never exceeds the target size, re-encoding smaller if needed
+    #[serde(rename = "strictSize")]
+    pub strict_size: bool,
+    /// Faster presets and fewer encoder threads to save battery; unset picks it
+    /// automatically when running on battery power
+    pub efficiency: Option<bool>,
+    /// Shift audio by this many milliseconds to fix sync (positive delays the audio)
+    #[serde(rename = "audioOffsetMs")]
+    pub audio_offset_ms: Option<i64>,
+    /// Keyframed playback speed (e.g. slow-mo highlights); empty keeps normal speed
+    #[serde(rename = "speedCurve")]
+    pub speed_curve: Vec<SpeedPoint>,
+    /// Destination preset the output is meant for (e.g. "twitter"): caps the target at
+    /// its size limit, rejects formats and lengths it won't take, fixes up resolution,
+    /// frame rate and pixel format, and checks the finished file against it
+    pub platform: Option<String>,
+    /// Name of the user preset the job was started from, kept with the encode settings
+    #[serde(rename = "presetName")]
+    pub preset_name: Option<String>,
+    /// Audio bitrate (kbps). Audio-only exports encode at it instead of filling the target
+    /// size; video exports budget it out of the target instead of 128k
+    #[serde(rename = "audioBitrate")]
+    pub audio_bitrate: Option<u32>,
+    /// Video exports: "aac", "opus" or "copy" (passthrough) instead of the container's
+    /// default audio codec
+    #[serde(rename = "audioCodec")]
+    pub audio_codec: Option<String>,
+    /// mp4_hevc: keep an HDR10/HLG source's colour signalling and mastering metadata
+    /// (10-bit output) instead of encoding it as washed-out SDR
+    #[serde(rename = "hdrPassthrough")]
+    pub hdr_passthrough: bool,
+    /// Subtitle file (.srt, .vtt or .ass) on the source's timeline, e.g. from generate_captions
+    pub captions: Option<String>,
+    /// "burn" (default) draws the captions into the video; "track" adds a subtitle track
+    #[serde(rename = "captionMode")]
+    pub caption_mode: Option<String>,
+    /// More subtitle files muxed as their own tracks (e.g. translations), with language
+    /// tags and default/forced flags; never burned in
+    #[serde(rename = "subtitleTracks")]
+    pub subtitle_tracks: Vec<SubtitleTrack>,
+    /// GIF only: "ffmpeg" (default, palettegen/paletteuse) or "gifski", which keeps
+    /// gradients cleaner at the same size; falls back to ffmpeg when gifski isn't installed
+    #[serde(rename = "gifEncoder")]
+    pub gif_encoder: Option<String>,
+    /// Audio-only exports: downmix to mono, which halves the bitrate voice content needs
+    pub mono: bool,
+    /// mov_prores: "proxy", "lt", "standard", "hq" (default), "4444" or "4444xq"
+    #[serde(rename = "proresProfile")]
+    pub prores_profile: Option<String>,
+    /// mov_dnxhr: "lb", "sq" or "hq" (default)
+    #[serde(rename = "dnxhrProfile")]
+    pub dnxhr_profile: Option<String>,
+    /// Mix these audio tracks into the output's single audio track instead of using only
+    /// the first one (e.g. game audio and mic from a multi-track recording)
+    #[serde(rename = "audioMix")]
+    pub audio_mix: Vec<TrackGain>,
+    /// Multi-track recordings: "mic", "game" or "mix", resolved to an audio mix from the
+    /// track names; ignored when audio_mix is given
+    #[serde(rename = "audioPreset")]
+    pub audio_preset: Option<String>,
+    /// mp4_hevc: 8 (default) or 10; 10-bit (Main10) avoids banding in dark gradients
+    #[serde(rename = "bitDepth")]
+    pub bit_depth: Option<u32>,
+    /// Web video types: scale down to at most this height (e.g. 720) instead of 1080
+    #[serde(rename = "maxHeight")]
+    pub max_height: Option<u32>,
+    /// Web video types: never scale below the 1080p/maxHeight ceiling, even when the
+    /// target is too tight for a sharp picture at that size
+    #[serde(rename = "keepResolution")]
+    pub keep_resolution: bool,
+    /// Scaler used when downscaling: "lanczos" (default), "spline" or "bicubic"
+    #[serde(rename = "scaleAlgorithm")]
+    pub scale_algorithm: Option<String>,
+    /// Fill for padded bars (e.g. DVD letterboxing): a color such as "black" or
+    /// "#1a1a1a", or "blur" for a blurred copy of the video
+    #[serde(rename = "padBackground")]
+    pub pad_background: Option<String>,
+    /// NVIDIA GPU for NVENC encodes and decodes, as numbered by nvidia-smi (ffmpeg runs
+    /// with CUDA_DEVICE_ORDER=PCI_BUS_ID to match); lets multi-GPU systems keep the
+    /// primary card free
+    #[serde(rename = "gpuIndex")]
+    pub gpu_index: Option<u32>,
+    /// Thumbnail for file managers: "sidecar" writes a .jpg poster next to the export,
+    /// "embed" puts it in the container (MKV, MP4, MOV), "both" does both
+    pub poster: Option<String>,
+    /// NVENC preset, "p1" (fastest) to "p7" (best); unset uses p7 (p4 in efficiency mode)
+    #[serde(rename = "nvencPreset")]
+    pub nvenc_preset: Option<String>,
+    /// NVENC rate control: "vbr" (default) or "cbr" for a constant bitrate
+    #[serde(rename = "nvencRateControl")]
+    pub nvenc_rate_control: Option<String>,
+    /// NVENC spatial adaptive quantization: more bits for flat areas within a frame
+    #[serde(rename = "nvencSpatialAq")]
+    pub nvenc_spatial_aq: bool,
+    /// NVENC temporal adaptive quantization: more bits for static areas across frames
+    #[serde(rename = "nvencTemporalAq")]
+    pub nvenc_temporal_aq: bool,
+    /// NVENC rate-control lookahead in frames (up to 32, 0 turns it off); unset uses 20
+    #[serde(rename = "nvencLookahead")]
+    pub nvenc_lookahead: Option<u32>,
+    /// Video exports: drop the frame rate (60 to 30, 50 to 25, 30 to 24) when the target
+    /// is too tight for a sharp picture, before lowering the resolution
+    #[serde(rename = "reduceFrameRate")]
+    pub reduce_frame_rate: bool,
+    /// Frame rate reduceFrameRate settled on for this job; set by the converter
+    #[serde(skip)]
+    pub reduced_fps: Option<f64>,
+    /// First size tier to try (webp, gif, apng); strict mode's retries set it past the
+    /// tier that overshot
+    #[serde(skip)]
+    pub min_tier: usize,
+    /// Video bitrate (bits per second) targetVmaf's probes chose; set by the converter
+    #[serde(skip)]
+    pub vmaf_bitrate: Option<f64>,
+    /// Video encodes more than this fraction over the target (default 0.02; always 0 when
+    /// a platform's limit set the target) are redone with a bitrate corrected by the overshoot
+    #[serde(rename = "overshootMargin")]
+    pub overshoot_margin: Option<f64>,
+    /// Encodes a video conversion gets to land within the margin (default 3; 1 disables retries)
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: Option<u32>,
+    /// H.264/HEVC: encode at a CRF (NVENC: -cq) capped at the target bitrate instead of
+    /// filling the target exactly, so simple clips come out smaller. Other encoders fill
+    /// the target, with a warning.
+    #[serde(rename = "constrainedQuality")]
+    pub constrained_quality: bool,
+    /// CRF for constrained-quality mode (0-51); unset uses 23 for H.264, 28 for HEVC
+    pub crf: Option<u32>,
+    /// Video types: encode at the lowest bitrate whose probe samples reach this VMAF
+    /// (e.g. 93), with the target size as the ceiling; needs ffmpeg with libvmaf
+    #[serde(rename = "targetVmaf")]
+    pub target_vmaf: Option<f64>,
+}
+
+/// One source audio track in a remix, with its volume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackGain {
+    /// Audio track index (0 = first audio track)
+    pub track: u32,
+    /// Linear volume, 1.0 unchanged
+    pub volume: f64,
+}
+
+/// One keyframe of a speed curve; the speed holds until the next keyframe
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedPoint {
+    /// Seconds from the start of the (trimmed) clip
+    pub time: f64,
+    /// Playback rate, e.g. 0.5 for half-speed slow motion
+    pub speed: f64,
+}
+
+impl ConversionOptions {
+    fn efficient(&self) -> bool {
+        self.efficiency == Some(true)
+    }
+
+    fn burns_captions(&self) -> bool {
+        self.caption_mode.as_deref() != Some("track")
+    }
+}
+
+/// Disposition override for one output stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamDisposition {
+    /// Output stream specifier, e.g. "a:0" or "s:1"
+    pub stream: String,
+    /// "+"-separated flags such as "default", "forced", "default+forced", or "0" to clear
+    pub disposition: String,
+}
+
+/// Prefix marking a container comment tag as torchio encode settings
+const SETTINGS_TAG_PREFIX: &str = "torchio:";
+
+/// Everything needed to reproduce an export, embedded in the output's comment tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncodeSettings {
+    #[serde(rename = "appVersion")]
+    pub app_version: String,
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    #[serde(rename = "trimStart")]
+    pub trim_start: Option<f64>,
+    #[serde(rename = "trimDuration")]
+    pub trim_duration: Option<f64>,
+    pub options: ConversionOptions,
+    /// Hash of the parameters above (excluding app version and preset name) for quick comparison
+    #[serde(rename = "paramsHash")]
+    pub params_hash: String,
+}
+
+impl EncodeSettings {
+    fn new(
+        conversion_type: &str,
+        target_bytes: u64,
+        trim_start: Option<f64>,
+        trim_duration: Option<f64>,
+        options: &ConversionOptions,
+    ) -> Self {
+        // Renaming a preset doesn't change what it encodes
+        let hashed_options = ConversionOptions { preset_name: None, ..options.clone() };
+        let params = serde_json::json!({
+            "conversionType": conversion_type,
+            "targetBytes": target_bytes,
+            "trimStart": trim_start,
+            "trimDuration": trim_duration,
+            "options": hashed_options,
+        });
+        let params_hash = format!("{:016x}", fnv1a_hash(params.to_string().as_bytes()));
+
+        EncodeSettings {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            conversion_type: conversion_type.to_string(),
+            target_bytes,
+            trim_start,
+            trim_duration,
+            options: options.clone(),
+            params_hash,
+        }
+    }
+
+    /// Output args that write these settings into the container comment tag
+    fn metadata_args(&self) -> Vec<String> {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        vec![
+            "-metadata".to_string(),
+            format!("comment={}{}", SETTINGS_TAG_PREFIX, json),
+        ]
+    }
+}
+
+/// Read back the encode settings embedded in a previous torchio export
+pub async fn read_encode_settings(ffprobe_path: &PathBuf, input: &str) -> Result<EncodeSettings, String> {
+    let tags = get_format_tags(ffprobe_path, input).await?;
+    let comment = tags
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("comment"))
+        .map(|(_, value)| value.as_str())
+        .unwrap_or("");
+
+    let json = comment
+        .strip_prefix(SETTINGS_TAG_PREFIX)
+        .ok_or("File has no torchio encode settings")?;
+
+    serde_json::from_str(json).map_err(|e| format!("Failed to parse encode settings: {}", e))
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct ProgressPayload {
     id: String,
@@ -32,9 +357,8 @@ struct ProgressPayload {
     status: String,
 }
 
-// Cache for NVENC availability checks
-static NVENC_H264_AVAILABLE: OnceLock<bool> = OnceLock::new();
-static NVENC_HEVC_AVAILABLE: OnceLock<bool> = OnceLock::new();
+/// Progress status while a failed GPU encode is redone on the CPU
+const CPU_FALLBACK_STATUS: &str = "falling back to cpu";
 
 fn emit_progress(app: &tauri::AppHandle, id: &str, progress: f64, status: &str) {
     let _ = app.emit(
@@ -47,48 +371,45 @@ fn emit_progress(app: &tauri::AppHandle, id: &str, progress: f64, status: &str)
     );
 }
 
-async fn check_nvenc_h264_available(ffmpeg_path: &PathBuf) -> bool {
-    if let Some(&available) = NVENC_H264_AVAILABLE.get() {
-        return available;
-    }
-
-    let output = Command::new(ffmpeg_path)
-        .args(["-hide_banner", "-encoders"])
-        .output()
-        .await;
+/// NVENC checks run a test encode on the GPU the job will use: the encoders are listed
+/// by any build with NVENC support, but fail without an NVIDIA GPU or with an old
+/// driver (e.g. in a VM)
+async fn check_nvenc_h264_available(ffmpeg_path: &PathBuf, options: &ConversionOptions) -> bool {
+    capabilities::nvenc_works(ffmpeg_path, "h264_nvenc", options.gpu_index).await
+}
 
-    let available = match output {
-        Ok(out) => {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            stdout.contains("h264_nvenc")
-        }
-        Err(_) => false,
-    };
+async fn check_nvenc_hevc_available(ffmpeg_path: &PathBuf, options: &ConversionOptions) -> bool {
+    capabilities::nvenc_works(ffmpeg_path, "hevc_nvenc", options.gpu_index).await
+}
 
-    let _ = NVENC_H264_AVAILABLE.set(available);
-    available
+/// Only RTX 40-series and newer GPUs can run av1_nvenc; encode failures still fall back
+/// to SVT-AV1
+async fn check_nvenc_av1_available(ffmpeg_path: &PathBuf, options: &ConversionOptions) -> bool {
+    capabilities::nvenc_works(ffmpeg_path, "av1_nvenc", options.gpu_index).await
 }
 
-async fn check_nvenc_hevc_available(ffmpeg_path: &PathBuf) -> bool {
-    if let Some(&available) = NVENC_HEVC_AVAILABLE.get() {
-        return available;
-    }
+/// Whether a GPU encoder may be tried: not when a benchmark run with this ffmpeg and
+/// driver recommended another encoder for the codec, e.g. libx264 because NVENC scored
+/// worse on this card. Without a benchmark, NVENC, then VAAPI, then the CPU.
+fn benchmark_allows(recommended: Option<&str>, encoder: &str) -> bool {
+    recommended.is_none_or(|recommended| recommended == encoder)
+}
 
-    let output = Command::new(ffmpeg_path)
-        .args(["-hide_banner", "-encoders"])
-        .output()
-        .await;
+/// VAAPI render node `encoder` passed a test encode on (Intel/AMD GPUs on Linux)
+async fn check_vaapi_available(ffmpeg_path: &PathBuf, encoder: &str) -> Option<PathBuf> {
+    capabilities::vaapi_device(ffmpeg_path, encoder).await
+}
 
-    let available = match output {
-        Ok(out) => {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            stdout.contains("hevc_nvenc")
+/// Escape the characters FFMETADATA gives meaning to ('=', ';', '#', '\\' and newlines)
+fn escape_metadata_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
         }
-        Err(_) => false,
-    };
-
-    let _ = NVENC_HEVC_AVAILABLE.set(available);
-    available
+        escaped.push(c);
+    }
+    escaped
 }
 
 /// Generate FFmetadata file content for MKV chapters
@@ -121,12 +442,63 @@ fn generate_chapter_metadata(markers: &[Marker], total_duration: f64) -> String
         content.push_str("TIMEBASE=1/1000\n");
         content.push_str(&format!("START={}\n", start_ms));
         content.push_str(&format!("END={}\n", end_ms));
-        content.push_str(&format!("title={}\n\n", title));
+        content.push_str(&format!("title={}\n\n", escape_metadata_value(&title)));
     }
 
     content
 }
 
+/// Write the chapter metadata into the job's workspace, which deletes it with the job
+fn write_chapter_file(workspace: &scratch::JobWorkspace, markers: &[Marker], total_duration: f64) -> Result<PathBuf, String> {
+    let path = workspace.file("chapters.txt");
+    fs::write(&path, generate_chapter_metadata(markers, total_duration))
+        .map_err(|e| format!("Failed to write chapter metadata: {}", e))?;
+    Ok(path)
+}
+
+/// Chapter starts are written in milliseconds and may be rounded by the muxer
+const CHAPTER_TOLERANCE: f64 = 0.01;
+
+/// Probe the output and check every requested chapter made it in at the right time;
+/// metadata mapping mistakes drop chapters without any ffmpeg error
+async fn verify_chapters(ffprobe: &PathBuf, output: &str, markers: &[Marker]) -> Result<ChapterVerification, String> {
+    let written = get_chapter_starts(ffprobe, output).await?;
+    let missing: Vec<f64> = markers
+        .iter()
+        .map(|m| m.time)
+        .filter(|&time| !written.iter().any(|&start| (start - time).abs() <= CHAPTER_TOLERANCE))
+        .collect();
+
+    Ok(ChapterVerification {
+        requested: markers.len(),
+        written: written.len(),
+        ok: missing.is_empty() && written.len() == markers.len(),
+        missing,
+    })
+}
+
+/// verify_chapters for a finished encode: problems, including a probe that fails, become
+/// warnings, since the encode itself succeeded and its output must be kept
+async fn check_chapters(ffprobe: &PathBuf, output: &str, markers: &[Marker], warnings: &mut Vec<String>) -> Option<ChapterVerification> {
+    match verify_chapters(ffprobe, output, markers).await {
+        Ok(verification) => {
+            if !verification.ok {
+                warnings.push(format!(
+                    "Output has {} chapters but {} were requested ({} missing)",
+                    verification.written,
+                    verification.requested,
+                    verification.missing.len()
+                ));
+            }
+            Some(verification)
+        }
+        Err(e) => {
+            warnings.push(format!("Could not verify chapters: {}", e));
+            None
+        }
+    }
+}
+
 /// Adjust markers relative to trim start (for chapters in trimmed video)
 fn adjust_markers_for_trim(markers: &[Marker], trim_start: Option<f64>, trim_duration: Option<f64>) -> Vec<Marker> {
     let start = trim_start.unwrap_or(0.0);
@@ -143,639 +515,5046 @@ fn adjust_markers_for_trim(markers: &[Marker], trim_start: Option<f64>, trim_dur
         .collect()
 }
 
-pub async fn convert_file_impl(
-    app: tauri::AppHandle,
-    id: String,
-    input_path: String,
-    output_name: String,
-    target_bytes: u64,
-    conversion_type: String,
-    trim_start: Option<f64>,
-    trim_duration: Option<f64>,
-    markers: Option<Vec<Marker>>,
-) -> Result<ConversionResult, String> {
-    let result = match conversion_type.as_str() {
-        // Video formats - H.264
-        "mp4" | "mov" => convert_video_h264(&app, &id, &input_path, &output_name, target_bytes, trim_start, trim_duration, None).await,
-        // MKV with optional chapters
-        "mkv" => convert_video_h264(&app, &id, &input_path, &output_name, target_bytes, trim_start, trim_duration, markers).await,
-        // Video format - H.265/HEVC
-        "mp4_hevc" => convert_video_hevc(&app, &id, &input_path, &output_name, target_bytes, trim_start, trim_duration).await,
-        // Animated image formats
-        "webp" => convert_to_webp(&app, &id, &input_path, &output_name, target_bytes, trim_start, trim_duration).await,
-        "gif" => convert_to_gif(&app, &id, &input_path, &output_name, target_bytes, trim_start, trim_duration).await,
-        _ => Err(format!("Unknown conversion type: {}", conversion_type)),
-    };
-
-    match result {
-        Ok(r) => Ok(r),
-        Err(e) => Ok(ConversionResult {
-            success: false,
-            output_path: None,
-            output_size: None,
-            error: Some(e),
-        }),
+/// Input-side args that throttle how fast ffmpeg reads the source
+fn read_rate_args(options: &ConversionOptions) -> Vec<String> {
+    match options.read_rate {
+        Some(rate) if rate > 0.0 => vec!["-readrate".to_string(), format!("{}", rate)],
+        _ => Vec::new(),
     }
 }
 
-async fn convert_video_h264(
-    app: &tauri::AppHandle,
-    id: &str,
-    input_path: &str,
-    output_name: &str,
-    target_bytes: u64,
-    trim_start: Option<f64>,
-    trim_duration: Option<f64>,
-    markers: Option<Vec<Marker>>,
-) -> Result<ConversionResult, String> {
-    let ffmpeg = get_ffmpeg_path(app);
-    let ffprobe = get_ffprobe_path(app);
+/// Output audio bitrate used when audio is transcoded
+const AUDIO_BITRATE: f64 = 128_000.0;
 
-    emit_progress(app, id, 0.0, "analyzing");
+/// Lowest bitrate a transcoded track is given, whatever the source's
+const MIN_AUDIO_BITRATE: f64 = 32_000.0;
 
-    // Get video info
-    let info = get_video_info(&ffprobe, input_path).await?;
+/// Bitrate a single transcoded audio track comes out at: AUDIO_BITRATE, or the source's
+/// own when lower, since re-encoding can't add back what it doesn't have. Zero for silent
+/// sources, so they don't reserve budget for audio they'll never have.
+fn transcoded_audio_bitrate(streams: &[AudioStreamInfo]) -> f64 {
+    if streams.is_empty() {
+        return 0.0;
+    }
+    // ffmpeg picks one track by itself; budget for the richest
+    match streams.iter().map(|s| s.bitrate).collect::<Option<Vec<u64>>>() {
+        Some(bitrates) => bitrates
+            .into_iter()
+            .max()
+            .map_or(AUDIO_BITRATE, |b| (b as f64).clamp(MIN_AUDIO_BITRATE, AUDIO_BITRATE)),
+        // A track without a bitrate (common in MKV) could be anything
+        None => AUDIO_BITRATE,
+    }
+}
 
-    // Use trim duration if provided, otherwise use full video duration
-    let effective_duration = trim_duration.unwrap_or(info.duration);
+/// Bitrate of an audio mix: a full-rate track, unless there's nothing to mix
+fn mixed_audio_bitrate(streams: &[AudioStreamInfo]) -> f64 {
+    if streams.is_empty() { 0.0 } else { AUDIO_BITRATE }
+}
 
-    // Check for NVENC H.264 support
-    let use_nvenc = check_nvenc_h264_available(&ffmpeg).await;
+/// -b:a for a track budgeted below AUDIO_BITRATE; the encoders' own args ask for the full rate
+fn audio_bitrate_args(audio_bitrate: f64) -> Vec<String> {
+    if audio_bitrate > 0.0 && audio_bitrate < AUDIO_BITRATE {
+        vec!["-b:a".to_string(), format!("{}k", (audio_bitrate / 1000.0) as u32)]
+    } else {
+        Vec::new()
+    }
+}
 
-    // Calculate target bitrate based on effective duration
-    let audio_bitrate = 128_000.0; // 128 kbps for audio
-    let total_bitrate = (target_bytes as f64 * 8.0) / effective_duration;
-    let video_bitrate = (total_bitrate - audio_bitrate).max(100_000.0);
+/// Values of the audioCodec option
+const AUDIO_CODECS: &[&str] = &["aac", "opus", "copy"];
 
-    // Convert to kbps for ffmpeg
-    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+/// audioBitrate range (kbps) for the audio of a video export
+const VIDEO_AUDIO_BITRATE_RANGE_K: (u32, u32) = (16, 512);
 
-    // Build output path using the provided output_name
-    let input_pathbuf = PathBuf::from(input_path);
-    let parent = input_pathbuf.parent().unwrap_or(&input_pathbuf);
-    let output_path = parent.join(output_name);
-    let output_str = output_path.to_string_lossy().to_string();
+/// A copied track that doesn't report its bitrate is budgeted this high; it could be anything
+const UNKNOWN_COPIED_AUDIO_BITRATE: f64 = 256_000.0;
 
-    // Determine scaling - cap at 1080p for web optimization
-    let scale_filter = if info.height > 1080 {
-        "scale=-2:1080"
-    } else if info.width > 1920 {
-        "scale=1920:-2"
-    } else {
-        "scale=trunc(iw/2)*2:trunc(ih/2)*2"
-    };
+/// Video exports whose audio codec and bitrate can be picked per job
+fn picks_audio(conversion_type: &str) -> bool {
+    matches!(
+        conversion_type,
+        "mp4" | "mov" | "mkv" | "ts" | "mp4_hevc" | "mp4_av1" | "webm_av1" | "webm_vp9" | "webm_vp8"
+    )
+}
 
-    // Prepare chapter metadata for MKV if markers provided
-    let metadata_path = if let Some(ref mkrs) = markers {
-        if !mkrs.is_empty() && output_name.ends_with(".mkv") {
-            // Adjust markers for trim and generate metadata
-            let adjusted = adjust_markers_for_trim(mkrs, trim_start, trim_duration);
-            if !adjusted.is_empty() {
-                let metadata = generate_chapter_metadata(&adjusted, effective_duration);
-                let temp_dir = std::env::temp_dir();
-                let meta_file = temp_dir.join(format!("chapters_{}.txt", id));
-                fs::write(&meta_file, &metadata).map_err(|e| format!("Failed to write chapter metadata: {}", e))?;
-                Some(meta_file)
-            } else {
-                None
+/// Check the audioCodec and audioBitrate options against the conversion type and the
+/// other options before any encode starts
+fn validate_audio_choice(conversion_type: &str, options: &ConversionOptions) -> Result<(), String> {
+    let codec = options.audio_codec.as_deref();
+    if codec.is_none() && (options.audio_bitrate.is_none() || !picks_audio(conversion_type)) {
+        return Ok(());
+    }
+    if let Some(codec) = codec {
+        if !AUDIO_CODECS.contains(&codec) {
+            return Err(format!("Unknown audio codec: {} (expected one of {})", codec, AUDIO_CODECS.join(", ")));
+        }
+        if !picks_audio(conversion_type) {
+            return Err(format!("The audio codec can't be picked for {}", conversion_type));
+        }
+    }
+    if conversion_type == "mkv" && options.preserve_all_streams {
+        return Err("Preserving all streams keeps each track's own audio settings".to_string());
+    }
+    match codec {
+        Some("opus") if conversion_type == "mov" => return Err("MOV can't carry Opus audio".to_string()),
+        Some("aac") if conversion_type.starts_with("webm") => return Err("WebM can't carry AAC audio".to_string()),
+        // Set-top players get a stereo downmix, which needs a re-encode
+        Some("copy") if conversion_type == "ts" => return Err("TS exports downmix audio to stereo, so it can't be copied".to_string()),
+        Some("copy") => {
+            if options.audio_bitrate.is_some() {
+                return Err("Copied audio keeps the source's bitrate".to_string());
+            }
+            let filtered = !options.speed_curve.is_empty()
+                || !options.audio_mix.is_empty()
+                || options.audio_preset.is_some()
+                || options.audio_offset_ms.is_some();
+            if filtered {
+                return Err("Audio can't be copied through a speed curve, track mix or sync offset".to_string());
             }
-        } else {
-            None
         }
-    } else {
-        None
-    };
+        _ => {}
+    }
+    let (min, max) = VIDEO_AUDIO_BITRATE_RANGE_K;
+    if options.audio_bitrate.is_some_and(|kbps| !(min..=max).contains(&kbps)) {
+        return Err(format!("Audio bitrate must be between {}k and {}k", min, max));
+    }
+    Ok(())
+}
 
-    emit_progress(app, id, 5.0, "converting");
+/// Whether a conversion type's container can carry a copied track of this audio codec;
+/// Matroska takes anything
+fn container_carries_audio(conversion_type: &str, codec: &str) -> bool {
+    match output_extension(conversion_type) {
+        "webm" => matches!(codec, "opus" | "vorbis"),
+        "mp4" => matches!(codec, "aac" | "mp3" | "alac" | "ac3" | "eac3" | "opus" | "flac"),
+        "mov" => codec.starts_with("pcm_") || matches!(codec, "aac" | "mp3" | "alac" | "ac3" | "eac3"),
+        _ => true,
+    }
+}
 
-    if use_nvenc {
-        // NVENC single-pass encoding (faster, uses GPU)
-        convert_video_nvenc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, scale_filter, trim_start, trim_duration, metadata_path.as_ref()).await?;
-    } else {
-        // CPU two-pass encoding (slower, better quality per bit)
-        convert_video_x264(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, scale_filter, trim_start, trim_duration, metadata_path.as_ref()).await?;
+/// audioCodec "copy": every source track (ffmpeg picks one by itself) has to fit the
+/// output container as it is
+async fn validate_audio_copy(ffprobe: &PathBuf, input_path: &str, conversion_type: &str) -> Result<(), String> {
+    let streams = get_audio_streams(ffprobe, input_path).await?;
+    for stream in &streams {
+        let codec = stream.codec.as_deref().unwrap_or("unknown");
+        if !container_carries_audio(conversion_type, codec) {
+            return Err(format!(
+                "The source's {} audio can't be copied into {}; re-encode it instead",
+                codec,
+                output_extension(conversion_type).to_uppercase()
+            ));
+        }
     }
+    Ok(())
+}
 
-    // Clean up temp metadata file
-    if let Some(ref meta_file) = metadata_path {
-        let _ = fs::remove_file(meta_file);
+/// Audio args for the audioCodec and audioBitrate options, placed after the encoder's own
+/// (the later -c:a and -b:a win), and the bitrate the track takes out of the budget.
+/// Without either option this is `default_bitrate`, as the path budgeted it.
+fn chosen_audio_args(options: &ConversionOptions, streams: &[AudioStreamInfo], default_bitrate: f64) -> (Vec<String>, f64) {
+    if streams.is_empty() {
+        return (Vec::new(), 0.0);
+    }
+    if options.audio_codec.is_none() && options.audio_bitrate.is_none() {
+        return (audio_bitrate_args(default_bitrate), default_bitrate);
     }
 
-    // Get output file size
-    let output_size = fs::metadata(&output_path)
-        .map(|m| m.len())
-        .unwrap_or(0);
+    let mut args = Vec::new();
+    match options.audio_codec.as_deref() {
+        Some("copy") => {
+            // ffmpeg picks one track by itself; budget for the richest
+            let bitrate = match streams.iter().map(|s| s.bitrate).collect::<Option<Vec<u64>>>() {
+                Some(bitrates) => bitrates.into_iter().max().map_or(UNKNOWN_COPIED_AUDIO_BITRATE, |b| b as f64),
+                None => UNKNOWN_COPIED_AUDIO_BITRATE,
+            };
+            return (vec!["-c:a".to_string(), "copy".to_string()], bitrate);
+        }
+        Some("opus") => args.extend(["-c:a".to_string(), "libopus".to_string()]),
+        Some("aac") => args.extend(["-c:a".to_string(), "aac".to_string()]),
+        _ => {}
+    }
+    let bitrate = options.audio_bitrate.map_or(default_bitrate, |kbps| kbps as f64 * 1000.0);
+    args.extend(["-b:a".to_string(), format!("{}k", (bitrate / 1000.0) as u32)]);
+    (args, bitrate)
+}
 
-    emit_progress(app, id, 100.0, "completed");
+/// When preserving all streams, audio at or below this bitrate is copied instead of transcoded
+const PRESERVE_COPY_MAX_BITRATE: u64 = 192_000;
+
+/// Stream mapping for archival MKV exports: first video, every audio and subtitle stream.
+/// Returns the args and the total audio bitrate they will produce (for the size budget).
+/// Tracks are only copied when `allow_copy` is set, since filtered audio can't be stream-copied.
+fn preserve_streams_args(streams: &[AudioStreamInfo], allow_copy: bool) -> (Vec<String>, f64) {
+    let mut args: Vec<String> = [
+        "-map", "0:v:0",
+        "-map", "0:a?",
+        "-map", "0:s?",
+        "-c:s", "copy",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    let mut audio_bitrate = 0.0;
+    for stream in streams {
+        match stream.bitrate {
+            // Already within budget: copy untouched (other tracks fall back to -c:a aac)
+            Some(bitrate) if allow_copy && bitrate <= PRESERVE_COPY_MAX_BITRATE => {
+                args.extend([format!("-c:a:{}", stream.index), "copy".to_string()]);
+                audio_bitrate += bitrate as f64;
+            }
+            _ => audio_bitrate += AUDIO_BITRATE,
+        }
+    }
 
-    Ok(ConversionResult {
-        success: true,
-        output_path: Some(output_str),
-        output_size: Some(output_size),
-        error: None,
-    })
+    (args, audio_bitrate)
 }
 
-async fn convert_video_hevc(
-    app: &tauri::AppHandle,
-    id: &str,
-    input_path: &str,
-    output_name: &str,
-    target_bytes: u64,
-    trim_start: Option<f64>,
-    trim_duration: Option<f64>,
-) -> Result<ConversionResult, String> {
-    let ffmpeg = get_ffmpeg_path(app);
-    let ffprobe = get_ffprobe_path(app);
+/// Disposition flags accepted from the frontend
+const DISPOSITION_FLAGS: &[&str] = &[
+    "default", "forced", "dub", "original", "comment", "lyrics", "karaoke",
+    "hearing_impaired", "visual_impaired", "captions", "descriptions",
+];
 
-    emit_progress(app, id, 0.0, "analyzing");
+/// Build -disposition args, rejecting malformed specifiers before they reach ffmpeg
+fn disposition_args(dispositions: &[StreamDisposition]) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
 
-    let info = get_video_info(&ffprobe, input_path).await?;
-    let effective_duration = trim_duration.unwrap_or(info.duration);
+    for d in dispositions {
+        let valid_stream = match d.stream.split_once(':') {
+            Some((kind, index)) => matches!(kind, "v" | "a" | "s") && !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()),
+            None => false,
+        };
+        if !valid_stream {
+            return Err(format!("Invalid stream specifier for disposition: {}", d.stream));
+        }
 
-    // Check for NVENC HEVC support
-    let use_nvenc = check_nvenc_hevc_available(&ffmpeg).await;
+        let valid_flags = d.disposition == "0"
+            || d.disposition.split('+').all(|flag| DISPOSITION_FLAGS.contains(&flag));
+        if !valid_flags {
+            return Err(format!("Invalid disposition: {}", d.disposition));
+        }
 
-    // Calculate target bitrate - HEVC is ~25% more efficient
-    let audio_bitrate = 128_000.0;
-    let total_bitrate = (target_bytes as f64 * 8.0) / effective_duration;
-    let video_bitrate = (total_bitrate - audio_bitrate).max(100_000.0);
-    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+        args.push(format!("-disposition:{}", d.stream));
+        args.push(d.disposition.clone());
+    }
 
-    let input_pathbuf = PathBuf::from(input_path);
-    let parent = input_pathbuf.parent().unwrap_or(&input_pathbuf);
-    let output_path = parent.join(output_name);
-    let output_str = output_path.to_string_lossy().to_string();
+    Ok(args)
+}
 
-    let scale_filter = if info.height > 1080 {
-        "scale=-2:1080"
-    } else if info.width > 1920 {
-        "scale=1920:-2"
-    } else {
-        "scale=trunc(iw/2)*2:trunc(ih/2)*2"
-    };
+/// Pick the encoder preset for normal or efficiency mode
+fn encoder_preset(options: &ConversionOptions, normal: &str, efficient: &str) -> String {
+    if options.efficient() { efficient } else { normal }.to_string()
+}
 
-    emit_progress(app, id, 5.0, "converting");
+/// Values of the nvencPreset and nvencRateControl options
+const NVENC_PRESETS: &[&str] = &["p1", "p2", "p3", "p4", "p5", "p6", "p7"];
+const NVENC_RATE_CONTROLS: &[&str] = &["vbr", "cbr"];
 
-    if use_nvenc {
-        convert_video_nvenc_hevc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, scale_filter, trim_start, trim_duration).await?;
-    } else {
-        convert_video_x265(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, scale_filter, trim_start, trim_duration).await?;
+/// Most frames NVENC looks ahead for rate control
+const NVENC_MAX_LOOKAHEAD: u32 = 32;
+
+/// Lookahead without the nvencLookahead option: enough for VBR to see a scene change
+/// coming and save bits for it
+const NVENC_DEFAULT_LOOKAHEAD: u32 = 20;
+
+/// Check the NVENC tuning options before any encode starts
+fn validate_nvenc_tuning(options: &ConversionOptions) -> Result<(), String> {
+    if let Some(preset) = options.nvenc_preset.as_deref() {
+        if !NVENC_PRESETS.contains(&preset) {
+            return Err(format!("Unknown NVENC preset: {} (expected p1 to p7)", preset));
+        }
     }
+    if let Some(rc) = options.nvenc_rate_control.as_deref() {
+        if !NVENC_RATE_CONTROLS.contains(&rc) {
+            return Err(format!(
+                "Unknown NVENC rate control: {} (expected one of {})",
+                rc,
+                NVENC_RATE_CONTROLS.join(", ")
+            ));
+        }
+    }
+    if options.constrained_quality && options.nvenc_rate_control.as_deref() == Some("cbr") {
+        return Err("Constrained quality needs NVENC rate control vbr, not cbr".to_string());
+    }
+    if options.nvenc_lookahead.is_some_and(|frames| frames > NVENC_MAX_LOOKAHEAD) {
+        return Err(format!("NVENC lookahead can be at most {} frames", NVENC_MAX_LOOKAHEAD));
+    }
+    Ok(())
+}
 
-    let output_size = fs::metadata(&output_path)
-        .map(|m| m.len())
-        .unwrap_or(0);
+/// Preset, tune and rate-control args for the bitrate-targeted NVENC encoders; without
+/// tuning options this is p7 (p4 in efficiency mode), hq, VBR with lookahead.
+/// `-multipass fullres` (qres in efficiency mode) is per-frame: each frame is analysed
+/// before it's encoded, all in one encode. Unlike x264's two passes it never sees the
+/// whole clip, so the lookahead is what lets it save bits for what's coming.
+fn nvenc_tuning_args(options: &ConversionOptions) -> Vec<String> {
+    let preset = options
+        .nvenc_preset
+        .clone()
+        .unwrap_or_else(|| encoder_preset(options, "p7", "p4"));
+    let rc = options.nvenc_rate_control.as_deref().unwrap_or("vbr");
+    let mut args = vec![
+        "-preset".to_string(), preset,
+        "-tune".to_string(), "hq".to_string(),
+        "-rc".to_string(), rc.to_string(),
+        "-multipass".to_string(), encoder_preset(options, "fullres", "qres"),
+    ];
+    if options.nvenc_spatial_aq {
+        args.extend(["-spatial-aq".to_string(), "1".to_string()]);
+    }
+    if options.nvenc_temporal_aq {
+        args.extend(["-temporal-aq".to_string(), "1".to_string()]);
+    }
+    let lookahead = options.nvenc_lookahead.unwrap_or(NVENC_DEFAULT_LOOKAHEAD);
+    args.extend(["-rc-lookahead".to_string(), lookahead.to_string()]);
+    args
+}
 
-    emit_progress(app, id, 100.0, "completed");
+/// In efficiency mode, limit CPU encoders to half the cores (at least two)
+fn efficiency_thread_args(options: &ConversionOptions) -> Vec<String> {
+    if !options.efficient() {
+        return Vec::new();
+    }
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+    vec!["-threads".to_string(), (cores / 2).max(2).to_string()]
+}
 
-    Ok(ConversionResult {
-        success: true,
-        output_path: Some(output_str),
-        output_size: Some(output_size),
-        error: None,
-    })
+/// Output args that remove per-run variation: muxer creation times, encoder version tags
+/// and random container UIDs (bitexact mode)
+fn deterministic_args() -> Vec<String> {
+    [
+        "-fflags", "+bitexact",
+        "-flags:v", "+bitexact",
+        "-flags:a", "+bitexact",
+        "-metadata", "creation_time=",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
 }
 
-async fn convert_video_nvenc(
-    app: &tauri::AppHandle,
-    id: &str,
-    input_path: &str,
-    output_str: &str,
-    ffmpeg: &PathBuf,
-    effective_duration: f64,
-    video_bitrate_k: u32,
-    scale_filter: &str,
+/// Output args common to the video paths: embedded settings, stream dispositions
+/// and optional deterministic flags
+fn video_output_args(settings: &EncodeSettings, options: &ConversionOptions) -> Result<Vec<String>, String> {
+    let mut args = settings.metadata_args();
+    args.extend(disposition_args(&options.dispositions)?);
+    if options.deterministic {
+        args.extend(deterministic_args());
+    }
+    Ok(args)
+}
+
+/// Markers on the output timeline: adjusted for trim, then retimed by any speed ramp
+fn output_markers(
+    markers: Option<&[Marker]>,
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
-    metadata_path: Option<&PathBuf>,
-) -> Result<(), String> {
-    let app_clone = app.clone();
-    let id_clone = id.to_string();
+    ramp: Option<&SpeedRamp>,
+) -> Vec<Marker> {
+    let mut adjusted = markers
+        .map(|mkrs| adjust_markers_for_trim(mkrs, trim_start, trim_duration))
+        .unwrap_or_default();
+    if let Some(ramp) = ramp {
+        for marker in adjusted.iter_mut() {
+            marker.time = ramp.map_time(marker.time);
+        }
+    }
+    adjusted
+}
 
-    let bitrate_str = format!("{}k", video_bitrate_k);
-    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
-    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+/// Build the -vf chain: speed ramp retiming, scaling, then marker title cards if requested
+fn marker_video_filter(
+    scale_filter: &str,
+    markers: &[Marker],
+    ramp: Option<&SpeedRamp>,
+    options: &ConversionOptions,
+) -> String {
+    let base = match ramp {
+        Some(ramp) => format!("{},{}", ramp.setpts_filter(), scale_filter),
+        None => scale_filter.to_string(),
+    };
+    let rate = options.reduced_fps.map(|fps| format!("fps={}", fps));
+    let base = filters::chain(&base, rate.as_deref());
+    let titles = if options.burn_in_titles {
+        filters::marker_titles_filter(markers)
+    } else {
+        None
+    };
+    let captions = options
+        .captions
+        .as_deref()
+        .filter(|_| options.burns_captions())
+        .map(filters::subtitles_filter);
+    filters::chain(&filters::chain(&base, titles.as_deref()), captions.as_deref())
+}
 
-    // Build args with optional trim parameters
-    let mut args: Vec<String> = vec!["-y".to_string()];
+/// Codecs a conversion writes, as ffprobe names them, for checking against a platform
+/// preset before encoding; None where the type doesn't say
+fn planned_codecs(conversion_type: &str, options: &ConversionOptions, source: &MediaMetadata) -> (Option<String>, Option<String>) {
+    let video = match conversion_type {
+        "mp4" | "mov" | "mkv" | "ts" => Some("h264"),
+        "mp4_hevc" => Some("hevc"),
+        "mp4_av1" | "webm_av1" => Some("av1"),
+        "webm_vp9" => Some("vp9"),
+        "webm_vp8" => Some("vp8"),
+        "mov_prores" => Some("prores"),
+        "mov_dnxhr" => Some("dnxhd"),
+        "mpeg2" => Some("mpeg2video"),
+        "webp" | "gif" | "apng" => Some(conversion_type),
+        "remux" => source.video_codec.as_deref(),
+        _ => None,
+    };
+    let audio = match (options.audio_codec.as_deref(), conversion_type) {
+        _ if source.audio_codec.is_none() => None,
+        (Some("copy"), _) | (_, "remux") => source.audio_codec.as_deref(),
+        (Some("opus"), _) => Some("opus"),
+        (Some("aac"), _) => Some("aac"),
+        (_, "mp4" | "mov" | "mkv" | "ts" | "mp4_hevc" | "mp4_av1") => Some("aac"),
+        (_, "webm_av1" | "webm_vp9") => Some("opus"),
+        (_, "webm_vp8") => Some("vorbis"),
+        (_, "mpeg2") => Some("mp2"),
+        _ => None,
+    };
+    (video.map(str::to_string), audio.map(str::to_string))
+}
 
-    // Add trim start (seek) before input for fast seeking
-    if let Some(start) = trim_start {
-        args.push("-ss".to_string());
-        args.push(format!("{:.3}", start));
+/// Check the job against its platform preset before anything is encoded. Returns the
+/// target capped to the platform's size limit, with a note when it was lowered.
+async fn apply_platform_preset(
+    ffprobe: &PathBuf,
+    platform: &str,
+    input_path: &str,
+    extension: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> Result<(u64, Option<String>), String> {
+    let source = get_media_metadata(ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(source.duration - trim_start.unwrap_or(0.0)).max(0.0);
+    let duration = SpeedRamp::new(&options.speed_curve, source_duration)?.map_or(source_duration, |r| r.output_duration());
+    let (video_codec, audio_codec) = planned_codecs(conversion_type, options, &source);
+    let planned = destinations::PlannedOutput {
+        extension,
+        video_codec: video_codec.as_deref(),
+        audio_codec: audio_codec.as_deref(),
+        duration,
+    };
+    let capped = destinations::check_planned(platform, target_bytes, &planned)?;
+    let note = (capped < target_bytes).then(|| {
+        format!("Target lowered to the {} limit of {:.0} MB", platform, capped as f64 / (1024.0 * 1024.0))
+    });
+    Ok((capped, note))
+}
+
+/// Format fix-ups for the selected platform on the web video paths, for what their own
+/// scaling (web_scale_filter), frame rate choice and `pixel_format` (None when the
+/// encoder keeps the source's) leave unmet
+async fn platform_fixups(ffprobe: &PathBuf, input_path: &str, info: &VideoInfo, pixel_format: Option<&str>, options: &ConversionOptions) -> Result<PlatformFixups, String> {
+    match options.platform.as_deref() {
+        Some(platform) => {
+            let source = get_media_metadata(ffprobe, input_path).await?;
+            let (width, height) = web_output_dimensions(info.width, info.height, options.max_height);
+            let frame_rate = match (source.frame_rate_decimal, options.reduced_fps) {
+                (Some(fps), Some(reduced)) => Some(fps.min(reduced)),
+                (fps, reduced) => fps.or(reduced),
+            };
+            let pixel_format = pixel_format.or(source.pixel_format.as_deref()).unwrap_or_default();
+            destinations::platform_fixups(platform, &source, &BaseOutput { width, height, frame_rate, pixel_format })
+        }
+        None => Ok(PlatformFixups::default()),
     }
+}
 
-    args.push("-i".to_string());
-    args.push(input_path.to_string());
+/// Audio sync correction for outputs whose audio isn't routed through a filter_complex
+fn audio_offset_args(options: &ConversionOptions) -> Vec<String> {
+    match options.audio_offset_ms.and_then(filters::audio_offset_filter) {
+        Some(filter) => vec!["-af".to_string(), filter],
+        None => Vec::new(),
+    }
+}
+
+/// filter_complex graph producing the output audio as [aout]: the track mix if one was
+/// chosen, then the speed ramp and sync offset. None when the first track passes through
+/// a plain -af (or untouched).
+fn audio_filter_graph(options: &ConversionOptions, ramp: Option<&SpeedRamp>) -> Option<String> {
+    let offset = options.audio_offset_ms.and_then(filters::audio_offset_filter);
+    match (options.audio_mix.is_empty(), ramp) {
+        (true, None) => None,
+        (true, Some(ramp)) => Some(ramp.audio_graph("[0:a:0]", "aout", offset.as_deref())),
+        (false, None) => Some(filters::audio_mix_graph(&options.audio_mix, "aout", offset.as_deref())),
+        (false, Some(ramp)) => Some(format!(
+            "{};{}",
+            filters::audio_mix_graph(&options.audio_mix, "mix", None),
+            ramp.audio_graph("[mix]", "aout", offset.as_deref())
+        )),
+    }
+}
+
+/// Stream mapping that sends the audio through the speed ramp's filtergraph
+fn speed_ramp_args(ramp: &SpeedRamp, has_audio: bool, options: &ConversionOptions) -> Vec<String> {
+    let mut args = vec!["-map".to_string(), "0:v:0".to_string()];
+    if has_audio {
+        if let Some(graph) = audio_filter_graph(options, Some(ramp)) {
+            args.extend([
+                "-filter_complex".to_string(), graph,
+                "-map".to_string(), "[aout]".to_string(),
+            ]);
+        }
+    }
+    args
+}
+
+/// Audio args without a speed ramp: the track mix if one was chosen, else the sync offset
+fn audio_args(options: &ConversionOptions) -> Vec<String> {
+    match audio_filter_graph(options, None) {
+        Some(graph) => vec![
+            "-map".to_string(), "0:v:0".to_string(),
+            "-filter_complex".to_string(), graph,
+            "-map".to_string(), "[aout]".to_string(),
+        ],
+        None => audio_offset_args(options),
+    }
+}
+
+pub async fn convert_file_impl(
+    app: tauri::AppHandle,
+    id: String,
+    input_path: String,
+    output_name: String,
+    target_bytes: u64,
+    conversion_type: String,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: Option<ConversionOptions>,
+) -> Result<ConversionResult, String> {
+    job_log::clear(&id);
+    resources::clear(&id);
+    let mut options = options.unwrap_or_default();
+    let settings = EncodeSettings::new(&conversion_type, target_bytes, trim_start, trim_duration, &options);
+
+    // Efficiency mode follows the power source unless the caller chose explicitly
+    let auto_efficiency = options.efficiency.is_none() && power::on_battery().await;
+    if auto_efficiency {
+        options.efficiency = Some(true);
+    }
+
+    if let Some(algorithm) = options.scale_algorithm.as_deref() {
+        if !filters::SCALE_ALGORITHMS.contains(&algorithm) {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(format!(
+                    "Unknown scale algorithm: {} (expected one of {})",
+                    algorithm,
+                    filters::SCALE_ALGORITHMS.join(", ")
+                )),
+                ..Default::default()
+            });
+        }
+    }
+
+    if options.overshoot_margin.is_some_and(|margin| !margin.is_finite() || margin < 0.0) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some("Overshoot margin must be zero or more".to_string()),
+            ..Default::default()
+        });
+    }
+
+    if options.crf.is_some_and(|crf| crf > 51) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some("CRF must be between 0 and 51".to_string()),
+            ..Default::default()
+        });
+    }
+    // Only the H.264 and HEVC paths have a quality-targeted mode to cap
+    let constrained_note = (options.constrained_quality && !matches!(conversion_type.as_str(), "mp4" | "mov" | "mkv" | "ts" | "mp4_hevc"))
+        .then(|| format!("Constrained quality isn't available for {}; encoded to fill the target size", conversion_type));
+
+    if let Some(score) = options.target_vmaf {
+        let error = if !(vmaf::MIN_TARGET..=vmaf::MAX_TARGET).contains(&score) {
+            Some(format!("Target VMAF must be between {} and {}", vmaf::MIN_TARGET, vmaf::MAX_TARGET))
+        } else if vmaf::probe_encoder(&conversion_type).is_none() {
+            Some(format!("Target VMAF isn't supported for {}", conversion_type))
+        } else {
+            None
+        };
+        if let Some(error) = error {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(error),
+                ..Default::default()
+            });
+        }
+    }
+
+    if options.max_attempts == Some(0) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some("At least one attempt is needed".to_string()),
+            ..Default::default()
+        });
+    }
+
+    if let Err(e) = validate_nvenc_tuning(&options) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some(e),
+            ..Default::default()
+        });
+    }
+
+    if let Err(e) = validate_audio_choice(&conversion_type, &options) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some(e),
+            ..Default::default()
+        });
+    }
+    if options.audio_codec.as_deref() == Some("copy") {
+        if let Err(e) = validate_audio_copy(&get_ffprobe_path(&app), &input_path, &conversion_type).await {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(e),
+                ..Default::default()
+            });
+        }
+    }
+
+    if let Err(e) = filters::PadBackground::parse(options.pad_background.as_deref()) {
+        return Ok(ConversionResult {
+            success: false,
+            error: Some(e),
+            ..Default::default()
+        });
+    }
+
+    if let Some(mode) = options.poster.as_deref() {
+        if !poster::POSTER_MODES.contains(&mode) {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(format!("Unknown poster mode: {}", mode)),
+                ..Default::default()
+            });
+        }
+    }
+
+    // An audio preset becomes an explicit track mix for this file
+    if let (Some(preset), true) = (options.audio_preset.clone(), options.audio_mix.is_empty()) {
+        match multitrack::resolve_preset(&get_ffprobe_path(&app), &input_path, &preset).await {
+            Ok(mix) => options.audio_mix = mix,
+            Err(e) => {
+                return Ok(ConversionResult {
+                    success: false,
+                    error: Some(e),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    // Fail fast on protected destinations instead of after a full encode
+    let location = match resolve_output_path(&app, &input_path, &output_name, options.output_fallback) {
+        Ok(location) => location,
+        Err(e) => {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(e),
+                error_code: Some(ERROR_OUTPUT_NOT_WRITABLE.to_string()),
+                ..Default::default()
+            })
+        }
+    };
+    let output_path = location.path.as_path();
+
+    // Make room for this job's temporary files
+    scratch::enforce_quota(&app);
+
+    // A platform preset caps the target and must take the output's format and length
+    let mut preset_note = None;
+    let target_bytes = match options.platform.clone() {
+        Some(platform) => {
+            let ffprobe = get_ffprobe_path(&app);
+            match apply_platform_preset(&ffprobe, &platform, &input_path, &path_extension(output_path), &conversion_type, target_bytes, trim_start, trim_duration, &options).await {
+                Ok((bytes, note)) => {
+                    preset_note = note;
+                    bytes
+                }
+                Err(e) => {
+                    return Ok(ConversionResult {
+                        success: false,
+                        error: Some(e),
+                        ..Default::default()
+                    })
+                }
+            }
+        }
+        None => target_bytes,
+    };
+    // The platform rejects anything over its limit, so retries allow no overshoot
+    if preset_note.is_some() {
+        options.overshoot_margin = Some(0.0);
+    }
+
+    // Target VMAF: probe for the smallest size that reaches the score
+    let mut vmaf_note = None;
+    if let Some(score) = options.target_vmaf {
+        match vmaf_bitrate(&app, &id, &input_path, &conversion_type, target_bytes, trim_start, trim_duration, score, &options).await {
+            Ok((bitrate, note)) => {
+                options.vmaf_bitrate = Some(bitrate);
+                vmaf_note = Some(note);
+            }
+            Err(e) => {
+                return Ok(ConversionResult {
+                    success: false,
+                    error: Some(e),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    // Tight budgets get a smaller, sharper picture instead of a smeared full-size one
+    // (and, if allowed, fewer frames); a VMAF target was sized at the full resolution
+    let mut downscale_notes = Vec::new();
+    if options.target_vmaf.is_none() {
+        let (height, fps) = auto_caps(&get_ffprobe_path(&app), &input_path, &conversion_type, target_bytes, trim_start, trim_duration, &options).await;
+        if let Some(height) = height {
+            downscale_notes.push(format!("Scaled down to {}p to keep the picture sharp at this size", height));
+            options.max_height = Some(height);
+        }
+        if let Some(fps) = fps {
+            downscale_notes.push(format!("Frame rate reduced to {} fps to fit the target size", (fps * 1000.0).round() / 1000.0));
+            options.reduced_fps = Some(fps);
+        }
+    }
+
+    // Warn before a long encode that the target is too small to look good; a VMAF
+    // target sized itself by quality already
+    let quality_warning = if options.target_vmaf.is_none() {
+        check_quality_floor(&get_ffprobe_path(&app), &input_path, &conversion_type, target_bytes, trim_start, trim_duration, &options).await
+    } else {
+        None
+    };
+    if let Some(ref warning) = quality_warning {
+        let _ = app.emit(
+            "quality-warning",
+            QualityWarningPayload {
+                id: id.clone(),
+                warning: warning.clone(),
+            },
+        );
+    }
+
+    // Captions are cut to the trim range up front; burned in by the encode or muxed after it
+    let prepared = match prepare_captions(&app, &id, &conversion_type, trim_start, trim_duration, &options).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            return Ok(ConversionResult {
+                success: false,
+                error: Some(e),
+                ..Default::default()
+            })
+        }
+    };
+    if let Some(ref captions) = prepared.captions {
+        options.captions = Some(captions.to_string_lossy().to_string());
+    }
+
+    // From here on the job writes files; a crash leaves them for startup recovery
+    job_journal::started(&app, JournaledJob {
+        id: id.clone(),
+        input_path: input_path.clone(),
+        output_name: output_name.clone(),
+        output_path: output_path.to_string_lossy().to_string(),
+        conversion_type: conversion_type.clone(),
+        target_bytes,
+        trim_start,
+        trim_duration,
+        markers: markers.clone(),
+        options: settings.options.clone(),
+        started_at: job_journal::now_secs(),
+    });
+
+    // Keep a file that's about to be overwritten so the overwrite can be undone
+    let staged_existing = output_path.is_file() && undo::stage_overwrite(&app, output_path).await.is_ok();
+    if staged_existing {
+        job_journal::staged(&app, &id, output_path);
+    }
+    let started = SystemTime::now();
+
+    let mut result = if options.strict_size && targets_size(&conversion_type) {
+        convert_strict(&app, &id, &input_path, output_path, target_bytes, &conversion_type, trim_start, trim_duration, markers, &options, &settings).await
+    } else if retries_overshoot(&conversion_type) {
+        convert_with_retry(&app, &id, &input_path, output_path, target_bytes, &conversion_type, trim_start, trim_duration, markers, &options, &settings).await
+    } else {
+        convert_to_target(&app, &id, &input_path, output_path, target_bytes, &conversion_type, trim_start, trim_duration, markers, &options, &settings).await
+    };
+
+    let mut tracks = prepared.tracks.clone();
+    if let (Some(ref captions), false) = (&prepared.captions, options.burns_captions()) {
+        let track = SubtitleTrack { path: captions.to_string_lossy().to_string(), ..Default::default() };
+        tracks.insert(0, track);
+    }
+    if !tracks.is_empty() {
+        if let Ok(ref mut r) = result {
+            emit_progress(&app, &id, 100.0, "adding captions");
+            job_journal::created(&app, &id, &captions::muxed_path(output_path));
+            match captions::mux_tracks(&get_ffmpeg_path(&app), output_path, &tracks).await {
+                Ok(()) => r.output_size = fs::metadata(output_path).map(|m| m.len()).ok(),
+                Err(e) => r.warnings.push(format!("Exported without captions: {}", e)),
+            }
+        }
+    }
+    prepared.remove();
+
+    // An embedded poster went in with the encode (convert_to_target); the sidecar is
+    // written once, from the final output
+    if let (Some("sidecar" | "both"), true, Ok(ref mut r)) = (options.poster.as_deref(), takes_poster(&conversion_type), &mut result) {
+        if r.success {
+            emit_progress(&app, &id, 100.0, "adding poster");
+            match poster::write_sidecar(&app, &id, output_path).await {
+                Ok(sidecar) => {
+                    if r.output_paths.is_empty() {
+                        r.output_paths.push(output_path.to_string_lossy().to_string());
+                    }
+                    r.output_paths.push(sidecar.to_string_lossy().to_string());
+                }
+                Err(e) => r.warnings.push(format!("Exported without a poster file: {}", e)),
+            }
+        }
+    }
+
+    let outcome = match result {
+        Ok(mut r) => {
+            // A job that reports failure without erroring leaves the same mess as an error
+            if !r.success {
+                if remove_partial_output(output_path, started) {
+                    if let Some(ref mut e) = r.error {
+                        e.push_str(" (the incomplete output file was removed)");
+                    }
+                }
+                if staged_existing {
+                    let _ = undo::unstage_overwrite(&app, output_path);
+                }
+            }
+            if auto_efficiency {
+                r.warnings.push("Running on battery; used efficiency mode (faster presets, fewer threads)".to_string());
+            }
+            if let Some(note) = vmaf_note {
+                r.warnings.push(note);
+            }
+            if let Some(note) = preset_note {
+                r.warnings.push(note);
+            }
+            if let Some(note) = constrained_note {
+                r.warnings.push(note);
+            }
+            r.warnings.extend(downscale_notes);
+            r.quality_warning = quality_warning;
+            if let (true, Some(platform)) = (r.success, options.platform.as_deref()) {
+                let ffprobe = get_ffprobe_path(&app);
+                match destinations::validate_output(&ffprobe, output_path, platform, target_bytes).await {
+                    Ok(checks) => {
+                        let failed: Vec<&str> = checks.iter().filter(|c| !c.passed).map(|c| c.constraint.as_str()).collect();
+                        if !failed.is_empty() {
+                            r.warnings.push(format!("Output doesn't meet {} limits: {}", platform, failed.join(", ")));
+                        }
+                        r.platform_checks = Some(checks);
+                    }
+                    Err(e) => r.warnings.push(format!("Could not check {} limits: {}", platform, e)),
+                }
+            }
+            if let Some(dir) = location.fallback_dir {
+                r.warnings.push(format!(
+                    "Source folder is not writable; saved to {} instead",
+                    dir.to_string_lossy()
+                ));
+            }
+            r.resources = resources::take(&id);
+            if r.success {
+                history::record(&app, &input_path, output_path, r.output_size, &settings);
+            }
+            Ok(r)
+        }
+        Err(mut e) => {
+            // A half-written file with the final name gets shared by mistake
+            if remove_partial_output(output_path, started) {
+                e.push_str(" (the incomplete output file was removed)");
+            }
+            if staged_existing {
+                let _ = undo::unstage_overwrite(&app, output_path);
+            }
+            Ok(ConversionResult {
+                success: false,
+                error: Some(e),
+                resources: resources::take(&id),
+                ..Default::default()
+            })
+        }
+    };
+    job_journal::finished(&app, &id);
+    outcome
+}
+
+/// Caption and subtitle files cut to the exported range, in the scratch folder
+#[derive(Default)]
+struct PreparedCaptions {
+    captions: Option<PathBuf>,
+    /// subtitle_tracks with their paths pointing at the prepared copies
+    tracks: Vec<SubtitleTrack>,
+}
+
+impl PreparedCaptions {
+    fn remove(&self) {
+        if let Some(ref captions) = self.captions {
+            let _ = fs::remove_file(captions);
+        }
+        for track in &self.tracks {
+            let _ = fs::remove_file(&track.path);
+        }
+    }
+}
+
+/// Cut the job's caption files to the exported range in the scratch folder
+async fn prepare_captions(
+    app: &tauri::AppHandle,
+    id: &str,
+    conversion_type: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> Result<PreparedCaptions, String> {
+    if options.captions.is_none() && options.subtitle_tracks.is_empty() {
+        return Ok(PreparedCaptions::default());
+    }
+    match options.caption_mode.as_deref() {
+        None | Some("burn") | Some("track") => {}
+        Some(other) => return Err(format!("Unknown caption mode: {}", other)),
+    }
+    captions::validate_tracks(&options.subtitle_tracks)?;
+
+    // Burning needs a video encode; a subtitle track only needs a container that has them
+    let burns = options.captions.is_some() && options.burns_captions();
+    let adds_tracks = !options.subtitle_tracks.is_empty() || (options.captions.is_some() && !burns);
+    let supported = match conversion_type {
+        "mp4" | "mov" | "mkv" | "mp4_hevc" | "mp4_av1" | "webm_av1" | "webm_vp9" | "webm_vp8" | "mov_prores" | "mov_dnxhr" | "mp4_lossless" => true,
+        // MPEG-TS and DVD program streams only carry bitmap subtitles
+        "ts" | "mpeg2" => !adds_tracks,
+        "remux" => !burns,
+        _ => false,
+    };
+    if !supported {
+        return Err(format!("Captions can't be added to {} output", conversion_type));
+    }
+    if !options.speed_curve.is_empty() {
+        return Err("Captions can't be combined with speed ramps".to_string());
+    }
+
+    let ffmpeg = get_ffmpeg_path(app);
+    let scratch_dir = scratch::scratch_dir(app);
+    let mut prepared = PreparedCaptions::default();
+    if let Some(ref source) = options.captions {
+        let name = format!("captions_{}", id);
+        prepared.captions = Some(captions::prepare(&ffmpeg, &scratch_dir, &name, source, trim_start, trim_duration).await?);
+    }
+    for (i, track) in options.subtitle_tracks.iter().enumerate() {
+        let name = format!("subtitles_{}_{}", id, i);
+        match captions::prepare(&ffmpeg, &scratch_dir, &name, &track.path, trim_start, trim_duration).await {
+            Ok(path) => prepared.tracks.push(SubtitleTrack { path: path.to_string_lossy().to_string(), ..track.clone() }),
+            Err(e) => {
+                prepared.remove();
+                return Err(e);
+            }
+        }
+    }
+    Ok(prepared)
+}
+
+/// Whether the conversion type aims at target_bytes; lossless and intermediate formats
+/// have no size to tighten, so strict mode would only fail
+pub(crate) fn targets_size(conversion_type: &str) -> bool {
+    match conversion_type {
+        "mov_prores" | "mov_dnxhr" | "mp4_lossless" | "remux" | "split_av" => false,
+        _ => audio_format(conversion_type).is_none_or(|f| f.bitrate_range_k.is_some()),
+    }
+}
+
+/// Video bitrate floors: below these the encoders fall apart, so the target is exceeded instead
+const MIN_VIDEO_BITRATE_H26X: f64 = 100_000.0;
+const MIN_VIDEO_BITRATE_MODERN: f64 = 50_000.0;
+const MIN_VIDEO_BITRATE_MPEG2: f64 = 1_000_000.0;
+
+/// DVD limits: 9.8 Mbps is the spec's video ceiling, kept under so older players'
+/// buffers keep up; MP2 at 224k is what authoring tools default to
+const DVD_MAX_VIDEO_BITRATE: f64 = 8_000_000.0;
+const DVD_AUDIO_BITRATE: f64 = 224_000.0;
+
+/// The DVD video standard matching a source frame rate: PAL for 25/50 fps, else NTSC
+struct DvdStandard {
+    height: u32,
+    frame_rate: &'static str,
+    fps: f64,
+    gop: u32,
+}
+
+fn dvd_standard(fps: Option<f64>) -> DvdStandard {
+    let pal = fps.is_some_and(|fps| (fps - 25.0).abs() < 0.5 || (fps - 50.0).abs() < 0.5);
+    if pal {
+        DvdStandard { height: 576, frame_rate: "25", fps: 25.0, gop: 15 }
+    } else {
+        DvdStandard { height: 480, frame_rate: "30000/1001", fps: 29.97, gop: 18 }
+    }
+}
+
+/// mpeg2 video bitrate: the target's share after audio, between the floor and DVD's cap
+fn mpeg2_video_bitrate(target_bytes: u64, duration: f64) -> f64 {
+    video_bitrate(target_bytes, duration, DVD_AUDIO_BITRATE, MIN_VIDEO_BITRATE_MPEG2).min(DVD_MAX_VIDEO_BITRATE)
+}
+
+/// Bits per second left for video once audio is reserved, never below `floor`
+fn video_bitrate(target_bytes: u64, duration: f64, audio_bitrate: f64, floor: f64) -> f64 {
+    let total_bitrate = (target_bytes as f64 * 8.0) / duration;
+    (total_bitrate - audio_bitrate).max(floor)
+}
+
+/// Video bitrate for an encode: the one a target VMAF settled on or, without one, what
+/// the target size leaves
+fn encode_video_bitrate(options: &ConversionOptions, target_bytes: u64, duration: f64, audio_bitrate: f64, floor: f64) -> f64 {
+    options
+        .vmaf_bitrate
+        .unwrap_or_else(|| video_bitrate(target_bytes, duration, audio_bitrate, floor))
+}
+
+/// Largest output of the web video paths: 1080p (or 1920 wide), or a lower height
+/// with the matching 16:9 width
+fn web_size_cap(max_height: Option<u32>) -> (u32, u32) {
+    let height = max_height.map_or(1080, |h| h.clamp(2, 1080)) / 2 * 2;
+    let width = (height as f64 * 16.0 / 9.0 / 2.0).round() as u32 * 2;
+    (width, height)
+}
+
+/// Scaling for the web video paths - cap at 1080p (or 1920 wide) unless a lower
+/// maxHeight is given (or chosen by budget_caps), even dimensions
+fn web_scale_filter(width: u32, height: u32, options: &ConversionOptions) -> String {
+    let (max_width, max_height) = web_size_cap(options.max_height);
+    let flags = filters::downscale_flags(options.scale_algorithm.as_deref());
+    if height > max_height {
+        format!("scale=-2:{}:{}", max_height, flags)
+    } else if width > max_width {
+        format!("scale={}:-2:{}", max_width, flags)
+    } else {
+        "scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string()
+    }
+}
+
+/// Output dimensions web_scale_filter produces
+fn web_output_dimensions(width: u32, height: u32, max_height: Option<u32>) -> (u32, u32) {
+    let (max_width, max_height) = web_size_cap(max_height);
+    let even = |v: f64| (v / 2.0).round() as u32 * 2;
+    if height > max_height {
+        (even(width as f64 * max_height as f64 / height as f64), max_height)
+    } else if width > max_width {
+        (max_width, even(height as f64 * max_width as f64 / width as f64))
+    } else {
+        (width / 2 * 2, height / 2 * 2)
+    }
+}
+
+/// Bits per pixel per frame below which output looks poor, and above which it looks good,
+/// for H.264; newer codecs get the same quality from fewer bits
+const POOR_BITS_PER_PIXEL: f64 = 0.05;
+const GOOD_BITS_PER_PIXEL: f64 = 0.1;
+
+/// The resolution choosers (budget_caps, destinations::recommend) step down until the
+/// picture gets this many bits per pixel (H.264 equivalent); a smaller sharp picture
+/// beats a larger smeared one
+pub(crate) const MIN_SCALED_BITS_PER_PIXEL: f64 = 0.07;
+
+/// Lowest height the resolution chooser goes to
+const MIN_AUTO_HEIGHT: u32 = 360;
+
+/// Frame rates reduceFrameRate drops to when they divide the source's rate evenly
+const REDUCED_FRAME_RATES: &[f64] = &[60.0, 50.0, 30.0, 25.0, 24.0];
+
+/// Frame rates a `fps` source can be reduced to, from best: the REDUCED_FRAME_RATES it
+/// divides into (60 to 30, 50 to 25, 59.94 to 29.97), so every nth frame is kept and
+/// motion stays even, plus 30/29.97 to 24/23.976. PAL's 25 is never dropped to 24.
+pub(crate) fn reduced_frame_rates(fps: f64) -> Vec<f64> {
+    let mut rates: Vec<f64> = (2..=5)
+        .map(|divisor| fps / divisor as f64)
+        .filter(|rate| REDUCED_FRAME_RATES.contains(&rate.round()))
+        .collect();
+    // Dropping one frame in five, the usual NTSC-to-film step
+    if fps.round() == 30.0 {
+        rates.push(fps * 0.8);
+    }
+    rates
+}
+
+/// Height cap and frame rate for a tight budget: the largest of COMMON_HEIGHTS under the
+/// 1080p/maxHeight ceiling, and with `reduce_fps` the fastest of reduced_frame_rates(fps),
+/// at which `video_bitrate` keeps MIN_SCALED_BITS_PER_PIXEL; the smallest and
+/// slowest when none does. Resolution is kept before frame rate, as for destinations:
+/// 1080p30 beats 720p60. None for whatever the ceiling and source rate already manage;
+/// `keep_resolution` leaves the ceiling as it is and only tries frame rates.
+fn budget_caps(
+    width: u32,
+    height: u32,
+    max_height: Option<u32>,
+    keep_resolution: bool,
+    fps: f64,
+    reduce_fps: bool,
+    video_bitrate: f64,
+    efficiency: f64,
+) -> (Option<u32>, Option<f64>) {
+    let ceiling = web_output_dimensions(width, height, max_height);
+    if ceiling.0 == 0 || ceiling.1 == 0 {
+        return (None, None);
+    }
+    let bits_per_pixel = |(w, h): (u32, u32), rate: f64| video_bitrate / (w as f64 * h as f64 * rate) * efficiency;
+
+    let mut sizes = vec![(None, ceiling)];
+    if !keep_resolution {
+        sizes.extend(
+            COMMON_HEIGHTS
+                .iter()
+                .filter(|&&h| h >= MIN_AUTO_HEIGHT && h < ceiling.1)
+                .map(|&h| (Some(h), web_output_dimensions(width, height, Some(h)))),
+        );
+    }
+    let mut rates = vec![None];
+    if reduce_fps {
+        rates.extend(reduced_frame_rates(fps).into_iter().map(Some));
+    }
+
+    for &(cap, dimensions) in &sizes {
+        for &rate in &rates {
+            if bits_per_pixel(dimensions, rate.unwrap_or(fps)) >= MIN_SCALED_BITS_PER_PIXEL {
+                return (cap, rate);
+            }
+        }
+    }
+    (sizes.last().and_then(|(cap, _)| *cap), rates.last().copied().flatten())
+}
+
+fn codec_efficiency(conversion_type: &str) -> f64 {
+    match conversion_type {
+        "mp4_hevc" => 1.35,
+        "mp4_av1" | "webm_av1" => 1.6,
+        "webm_vp9" => 1.3,
+        "mpeg2" => 0.5,
+        _ => 1.0,
+    }
+}
+
+/// Inputs for calculate_target; the same numbers convert_file would see
+#[derive(Debug, Clone, Deserialize)]
+pub struct TargetParams {
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    /// Output duration in seconds (after trim)
+    pub duration: f64,
+    pub width: u32,
+    pub height: u32,
+    pub fps: Option<f64>,
+    /// The audioBitrate conversion option
+    #[serde(rename = "audioBitrate", default)]
+    pub audio_bitrate: Option<u32>,
+    /// The maxHeight conversion option
+    #[serde(rename = "maxHeight", default)]
+    pub max_height: Option<u32>,
+    /// The keepResolution conversion option
+    #[serde(rename = "keepResolution", default)]
+    pub keep_resolution: bool,
+    /// The reduceFrameRate conversion option
+    #[serde(rename = "reduceFrameRate", default)]
+    pub reduce_frame_rate: bool,
+}
+
+/// What the converter would do for a target, for live feedback while the size is adjusted
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetCalculation {
+    /// Bits per second the target allows in total
+    #[serde(rename = "totalBitrate")]
+    pub total_bitrate: f64,
+    #[serde(rename = "videoBitrate")]
+    pub video_bitrate: f64,
+    #[serde(rename = "audioBitrate")]
+    pub audio_bitrate: f64,
+    /// The target is below the encoder's floor, so the output will be larger than asked
+    pub floored: bool,
+    /// Expected output size; None when it doesn't follow from a bitrate (lossless, animated tiers)
+    #[serde(rename = "estimatedBytes")]
+    pub estimated_bytes: Option<u64>,
+    pub width: u32,
+    pub height: u32,
+    /// Output frame rate, when it's known
+    pub fps: Option<f64>,
+    #[serde(rename = "bitsPerPixel")]
+    pub bits_per_pixel: Option<f64>,
+    /// "poor", "ok" or "good"; "lossless" for formats that don't target a size;
+    /// None for animated formats, which find their tier by trial encodes
+    pub quality: Option<String>,
+}
+
+fn quality_label(score: f64, poor: f64, good: f64) -> String {
+    if score < poor {
+        "poor"
+    } else if score < good {
+        "ok"
+    } else {
+        "good"
+    }
+    .to_string()
+}
+
+/// Run the converter's bitrate math without encoding
+pub fn calculate_target(params: &TargetParams) -> Result<TargetCalculation, String> {
+    let conversion_type = params.conversion_type.as_str();
+    if !CONVERSION_TYPES.contains(&conversion_type) {
+        return Err(format!("Unknown conversion type: {}", conversion_type));
+    }
+    if params.duration <= 0.0 {
+        return Err("Duration must be positive".to_string());
+    }
+
+    let total_bitrate = params.target_bytes as f64 * 8.0 / params.duration;
+    let mut calc = TargetCalculation {
+        total_bitrate,
+        video_bitrate: 0.0,
+        audio_bitrate: 0.0,
+        floored: false,
+        estimated_bytes: None,
+        width: params.width,
+        height: params.height,
+        fps: None,
+        bits_per_pixel: None,
+        quality: None,
+    };
+
+    if !targets_size(conversion_type) {
+        calc.quality = Some("lossless".to_string());
+        return Ok(calc);
+    }
+
+    if let Some(format) = audio_format(conversion_type) {
+        let (min, max) = format.bitrate_range_k.unwrap_or((0, u32::MAX));
+        let wanted_k = params.audio_bitrate.unwrap_or((total_bitrate / 1000.0) as u32);
+        let bitrate_k = wanted_k.clamp(min, max);
+        calc.audio_bitrate = bitrate_k as f64 * 1000.0;
+        calc.floored = wanted_k < min;
+        calc.estimated_bytes = Some((calc.audio_bitrate * params.duration / 8.0) as u64);
+        calc.width = 0;
+        calc.height = 0;
+        calc.quality = Some(quality_label(bitrate_k as f64, 48.0, 96.0));
+        return Ok(calc);
+    }
+
+    let floor = match conversion_type {
+        "mp4" | "mov" | "mkv" | "ts" | "mp4_hevc" | "webm_vp8" => MIN_VIDEO_BITRATE_H26X,
+        "mp4_av1" | "webm_av1" | "webm_vp9" => MIN_VIDEO_BITRATE_MODERN,
+        "mpeg2" => MIN_VIDEO_BITRATE_MPEG2,
+        // Animated formats walk a ladder of size/fps tiers chosen by trial encodes
+        _ => return Ok(calc),
+    };
+
+    // DVD output has fixed dimensions and frame rate and a capped bitrate
+    let (audio_bitrate, video_bitrate, (width, height), fps) = if conversion_type == "mpeg2" {
+        let dvd = dvd_standard(params.fps);
+        (DVD_AUDIO_BITRATE, mpeg2_video_bitrate(params.target_bytes, params.duration), (720, dvd.height), dvd.fps)
+    } else {
+        let audio_bitrate = params.audio_bitrate.map_or(AUDIO_BITRATE, |kbps| kbps as f64 * 1000.0);
+        let video_bitrate = video_bitrate(params.target_bytes, params.duration, audio_bitrate, floor);
+        let fps = params.fps.unwrap_or(30.0);
+        // Tight budgets get a smaller picture (and a lower frame rate), as convert_file does
+        let (cap, rate) = budget_caps(
+            params.width,
+            params.height,
+            params.max_height,
+            params.keep_resolution,
+            fps,
+            params.reduce_frame_rate,
+            video_bitrate,
+            codec_efficiency(conversion_type),
+        );
+        (
+            audio_bitrate,
+            video_bitrate,
+            web_output_dimensions(params.width, params.height, cap.or(params.max_height)),
+            rate.unwrap_or(fps),
+        )
+    };
+    calc.audio_bitrate = audio_bitrate;
+    calc.video_bitrate = video_bitrate;
+    calc.floored = total_bitrate - audio_bitrate < floor;
+    calc.estimated_bytes = Some(((calc.video_bitrate + audio_bitrate) * params.duration / 8.0) as u64);
+
+    calc.width = width;
+    calc.height = height;
+    calc.fps = Some(fps);
+    if width > 0 && height > 0 {
+        let bpp = calc.video_bitrate / (width as f64 * height as f64 * fps);
+        calc.bits_per_pixel = Some(bpp);
+        calc.quality = Some(quality_label(
+            bpp * codec_efficiency(conversion_type),
+            POOR_BITS_PER_PIXEL,
+            GOOD_BITS_PER_PIXEL,
+        ));
+    }
+    Ok(calc)
+}
+
+/// calculate_target's inputs for a real source: its dimensions and frame rate, and the
+/// output length after trim and speed curve
+async fn source_target_params(
+    ffprobe: &PathBuf,
+    input_path: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> Result<TargetParams, String> {
+    let source = get_media_metadata(ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(source.duration - trim_start.unwrap_or(0.0)).max(0.0);
+    let duration = SpeedRamp::new(&options.speed_curve, source_duration)?.map_or(source_duration, |r| r.output_duration());
+    // The bitrate-targeted video types split the target as their encoders will
+    let (target_bytes, audio_bitrate) = if matches!(conversion_type, "mp4" | "mov" | "mkv" | "ts" | "mp4_hevc" | "mp4_av1" | "webm_av1" | "webm_vp9" | "webm_vp8") {
+        let streams = get_audio_streams(ffprobe, input_path).await.unwrap_or_default();
+        let budget = stream_budget(ffprobe, input_path, output_extension(conversion_type), target_bytes, duration, options, &streams, 0).await;
+        (budget.target_bytes, Some((budget.audio_bitrate / 1000.0).round() as u32))
+    } else {
+        (target_bytes, options.audio_bitrate)
+    };
+    Ok(TargetParams {
+        conversion_type: conversion_type.to_string(),
+        target_bytes,
+        duration,
+        width: source.width,
+        height: source.height,
+        fps: match (source.frame_rate_decimal, options.reduced_fps) {
+            (Some(fps), Some(reduced)) => Some(fps.min(reduced)),
+            (fps, _) => fps,
+        },
+        audio_bitrate,
+        max_height: options.max_height,
+        keep_resolution: options.keep_resolution,
+        reduce_frame_rate: options.reduce_frame_rate,
+    })
+}
+
+/// Common output heights, from best to smallest
+const COMMON_HEIGHTS: &[u32] = &[1080, 720, 540, 480, 360, 240];
+
+/// The target leaves too few bits per pixel for watchable video, with what would fix it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityWarning {
+    /// Bits per pixel per frame the video will get, scaled to H.264 equivalent
+    #[serde(rename = "bitsPerPixel")]
+    pub bits_per_pixel: f64,
+    /// The floor it fell below
+    #[serde(rename = "minBitsPerPixel")]
+    pub min_bits_per_pixel: f64,
+    /// Largest common height that clears the floor at this target; None if even 240p doesn't
+    #[serde(rename = "suggestedHeight")]
+    pub suggested_height: Option<u32>,
+    /// Longest output (seconds) that clears the floor at this target and resolution
+    #[serde(rename = "maxDuration")]
+    pub max_duration: f64,
+    /// Smallest target that clears the floor at this resolution and length
+    #[serde(rename = "minTargetBytes")]
+    pub min_target_bytes: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct QualityWarningPayload {
+    id: String,
+    #[serde(flatten)]
+    warning: QualityWarning,
+}
+
+/// Height cap and frame rate budget_caps picks for this job, if the budget is too tight
+/// for the 1080p/maxHeight ceiling and the source's rate. Web video types only; DVD
+/// output has a fixed size and rate.
+async fn auto_caps(
+    ffprobe: &PathBuf,
+    input_path: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> (Option<u32>, Option<f64>) {
+    if conversion_type == "mpeg2" {
+        return (None, None);
+    }
+    let Ok(params) = source_target_params(ffprobe, input_path, conversion_type, target_bytes, trim_start, trim_duration, options).await else {
+        return (None, None);
+    };
+    match calculate_target(&params) {
+        Ok(calc) if calc.bits_per_pixel.is_some() => budget_caps(
+            params.width,
+            params.height,
+            params.max_height,
+            params.keep_resolution,
+            params.fps.unwrap_or(30.0),
+            params.reduce_frame_rate,
+            calc.video_bitrate,
+            codec_efficiency(conversion_type),
+        ),
+        _ => (None, None),
+    }
+}
+
+/// Run the bitrate math for the source before encoding and, if the result would look
+/// poor, say what would help: a lower resolution, a shorter trim or a larger target
+async fn check_quality_floor(
+    ffprobe: &PathBuf,
+    input_path: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> Option<QualityWarning> {
+    let params = source_target_params(ffprobe, input_path, conversion_type, target_bytes, trim_start, trim_duration, options)
+        .await
+        .ok()?;
+    let duration = params.duration;
+    let calc = calculate_target(&params).ok()?;
+    let bpp = calc.bits_per_pixel? * codec_efficiency(conversion_type);
+    if bpp >= POOR_BITS_PER_PIXEL {
+        return None;
+    }
+
+    let fps = calc.fps.unwrap_or(30.0);
+    let pixel_rate = |width: u32, height: u32| width as f64 * height as f64 * fps;
+    let suggested_height = COMMON_HEIGHTS
+        .iter()
+        .copied()
+        .filter(|&h| h < calc.height && conversion_type != "mpeg2")
+        .find(|&h| {
+            let width = (calc.width as f64 * h as f64 / calc.height as f64 / 2.0).round() * 2.0;
+            calc.video_bitrate / (width * h as f64 * fps) * codec_efficiency(conversion_type) >= POOR_BITS_PER_PIXEL
+        });
+    // Video bitrate that reaches the floor at the current resolution
+    let needed_bitrate = POOR_BITS_PER_PIXEL / codec_efficiency(conversion_type) * pixel_rate(calc.width, calc.height);
+    // params.target_bytes is what's left after the container; it needs the same again
+    let container_bytes = target_bytes.saturating_sub(params.target_bytes);
+    let max_duration = params.target_bytes as f64 * 8.0 / (needed_bitrate + calc.audio_bitrate);
+    let min_target_bytes = ((needed_bitrate + calc.audio_bitrate) * duration / 8.0) as u64 + container_bytes;
+
+    let mut fixes = Vec::new();
+    if let Some(height) = suggested_height {
+        fixes.push(format!("exporting at {}p", height));
+    }
+    fixes.push(format!("trimming to {:.0} s", max_duration.floor()));
+    fixes.push(format!("a target of at least {:.1} MB", min_target_bytes as f64 / (1024.0 * 1024.0)));
+    let message = format!(
+        "At this size the video gets {:.3} bits per pixel and will look smeared and blocky; try {}",
+        bpp,
+        fixes.join(", ")
+    );
+
+    Some(QualityWarning {
+        bits_per_pixel: bpp,
+        min_bits_per_pixel: POOR_BITS_PER_PIXEL,
+        suggested_height,
+        max_duration,
+        min_target_bytes,
+        message,
+    })
+}
+
+/// Projected result of a conversion, for a "projected size" readout before it starts
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputEstimate {
+    #[serde(flatten)]
+    pub calculation: TargetCalculation,
+    /// Encoder the encode time is estimated for
+    pub encoder: Option<String>,
+    /// Expected encode time in seconds; None until benchmark_encoders has run on this
+    /// machine, and for formats it doesn't cover
+    #[serde(rename = "encodeSeconds")]
+    pub encode_seconds: Option<f64>,
+    /// Set when the selected platform's limit lowered the target
+    #[serde(rename = "platformNote")]
+    pub platform_note: Option<String>,
+}
+
+/// The benchmark codec a conversion type encodes with
+fn benchmark_codec(conversion_type: &str) -> Option<&'static str> {
+    match conversion_type {
+        "mp4" | "mov" | "mkv" | "ts" => Some("h264"),
+        "mp4_hevc" => Some("hevc"),
+        "mp4_av1" | "webm_av1" => Some("av1"),
+        "webm_vp9" => Some("vp9"),
+        _ => None,
+    }
+}
+
+/// Size, bitrates and encode time convert_file would give this input, without encoding.
+/// The time comes from the cached encoder benchmark, scaled to the output resolution.
+pub async fn estimate_output(
+    app: &tauri::AppHandle,
+    input_path: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+) -> Result<OutputEstimate, String> {
+    let ffprobe = get_ffprobe_path(app);
+    // The platform's limit and the container's share come off first, as in convert_file
+    let (target_bytes, platform_note) = match options.platform.as_deref() {
+        Some(platform) => apply_platform_preset(&ffprobe, platform, input_path, output_extension(conversion_type), conversion_type, target_bytes, trim_start, trim_duration, options).await?,
+        None => (target_bytes, None),
+    };
+    let params = source_target_params(&ffprobe, input_path, conversion_type, target_bytes, trim_start, trim_duration, options).await?;
+    let mut calculation = calculate_target(&params)?;
+    // calculate_target sizes the streams; the container's share comes on top
+    let container_bytes = target_bytes.saturating_sub(params.target_bytes);
+    calculation.estimated_bytes = calculation.estimated_bytes.map(|bytes| bytes + container_bytes);
+
+    let report = benchmark::cached_report(app, &get_ffmpeg_path(app)).await;
+    let speed = match (benchmark_codec(conversion_type), report) {
+        (Some(codec), Some(report)) => benchmark::expected_encode_fps(&report, codec, calculation.width, calculation.height),
+        _ => None,
+    };
+    let (encoder, encode_seconds) = match speed {
+        Some((encoder, fps)) => {
+            // libx264 encodes twice unless constrained quality skips the first pass
+            let passes = if encoder == "libx264" && !options.constrained_quality { 2.0 } else { 1.0 };
+            let frames = params.duration * calculation.fps.unwrap_or(30.0);
+            (Some(encoder), Some(frames / fps * passes))
+        }
+        None => (None, None),
+    };
+
+    Ok(OutputEstimate {
+        calculation,
+        encoder,
+        encode_seconds,
+        platform_note,
+    })
+}
+
+/// Conversion types convert_file accepts
+pub const CONVERSION_TYPES: &[&str] = &["mp4", "mov", "mkv", "ts", "mp4_hevc", "mp4_av1", "webm_av1", "webm_vp9", "webm_vp8", "mov_prores", "mov_dnxhr", "mp4_lossless", "mpeg2", "remux", "split_av", "webp", "gif", "apng", "mp3", "opus", "ogg", "m4a", "flac", "wav"];
+
+/// File extension of a conversion type's output
+pub(crate) fn output_extension(conversion_type: &str) -> &str {
+    match conversion_type {
+        "mp4" | "mp4_hevc" | "mp4_av1" | "mp4_lossless" | "split_av" => "mp4",
+        "mov" | "mov_prores" | "mov_dnxhr" => "mov",
+        "mkv" | "remux" => "mkv",
+        "webm_av1" | "webm_vp9" | "webm_vp8" => "webm",
+        "mpeg2" => "mpg",
+        other => other,
+    }
+}
+
+/// Encoders a conversion type needs from the ffmpeg build: one of each group
+pub fn required_encoders(conversion_type: &str) -> &'static [&'static [&'static str]] {
+    match conversion_type {
+        "mp4" | "mov" | "mkv" | "ts" => &[&["libx264", "h264_nvenc"], &["aac"]],
+        "mp4_hevc" => &[&["libx265", "hevc_nvenc"], &["aac"]],
+        "mp4_av1" => &[&["libsvtav1", "av1_nvenc"], &["aac"]],
+        "webm_av1" => &[&["libsvtav1", "av1_nvenc"], &["libopus"]],
+        "webm_vp9" => &[&["libvpx-vp9"], &["libopus"]],
+        "webm_vp8" => &[&["libvpx"], &["libvorbis"]],
+        "mov_prores" => &[&["prores_ks"], &["pcm_s16le"]],
+        "mov_dnxhr" => &[&["dnxhd"], &["pcm_s16le"]],
+        "mp4_lossless" => &[&["libx264", "h264_nvenc"], &["alac"]],
+        "mpeg2" => &[&["mpeg2video"], &["mp2"]],
+        "remux" => &[],
+        "split_av" => &[&["pcm_s16le"]],
+        "webp" => &[&["libwebp_anim", "libwebp"]],
+        "gif" => &[&["gif"]],
+        "apng" => &[&["apng"]],
+        "mp3" => &[&["libmp3lame"]],
+        "opus" | "ogg" => &[&["libopus"]],
+        "m4a" => &[&["aac"]],
+        "flac" => &[&["flac"]],
+        "wav" => &[&["pcm_s16le"]],
+        _ => &[],
+    }
+}
+
+/// Audio-only and animated outputs have no use for a poster frame
+fn takes_poster(conversion_type: &str) -> bool {
+    !matches!(conversion_type, "webp" | "gif" | "apng") && audio_format(conversion_type).is_none()
+}
+
+/// Encode once, then embed the poster frame if asked, so the size checks of
+/// convert_strict and convert_with_retry see the file as it will be delivered
+async fn convert_to_target(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    conversion_type: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let mut result = encode_to_target(app, id, input_path, output_path, target_bytes, conversion_type, trim_start, trim_duration, markers, options, settings).await?;
+    if let (Some("embed" | "both"), true, true) = (options.poster.as_deref(), takes_poster(conversion_type), result.success) {
+        emit_progress(app, id, 100.0, "adding poster");
+        match poster::embed(app, id, output_path).await {
+            Ok(()) => result.output_size = fs::metadata(output_path).map(|m| m.len()).ok(),
+            Err(e) => result.warnings.push(format!("Exported without an embedded poster: {}", e)),
+        }
+    }
+    Ok(result)
+}
+
+/// Run the encoder for `conversion_type` aiming at `target_bytes`
+async fn encode_to_target(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    conversion_type: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    match conversion_type {
+        // Video formats - H.264 (MKV also gets chapters from markers, TS plays on old TVs)
+        "mp4" | "mov" | "mkv" | "ts" => convert_video_h264(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // Video format - H.265/HEVC
+        "mp4_hevc" => convert_video_hevc(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // Video format - AV1 (SVT-AV1), in MP4 or WebM
+        "mp4_av1" | "webm_av1" => convert_video_av1(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // Video format - VP9 WebM, keeping transparency when the source has it
+        "webm_vp9" => convert_video_vp9(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // VP8 + Vorbis for embeds that predate VP9
+        "webm_vp8" => convert_video_vp8(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // Editing intermediates: full quality, target size ignored
+        "mov_prores" => {
+            let codec_args = prores_args(options.prores_profile.as_deref())?;
+            convert_video_intermediate(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings, codec_args).await
+        }
+        "mov_dnxhr" => {
+            let codec_args = dnxhr_args(options.dnxhr_profile.as_deref())?;
+            convert_video_intermediate(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings, codec_args).await
+        }
+        // Lossless H.264 for archival trims
+        "mp4_lossless" => convert_video_lossless(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings).await,
+        // DVD-compatible MPEG-2 program stream for standalone players
+        "mpeg2" => convert_video_mpeg2(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, markers, options, settings).await,
+        // Stream copy into another container, no re-encode
+        "remux" => convert_remux(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings).await,
+        // Separate video and audio files from one read of the source
+        "split_av" => convert_split_av(app, id, input_path, output_path, trim_start, trim_duration, options, settings).await,
+        // Animated image formats
+        "webp" => convert_to_webp(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, options).await,
+        "gif" => convert_to_gif(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, options).await,
+        "apng" => convert_to_apng(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, options).await,
+        // Audio only
+        "mp3" | "opus" | "ogg" | "m4a" | "flac" | "wav" => {
+            let format = audio_format(conversion_type).ok_or("Unsupported audio format")?;
+            convert_to_audio(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, options, settings, format).await
+        }
+        _ => Err(format!("Unknown conversion type: {}", conversion_type)),
+    }
+}
+
+/// Internal target as a fraction of the real limit in strict mode
+const STRICT_TARGET_RATIO: f64 = 0.97;
+
+/// Strict mode gives up (and deletes the output) after this many encodes
+const STRICT_MAX_ATTEMPTS: u32 = 4;
+
+/// Strict mode: encode to a slightly lower internal target and, if the result still
+/// overshoots, shrink the target by the overshoot ratio and encode again
+async fn convert_strict(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    conversion_type: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let mut internal_target = (target_bytes as f64 * STRICT_TARGET_RATIO) as u64;
+    let mut last_size = 0;
+    let mut options = options.clone();
+
+    for attempt in 1..=STRICT_MAX_ATTEMPTS {
+        if attempt > 1 {
+            emit_progress(app, id, 0.0, "retrying");
+        }
+
+        let mut result = convert_to_target(app, id, input_path, output_path, internal_target, conversion_type, trim_start, trim_duration, markers.clone(), &options, settings).await?;
+        let size = result.output_size.unwrap_or(0);
+        if size <= target_bytes {
+            result.attempts = Some(attempt);
+            return Ok(result);
+        }
+        // Tiers above the one that overshot are larger still; don't encode them again
+        if let Some(tier) = result.tier {
+            options.min_tier = tier + 1;
+        }
+
+        last_size = size;
+        let ratio = target_bytes as f64 / size as f64;
+        internal_target = (internal_target as f64 * ratio * STRICT_TARGET_RATIO) as u64;
+        if let Some(bitrate) = options.vmaf_bitrate.as_mut() {
+            *bitrate *= ratio * STRICT_TARGET_RATIO;
+        }
+    }
+
+    let _ = fs::remove_file(output_path);
+    Err(format!(
+        "Output was still {} bytes over the {} byte limit after {} attempts",
+        last_size - target_bytes,
+        target_bytes,
+        STRICT_MAX_ATTEMPTS
+    ))
+}
+
+/// Video bitrate for a target VMAF: the lowest whose probe samples reach `score`, never
+/// above what `target_bytes` leaves for video, with a note on what the probes measured
+async fn vmaf_bitrate(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    conversion_type: &str,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    score: f64,
+    options: &ConversionOptions,
+) -> Result<(f64, String), String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+    let (encoder, encoder_args) = vmaf::probe_encoder(conversion_type).ok_or_else(|| format!("Target VMAF isn't supported for {}", conversion_type))?;
+
+    emit_progress(app, id, 0.0, "measuring quality");
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let output_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let source = vmaf::ProbeSource {
+        input_path,
+        scale_filter: &scale_filter,
+        trim_start,
+        duration: source_duration,
+    };
+    let floor = if matches!(conversion_type, "mp4_hevc" | "mp4" | "mov" | "mkv" | "ts") {
+        MIN_VIDEO_BITRATE_H26X
+    } else {
+        MIN_VIDEO_BITRATE_MODERN
+    };
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let budget = stream_budget(&ffprobe, input_path, output_extension(conversion_type), target_bytes, output_duration, options, &streams, 0).await;
+    let ceiling = video_bitrate(budget.target_bytes, output_duration, budget.audio_bitrate, floor);
+
+    let workspace = scratch::JobWorkspace::new(app, id)?;
+    let search = vmaf::bitrate_for_score(&ffmpeg, &workspace, &source, (encoder, encoder_args), score, floor, ceiling, |probes| {
+        emit_progress(app, id, probes as f64 / vmaf::MAX_PROBES as f64 * 100.0, "measuring quality");
+    })
+    .await?;
+
+    // The probes always run the CPU encoder; a GPU encode scores a little lower
+    let note = if search.reached {
+        format!(
+            "Encoded at {} kbps for VMAF {} ({} samples scored {:.1})",
+            (search.bitrate / 1000.0) as u32,
+            score,
+            encoder,
+            search.score
+        )
+    } else {
+        format!(
+            "VMAF {} isn't reachable within the target size; {} samples scored {:.1} at the full budget",
+            score, encoder, search.score
+        )
+    };
+    Ok((search.bitrate.min(ceiling), note))
+}
+
+/// Overshoot a normal video encode may have before it's redone, as a fraction of the target
+const DEFAULT_OVERSHOOT_MARGIN: f64 = 0.02;
+
+/// Encodes a normal video conversion gets to land within the margin
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Video types encoded at a bitrate, where an overshoot can be corrected by encoding
+/// again; animated images and audio have their own size handling
+fn retries_overshoot(conversion_type: &str) -> bool {
+    targets_size(conversion_type)
+        && audio_format(conversion_type).is_none()
+        && !matches!(conversion_type, "webp" | "gif" | "apng")
+}
+
+/// Encode at the target and, if the output overshoots it by more than the margin, scale
+/// the target down by the overshoot ratio and encode again. Unlike strict mode, an
+/// output still over after the last attempt is kept, with a warning.
+async fn convert_with_retry(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    conversion_type: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let margin = options.overshoot_margin.unwrap_or(DEFAULT_OVERSHOOT_MARGIN);
+    let max_attempts = options.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS);
+    let limit = (target_bytes as f64 * (1.0 + margin)) as u64;
+    let mut internal_target = target_bytes;
+    let mut last_size = u64::MAX;
+    let mut attempt = 1;
+    let mut options = options.clone();
+
+    loop {
+        let mut result = convert_to_target(app, id, input_path, output_path, internal_target, conversion_type, trim_start, trim_duration, markers.clone(), &options, settings).await?;
+        let size = result.output_size.unwrap_or(0);
+        result.attempts = Some(attempt);
+        if !result.success || size <= limit {
+            return Ok(result);
+        }
+
+        // Bitrate floors (e.g. DVD's) can keep a lower target from shrinking the output
+        if attempt >= max_attempts || size >= last_size {
+            result.warnings.push(format!(
+                "Output is {:.0}% over the target size (encoded {} times)",
+                (size as f64 / target_bytes as f64 - 1.0) * 100.0,
+                attempt
+            ));
+            return Ok(result);
+        }
+
+        last_size = size;
+        let ratio = target_bytes as f64 / size as f64;
+        internal_target = (internal_target as f64 * ratio) as u64;
+        if let Some(bitrate) = options.vmaf_bitrate.as_mut() {
+            *bitrate *= ratio;
+        }
+        attempt += 1;
+        emit_progress(app, id, 0.0, "retrying");
+    }
+}
+
+/// Share of an MPEG-TS file taken by packet and PES headers
+const TS_MUX_OVERHEAD: f64 = 0.05;
+
+/// Fixed MP4/Matroska headers (ftyp and moov boxes, EBML header, tracks, cues), in bytes
+const CONTAINER_HEADER_BYTES: f64 = 4096.0;
+
+/// Index and framing cost per packet: MP4 sample tables, Matroska block and cluster headers
+const MP4_BYTES_PER_PACKET: f64 = 8.0;
+const MATROSKA_BYTES_PER_PACKET: f64 = 12.0;
+
+/// Packets per second of an AAC (1024 samples at 48 kHz) or Opus (20 ms) track
+const AUDIO_PACKETS_PER_SECOND: f64 = 50.0;
+
+/// A chapter atom with its title
+const CHAPTER_BYTES: f64 = 120.0;
+
+/// Bytes an MP4/Matroska container adds on top of the streams: grows with the duration,
+/// the frame rate and the number of tracks
+pub(crate) fn container_bytes(extension: &str, duration: f64, fps: f64, audio_tracks: usize, chapters: usize) -> u64 {
+    let per_packet = match extension {
+        "mp4" | "mov" | "m4v" => MP4_BYTES_PER_PACKET,
+        "mkv" | "webm" => MATROSKA_BYTES_PER_PACKET,
+        _ => return 0,
+    };
+    let packets = duration * (fps + audio_tracks as f64 * AUDIO_PACKETS_PER_SECOND);
+    (CONTAINER_HEADER_BYTES + packets * per_packet + chapters as f64 * CHAPTER_BYTES) as u64
+}
+
+/// container_bytes at the source's frame rate
+async fn muxing_overhead(ffprobe: &PathBuf, input_path: &str, extension: &str, duration: f64, audio_tracks: usize, chapters: usize) -> u64 {
+    let fps = get_media_metadata(ffprobe, input_path)
+        .await
+        .ok()
+        .and_then(|m| m.frame_rate_decimal)
+        .unwrap_or(30.0);
+    container_bytes(extension, duration, fps, audio_tracks, chapters)
+}
+
+/// What a bitrate-targeted video encode has to spend
+struct StreamBudget {
+    /// The target less the container's share
+    target_bytes: u64,
+    /// Audio args for the audioCodec/audioBitrate options (none for preserved MKV tracks)
+    audio_args: Vec<String>,
+    /// Bits per second the audio takes out of `target_bytes`; the video gets the rest
+    audio_bitrate: f64,
+}
+
+/// Split a target between container and audio, as the encoders and the estimates
+/// (estimate_output, check_quality_floor, auto_caps) all need to: TS loses a fixed share
+/// to packetizing, MP4/Matroska their per-packet framing, and the audio is budgeted from
+/// the source's tracks as they'll be encoded (nothing for a silent source)
+async fn stream_budget(
+    ffprobe: &PathBuf,
+    input_path: &str,
+    extension: &str,
+    target_bytes: u64,
+    duration: f64,
+    options: &ConversionOptions,
+    streams: &[AudioStreamInfo],
+    chapters: usize,
+) -> StreamBudget {
+    // Archival MKV keeps every track; otherwise one track, mixed or transcoded
+    let preserved = extension == "mkv" && options.preserve_all_streams;
+    let (audio_args, audio_bitrate) = if preserved {
+        (Vec::new(), preserve_streams_args(streams, options.audio_offset_ms.is_none()).1)
+    } else if options.audio_mix.is_empty() || !options.speed_curve.is_empty() {
+        chosen_audio_args(options, streams, transcoded_audio_bitrate(streams))
+    } else {
+        chosen_audio_args(options, streams, mixed_audio_bitrate(streams))
+    };
+    let target_bytes = if extension == "ts" {
+        (target_bytes as f64 * (1.0 - TS_MUX_OVERHEAD)) as u64
+    } else {
+        let audio_tracks = if preserved { streams.len() } else { streams.len().min(1) };
+        target_bytes.saturating_sub(muxing_overhead(ffprobe, input_path, extension, duration, audio_tracks, chapters).await)
+    };
+    StreamBudget {
+        target_bytes,
+        audio_args,
+        audio_bitrate,
+    }
+}
+
+/// Lowercase extension of an output path, for stream_budget
+fn path_extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+async fn convert_video_h264(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    // Get video info
+    let info = get_video_info(&ffprobe, input_path).await?;
+
+    // Use trim duration if provided, otherwise use full video duration
+    let source_duration = trim_duration.unwrap_or(info.duration);
+
+    // A speed ramp changes the output length, which the bitrate budget is based on
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    // Check for NVENC H.264 support, then VAAPI (Intel/AMD on Linux)
+    let recommended = benchmark::recommended_encoder(app, &ffmpeg, "h264").await;
+    let use_nvenc = benchmark_allows(recommended.as_deref(), "h264_nvenc") && check_nvenc_h264_available(&ffmpeg, options).await;
+    let vaapi_device = if use_nvenc || !benchmark_allows(recommended.as_deref(), "h264_vaapi") {
+        None
+    } else {
+        check_vaapi_available(&ffmpeg, "h264_vaapi").await
+    };
+
+    let is_mkv = output_path.extension().is_some_and(|ext| ext == "mkv");
+    let is_ts = output_path.extension().is_some_and(|ext| ext == "ts");
+
+    // Archival MKV can carry every audio/subtitle track
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let stream_args = if let Some(ref ramp) = ramp {
+        if options.preserve_all_streams {
+            return Err("Speed ramps can't be combined with preserving all streams".to_string());
+        }
+        speed_ramp_args(ramp, !streams.is_empty(), options)
+    } else if !options.audio_mix.is_empty() {
+        if options.preserve_all_streams {
+            return Err("Mixing audio tracks can't be combined with preserving all streams".to_string());
+        }
+        audio_args(options)
+    } else if is_mkv && options.preserve_all_streams {
+        preserve_streams_args(&streams, options.audio_offset_ms.is_none()).0
+    } else {
+        Vec::new()
+    };
+
+    // Leave room for the container and the audio
+    let chapters = if is_mkv { markers.as_ref().map_or(0, |m| m.len()) } else { 0 };
+    let StreamBudget { target_bytes, audio_args: chosen_audio, audio_bitrate } =
+        stream_budget(&ffprobe, input_path, &path_extension(output_path), target_bytes, effective_duration, options, &streams, chapters).await;
+
+    // Calculate target bitrate based on effective duration
+    let video_bitrate = encode_video_bitrate(options, target_bytes, effective_duration, audio_bitrate, MIN_VIDEO_BITRATE_H26X);
+
+    // Convert to kbps for ffmpeg
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let fixups = platform_fixups(&ffprobe, input_path, &info, is_ts.then_some("yuv420p"), options).await?;
+    let video_filter = filters::chain(&marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options), fixups.filter().as_deref());
+    let output_args = video_output_args(settings, options)?;
+
+    // Prepare chapter metadata for MKV if markers provided
+    let workspace = scratch::JobWorkspace::new(app, id)?;
+    let metadata_path = if is_mkv && !adjusted.is_empty() {
+        Some(write_chapter_file(&workspace, &adjusted, effective_duration)?)
+    } else {
+        None
+    };
+
+    // With a chapter file as second input, map all streams of the first input explicitly
+    let mut extra_args = if !stream_args.is_empty() {
+        stream_args
+    } else if metadata_path.is_some() {
+        vec!["-map".to_string(), "0".to_string()]
+    } else {
+        Vec::new()
+    };
+    extra_args.extend(chosen_audio);
+    if ramp.is_none() && options.audio_mix.is_empty() {
+        extra_args.extend(audio_offset_args(options));
+    }
+    if is_ts {
+        // Hardware players decode 8-bit 4:2:0 with stereo audio at most
+        extra_args.extend(["-pix_fmt", "yuv420p", "-ac", "2"].iter().map(|s| s.to_string()));
+    }
+    extra_args.extend(output_args);
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let mut warnings = Vec::new();
+    let encoded_on_vaapi = match vaapi_device {
+        Some(ref device) => {
+            let vaapi_filter = vaapi_video_filter(&info, &adjusted, ramp.as_ref(), &fixups, options, false);
+            let codec_args = ["-c:v", "h264_vaapi", "-profile:v", "high"].map(String::from);
+            match convert_video_vaapi(app, id, input_path, &output_str, &ffmpeg, device, &codec_args, effective_duration, video_bitrate_k, &vaapi_filter, trim_start, output_duration, metadata_path.as_ref(), &extra_args, options).await {
+                Ok(()) => true,
+                Err(e) => {
+                    // Listed and a device present, but the driver can't encode this (e.g. no H.264 entrypoint)
+                    warnings.push(format!("VAAPI encoding failed ({}); used libx264 on the CPU instead", e));
+                    emit_progress(app, id, 5.0, CPU_FALLBACK_STATUS);
+                    false
+                }
+            }
+        }
+        None => false,
+    };
+    if encoded_on_vaapi && options.constrained_quality {
+        warnings.push(VAAPI_IGNORES_CQ.to_string());
+    }
+
+    // NVENC encoding (faster, uses GPU). When only scaling is needed, frames
+    // stay on the GPU; a source NVDEC can't decode fails there and is encoded again with
+    // CPU scaling. A driver reset or the session limit can end an encode partway; the
+    // CPU path then starts over.
+    let cuda_filter = match cuda_video_filter(&info, &adjusted, ramp.as_ref(), &fixups, options, false) {
+        Some(filter) if use_nvenc && capabilities::cuda_scaling_works(&ffmpeg, options.gpu_index).await => Some(filter),
+        _ => None,
+    };
+    let encoded_on_cuda = match cuda_filter {
+        Some(ref filter) => match convert_video_nvenc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, filter, trim_start, output_duration, metadata_path.as_ref(), &extra_args, true, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("GPU scaling failed ({}); scaled on the CPU instead", e));
+                emit_progress(app, id, 5.0, "converting");
+                false
+            }
+        },
+        None => false,
+    };
+    let encoded_on_nvenc = encoded_on_cuda
+        || use_nvenc && match convert_video_nvenc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, metadata_path.as_ref(), &extra_args, false, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("NVENC encoding failed ({}); used libx264 on the CPU instead", e));
+                emit_progress(app, id, 5.0, CPU_FALLBACK_STATUS);
+                false
+            }
+        };
+
+    if !encoded_on_nvenc && !encoded_on_vaapi {
+        // CPU two-pass encoding (slower, better quality per bit)
+        convert_video_x264(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, metadata_path.as_ref(), &extra_args, options).await?;
+    }
+
+    let chapters = if metadata_path.is_some() {
+        check_chapters(&ffprobe, &output_str, &adjusted, &mut warnings).await
+    } else {
+        None
+    };
+
+    // Get output file size
+    let output_size = fs::metadata(&output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        fixups: fixups.applied,
+        chapters,
+        ..Default::default()
+    })
+}
+
+async fn convert_video_hevc(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    let mut ten_bit = match options.bit_depth {
+        None | Some(8) => false,
+        Some(10) => true,
+        Some(other) => return Err(format!("Unsupported bit depth: {}", other)),
+    };
+
+    // Check for NVENC HEVC support, then VAAPI (Intel/AMD on Linux)
+    let recommended = benchmark::recommended_encoder(app, &ffmpeg, "hevc").await;
+    let use_nvenc = benchmark_allows(recommended.as_deref(), "hevc_nvenc") && check_nvenc_hevc_available(&ffmpeg, options).await;
+    let vaapi_device = if use_nvenc || !benchmark_allows(recommended.as_deref(), "hevc_vaapi") {
+        None
+    } else {
+        check_vaapi_available(&ffmpeg, "hevc_vaapi").await
+    };
+
+    // Budget for the container and the audio the output will actually have
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let StreamBudget { target_bytes, audio_args: chosen_audio, audio_bitrate } =
+        stream_budget(&ffprobe, input_path, &path_extension(output_path), target_bytes, effective_duration, options, &streams, 0).await;
+
+    // Calculate target bitrate - HEVC is ~25% more efficient
+    let video_bitrate = encode_video_bitrate(options, target_bytes, effective_duration, audio_bitrate, MIN_VIDEO_BITRATE_H26X);
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let fixups = platform_fixups(&ffprobe, input_path, &info, Some(if ten_bit { "yuv420p10le" } else { "yuv420p" }), options).await?;
+    let video_filter = filters::chain(&marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options), fixups.filter().as_deref());
+    let mut extra_args = match ramp {
+        Some(ref ramp) => speed_ramp_args(ramp, !streams.is_empty(), options),
+        None => audio_args(options),
+    };
+    extra_args.extend(chosen_audio);
+    extra_args.extend(video_output_args(settings, options)?);
+
+    let mut warnings = Vec::new();
+    let mut hdr = if options.hdr_passthrough {
+        let hdr = get_hdr_metadata(&ffprobe, input_path).await?;
+        if hdr.is_none() {
+            warnings.push("Source isn't HDR; encoded as regular SDR video".to_string());
+        }
+        hdr
+    } else {
+        None
+    };
+    if hdr.is_some() {
+        ten_bit = true;
+    }
+
+    // A destination that pins the pixel format (e.g. yuv420p) only accepts 8-bit SDR
+    if ten_bit && fixups.pixel_format.is_some() {
+        ten_bit = false;
+        warnings.push("The destination requires 8-bit video; encoded 8-bit instead of 10-bit".to_string());
+        hdr = None;
+    }
+
+    emit_progress(app, id, 5.0, "converting");
+
+    // HDR10 goes to x265, which writes the mastering metadata VAAPI drivers may drop
+    let encoded_on_vaapi = match vaapi_device {
+        Some(ref device) if hdr.is_none() => {
+            let vaapi_filter = vaapi_video_filter(&info, &adjusted, ramp.as_ref(), &fixups, options, ten_bit);
+            let mut codec_args = ["-c:v", "hevc_vaapi", "-tag:v", "hvc1"].map(String::from).to_vec();
+            codec_args.extend(["-profile:v".to_string(), if ten_bit { "main10" } else { "main" }.to_string()]);
+            match convert_video_vaapi(app, id, input_path, &output_str, &ffmpeg, device, &codec_args, effective_duration, video_bitrate_k, &vaapi_filter, trim_start, output_duration, None, &extra_args, options).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warnings.push(format!("VAAPI encoding failed ({}); used libx265 on the CPU instead", e));
+                    emit_progress(app, id, 5.0, CPU_FALLBACK_STATUS);
+                    false
+                }
+            }
+        }
+        _ => false,
+    };
+    if encoded_on_vaapi && options.constrained_quality {
+        warnings.push(VAAPI_IGNORES_CQ.to_string());
+    }
+
+    // Frames stay on the GPU when only scaling is needed, as for H.264
+    let cuda_filter = match cuda_video_filter(&info, &adjusted, ramp.as_ref(), &fixups, options, ten_bit) {
+        Some(filter) if use_nvenc && capabilities::cuda_scaling_works(&ffmpeg, options.gpu_index).await => Some(filter),
+        _ => None,
+    };
+    let encoded_on_cuda = match cuda_filter {
+        Some(ref filter) => match convert_video_nvenc_hevc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, filter, trim_start, output_duration, ten_bit, hdr.as_ref(), &extra_args, true, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("GPU scaling failed ({}); scaled on the CPU instead", e));
+                emit_progress(app, id, 5.0, "converting");
+                false
+            }
+        },
+        None => false,
+    };
+    let encoded_on_nvenc = encoded_on_cuda
+        || use_nvenc && match convert_video_nvenc_hevc(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, ten_bit, hdr.as_ref(), &extra_args, false, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("NVENC encoding failed ({}); used libx265 on the CPU instead", e));
+                emit_progress(app, id, 5.0, CPU_FALLBACK_STATUS);
+                false
+            }
+        };
+
+    if !encoded_on_nvenc && !encoded_on_vaapi {
+        convert_video_x265(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, ten_bit, hdr.as_ref(), &extra_args, options).await?;
+    }
+
+    let output_size = fs::metadata(&output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        fixups: fixups.applied,
+        ..Default::default()
+    })
+}
+
+async fn convert_video_av1(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    let is_webm = output_path.extension().is_some_and(|ext| ext == "webm");
+
+    // Check for NVENC AV1 support
+    let recommended = benchmark::recommended_encoder(app, &ffmpeg, "av1").await;
+    let use_nvenc = benchmark_allows(recommended.as_deref(), "av1_nvenc") && check_nvenc_av1_available(&ffmpeg, options).await;
+
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let StreamBudget { target_bytes, audio_args: chosen_audio, audio_bitrate } =
+        stream_budget(&ffprobe, input_path, &path_extension(output_path), target_bytes, effective_duration, options, &streams, 0).await;
+    let video_bitrate = encode_video_bitrate(options, target_bytes, effective_duration, audio_bitrate, MIN_VIDEO_BITRATE_MODERN);
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let fixups = platform_fixups(&ffprobe, input_path, &info, Some("yuv420p"), options).await?;
+    let video_filter = filters::chain(&marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options), fixups.filter().as_deref());
+    let mut extra_args = match ramp {
+        Some(ref ramp) => speed_ramp_args(ramp, !streams.is_empty(), options),
+        None => audio_args(options),
+    };
+    extra_args.extend(chosen_audio);
+    extra_args.extend(video_output_args(settings, options)?);
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let mut warnings = Vec::new();
+    // Frames stay on the GPU when only scaling is needed, as for H.264
+    let cuda_filter = match cuda_video_filter(&info, &adjusted, ramp.as_ref(), &fixups, options, false) {
+        Some(filter) if use_nvenc && capabilities::cuda_scaling_works(&ffmpeg, options.gpu_index).await => Some(filter),
+        _ => None,
+    };
+    let encoded_on_cuda = match cuda_filter {
+        Some(ref filter) => match convert_video_nvenc_av1(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, filter, trim_start, output_duration, is_webm, &extra_args, true, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                warnings.push(format!("GPU scaling failed ({}); scaled on the CPU instead", e));
+                emit_progress(app, id, 5.0, "converting");
+                false
+            }
+        },
+        None => false,
+    };
+    let encoded_on_gpu = encoded_on_cuda
+        || use_nvenc && match convert_video_nvenc_av1(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, is_webm, &extra_args, false, options).await {
+            Ok(()) => true,
+            Err(e) => {
+                // Listed but unusable (pre-RTX 40 GPU or driver too old)
+                warnings.push(format!("GPU AV1 encoding failed ({}); used SVT-AV1 on the CPU instead", e));
+                emit_progress(app, id, 5.0, CPU_FALLBACK_STATUS);
+                false
+            }
+        };
+
+    if !encoded_on_gpu {
+        convert_video_svtav1(app, id, input_path, &output_str, &ffmpeg, effective_duration, video_bitrate_k, &video_filter, trim_start, output_duration, is_webm, &extra_args, options).await?;
+    }
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        fixups: fixups.applied,
+        ..Default::default()
+    })
+}
+
+/// Container-specific audio and muxer args: Opus for WebM, AAC + faststart for MP4
+fn av1_container_args(is_webm: bool) -> Vec<String> {
+    let args: &[&str] = if is_webm {
+        &["-c:a", "libopus", "-b:a", "128k"]
+    } else {
+        &["-c:a", "aac", "-b:a", "128k", "-movflags", "+faststart"]
+    };
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+async fn convert_video_nvenc_av1(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    is_webm: bool,
+    extra_args: &[String],
+    cuda_frames: bool,
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    let bitrate_str = format!("{}k", video_bitrate_k);
+    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
+    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(nvenc_decode_args(options, cuda_frames));
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // NVENC AV1 encoding (RTX 40-series and newer)
+    args.extend(["-c:v".to_string(), "av1_nvenc".to_string()]);
+    args.extend(nvenc_tuning_args(options));
+    args.extend([
+        "-b:v".to_string(), bitrate_str,
+        "-maxrate".to_string(), maxrate_str,
+        "-bufsize".to_string(), bufsize_str,
+        "-vf".to_string(), video_filter.to_string(),
+    ]);
+    if !cuda_frames {
+        args.extend(["-pix_fmt".to_string(), "yuv420p".to_string()]);
+    }
+    args.extend(nvenc_gpu_args(options));
+    args.extend(av1_container_args(is_webm));
+    if cuda_frames {
+        args.extend(without_pix_fmt(extra_args));
+    } else {
+        args.extend(extra_args.iter().cloned());
+    }
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+async fn convert_video_svtav1(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    is_webm: bool,
+    extra_args: &[String],
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    let bitrate_str = format!("{}k", video_bitrate_k);
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // SVT-AV1 in VBR mode (rc=1) targeting the bitrate; screen content compresses very well
+    args.extend([
+        "-c:v".to_string(), "libsvtav1".to_string(),
+        "-preset".to_string(), encoder_preset(options, "6", "10"),
+        "-b:v".to_string(), bitrate_str,
+        "-svtav1-params".to_string(), "rc=1".to_string(),
+        "-pix_fmt".to_string(), "yuv420p".to_string(),
+        "-vf".to_string(), video_filter.to_string(),
+    ]);
+    args.extend(av1_container_args(is_webm));
+    args.extend(efficiency_thread_args(options));
+    args.extend(extra_args.iter().cloned());
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+/// Whether a pixel format carries an alpha channel (yuva420p, rgba, gbrap, ya8, ...)
+fn has_alpha(pixel_format: &str) -> bool {
+    pixel_format.starts_with("yuva")
+        || pixel_format.starts_with("gbrap")
+        || pixel_format.starts_with("ya")
+        || ["rgba", "bgra", "argb", "abgr"].iter().any(|f| pixel_format.starts_with(f))
+}
+
+async fn convert_video_vp9(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source = get_media_metadata(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    // Alpha in the source (ProRes 4444, PNG, QuickTime Animation) is carried as yuva420p.
+    // Note ffmpeg's native VP9 decoder drops alpha, so VP9 sources read as opaque.
+    let alpha = source.pixel_format.as_deref().is_some_and(has_alpha);
+
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let StreamBudget { target_bytes, audio_args: chosen_audio, audio_bitrate } =
+        stream_budget(&ffprobe, input_path, &path_extension(output_path), target_bytes, effective_duration, options, &streams, 0).await;
+    let video_bitrate = encode_video_bitrate(options, target_bytes, effective_duration, audio_bitrate, MIN_VIDEO_BITRATE_MODERN);
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let fixups = platform_fixups(&ffprobe, input_path, &info, Some(if alpha { "yuva420p" } else { "yuv420p" }), options).await?;
+    // A destination that pins the pixel format takes no alpha channel
+    let alpha = alpha && fixups.pixel_format.is_none();
+    let video_filter = filters::chain(&marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options), fixups.filter().as_deref());
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = output_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    args.extend([
+        "-c:v".to_string(), "libvpx-vp9".to_string(),
+        "-b:v".to_string(), format!("{}k", video_bitrate_k),
+        "-deadline".to_string(), "good".to_string(),
+        "-cpu-used".to_string(), encoder_preset(options, "2", "5"),
+        "-row-mt".to_string(), "1".to_string(),
+        "-vf".to_string(), video_filter,
+        "-pix_fmt".to_string(), if alpha { "yuva420p" } else { "yuv420p" }.to_string(),
+    ]);
+    if alpha {
+        // libvpx can't encode alpha with alt-ref frames
+        args.extend(["-auto-alt-ref".to_string(), "0".to_string()]);
+    }
+    args.extend(["-c:a".to_string(), "libopus".to_string(), "-b:a".to_string(), "128k".to_string()]);
+    match ramp {
+        Some(ref ramp) => args.extend(speed_ramp_args(ramp, !streams.is_empty(), options)),
+        None => args.extend(audio_args(options)),
+    }
+    args.extend(chosen_audio);
+    args.extend(efficiency_thread_args(options));
+    args.extend(video_output_args(settings, options)?);
+    args.push(output_str.clone());
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await?;
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        fixups: fixups.applied,
+        ..Default::default()
+    })
+}
+
+/// DVD-compatible MPEG-2: 720x480 (NTSC) or 720x576 (PAL) anamorphic 4:3 or 16:9,
+/// MP2 audio at 48 kHz, in a DVD program stream that standalone players accept
+async fn convert_video_mpeg2(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source = get_media_metadata(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    let mut warnings = Vec::new();
+    let total_bitrate = (target_bytes as f64 * 8.0) / effective_duration;
+    let video_bitrate = mpeg2_video_bitrate(target_bytes, effective_duration);
+    if total_bitrate - DVD_AUDIO_BITRATE > DVD_MAX_VIDEO_BITRATE {
+        warnings.push("Capped at DVD's maximum bitrate; the file is smaller than the target".to_string());
+    }
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    // Letterbox onto a square-pixel canvas of the display aspect, so titles and captions
+    // are drawn undistorted, then squeeze to DVD's anamorphic 720 width
+    let dvd = dvd_standard(source.frame_rate_decimal);
+    let widescreen = info.height == 0 || info.width as f64 / info.height as f64 > 1.5;
+    let (dar, dar_value) = if widescreen { ("16/9", 16.0 / 9.0) } else { ("4/3", 4.0 / 3.0) };
+    let canvas_width = ((dvd.height as f64 * dar_value / 2.0).round() as u32) * 2;
+    let scale_filter = filters::fit_with_padding(
+        canvas_width,
+        dvd.height,
+        &filters::PadBackground::parse(options.pad_background.as_deref())?,
+        &filters::downscale_flags(options.scale_algorithm.as_deref()),
+    );
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let video_filter = filters::chain(
+        &marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options),
+        Some(&format!("scale=720:{},setdar={}", dvd.height, dar)),
+    );
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = output_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // Rate control and mux settings follow ffmpeg's own -target dvd
+    args.extend([
+        "-c:v".to_string(), "mpeg2video".to_string(),
+        "-b:v".to_string(), format!("{}k", video_bitrate_k),
+        "-maxrate".to_string(), "9000k".to_string(),
+        "-bufsize".to_string(), "1835k".to_string(),
+        "-g".to_string(), dvd.gop.to_string(),
+        "-bf".to_string(), "2".to_string(),
+        "-r".to_string(), dvd.frame_rate.to_string(),
+        "-vf".to_string(), video_filter,
+        "-pix_fmt".to_string(), "yuv420p".to_string(),
+        "-c:a".to_string(), "mp2".to_string(),
+        "-b:a".to_string(), format!("{}k", (DVD_AUDIO_BITRATE / 1000.0) as u32),
+        "-ar".to_string(), "48000".to_string(),
+        "-ac".to_string(), "2".to_string(),
+    ]);
+    match ramp {
+        Some(ref ramp) => {
+            let has_audio = !get_audio_streams(&ffprobe, input_path).await.unwrap_or_default().is_empty();
+            args.extend(speed_ramp_args(ramp, has_audio, options));
+        }
+        None => args.extend(audio_args(options)),
+    }
+    args.extend(efficiency_thread_args(options));
+    args.extend(video_output_args(settings, options)?);
+    args.extend([
+        "-f".to_string(), "dvd".to_string(),
+        "-muxrate".to_string(), "10080k".to_string(),
+        "-packetsize".to_string(), "2048".to_string(),
+    ]);
+    args.push(output_str.clone());
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await?;
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        ..Default::default()
+    })
+}
+
+/// Two-pass VP8 WebM with Vorbis audio, for legacy embeds (forum software, old
+/// players) that reject VP9 or Opus
+async fn convert_video_vp8(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source = get_media_metadata(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    // libvpx encodes VP8 alpha too, with the same alt-ref restriction as VP9
+    let alpha = source.pixel_format.as_deref().is_some_and(has_alpha);
+
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    let StreamBudget { target_bytes, audio_args: chosen_audio, audio_bitrate } =
+        stream_budget(&ffprobe, input_path, &path_extension(output_path), target_bytes, effective_duration, options, &streams, 0).await;
+    let video_bitrate = encode_video_bitrate(options, target_bytes, effective_duration, audio_bitrate, MIN_VIDEO_BITRATE_H26X);
+    let video_bitrate_k = (video_bitrate / 1000.0) as u32;
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let scale_filter = web_scale_filter(info.width, info.height, options);
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let video_filter = marker_video_filter(&scale_filter, &adjusted, ramp.as_ref(), options);
+
+    let passlog = scratch::scratch_dir(app)
+        .join(format!("vp8pass_{}", id))
+        .to_string_lossy()
+        .to_string();
+
+    let pass_args = |pass: &str| {
+        let mut args: Vec<String> = vec!["-y".to_string()];
+        if let Some(start) = trim_start {
+            args.push("-ss".to_string());
+            args.push(format!("{:.3}", start));
+        }
+        args.extend(read_rate_args(options));
+        args.push("-i".to_string());
+        args.push(input_path.to_string());
+        if let Some(duration) = output_duration {
+            args.push("-t".to_string());
+            args.push(format!("{:.3}", duration));
+        }
+        args.extend([
+            "-c:v".to_string(), "libvpx".to_string(),
+            "-b:v".to_string(), format!("{}k", video_bitrate_k),
+            "-maxrate".to_string(), format!("{}k", (video_bitrate_k as f64 * 1.5) as u32),
+            "-bufsize".to_string(), format!("{}k", video_bitrate_k * 2),
+            "-deadline".to_string(), "good".to_string(),
+            "-cpu-used".to_string(), encoder_preset(options, "1", "4"),
+            "-vf".to_string(), video_filter.clone(),
+            "-pix_fmt".to_string(), if alpha { "yuva420p" } else { "yuv420p" }.to_string(),
+            "-auto-alt-ref".to_string(), if alpha { "0" } else { "1" }.to_string(),
+            "-pass".to_string(), pass.to_string(),
+            "-passlogfile".to_string(), passlog.clone(),
+        ]);
+        args.extend(efficiency_thread_args(options));
+        args
+    };
+
+    #[cfg(target_os = "windows")]
+    let null_output = "NUL";
+    #[cfg(not(target_os = "windows"))]
+    let null_output = "/dev/null";
+
+    let mut pass1_args = pass_args("1");
+    pass1_args.extend(["-an".to_string(), "-f".to_string(), "null".to_string(), null_output.to_string()]);
+
+    let mut pass2_args = pass_args("2");
+    pass2_args.extend(["-c:a".to_string(), "libvorbis".to_string(), "-b:a".to_string(), "128k".to_string()]);
+    match ramp {
+        Some(ref ramp) => pass2_args.extend(speed_ramp_args(ramp, !streams.is_empty(), options)),
+        None => pass2_args.extend(audio_args(options)),
+    }
+    pass2_args.extend(chosen_audio);
+    pass2_args.extend(video_output_args(settings, options)?);
+    pass2_args.push(output_str.clone());
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+    let pass1_refs: Vec<&str> = pass1_args.iter().map(|s| s.as_str()).collect();
+    let pass1 = run_ffmpeg_with_progress(&ffmpeg, pass1_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.45, "converting");
+    })
+    .await;
+
+    let result = match pass1 {
+        Ok(()) => {
+            let app_clone = app.clone();
+            let id_clone = id.to_string();
+            let pass2_refs: Vec<&str> = pass2_args.iter().map(|s| s.as_str()).collect();
+            run_ffmpeg_with_progress(&ffmpeg, pass2_refs, effective_duration, Some(id), |progress| {
+                emit_progress(&app_clone, &id_clone, 50.0 + progress * 0.5, "converting");
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    remove_pass_logs(&passlog);
+    result?;
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        ..Default::default()
+    })
+}
+
+/// prores_ks args for a profile name; 4444 profiles keep alpha
+fn prores_args(profile: Option<&str>) -> Result<Vec<String>, String> {
+    let (index, pix_fmt) = match profile.unwrap_or("hq") {
+        "proxy" => ("0", "yuv422p10le"),
+        "lt" => ("1", "yuv422p10le"),
+        "standard" => ("2", "yuv422p10le"),
+        "hq" => ("3", "yuv422p10le"),
+        "4444" => ("4", "yuva444p10le"),
+        "4444xq" => ("5", "yuva444p10le"),
+        other => return Err(format!("Unknown ProRes profile: {}", other)),
+    };
+    Ok([
+        "-c:v", "prores_ks",
+        "-profile:v", index,
+        // Apple's vendor ID, which some NLEs check before trusting the stream
+        "-vendor", "apl0",
+        "-pix_fmt", pix_fmt,
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect())
+}
+
+/// DNxHR args for a profile name (8-bit 4:2:2 profiles)
+fn dnxhr_args(profile: Option<&str>) -> Result<Vec<String>, String> {
+    let profile = match profile.unwrap_or("hq") {
+        "lb" => "dnxhr_lb",
+        "sq" => "dnxhr_sq",
+        "hq" => "dnxhr_hq",
+        other => return Err(format!("Unknown DNxHR profile: {}", other)),
+    };
+    Ok([
+        "-c:v", "dnxhd",
+        "-profile:v", profile,
+        "-pix_fmt", "yuv422p",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect())
+}
+
+/// Audio codecs MP4 can carry as-is
+const MP4_COPYABLE_AUDIO: &[&str] = &["aac", "mp3", "ac3", "eac3", "opus", "alac", "flac"];
+
+/// Audio for intermediates: PCM in MOV; in MP4 the source stream is copied when nothing
+/// filters it, otherwise (or if MP4 can't hold it) ALAC keeps it lossless
+async fn intermediate_audio_args(ffprobe: &PathBuf, input_path: &str, is_mp4: bool, filtered: bool) -> Vec<String> {
+    let codec = if !is_mp4 {
+        "pcm_s16le"
+    } else if filtered {
+        "alac"
+    } else {
+        let source = get_media_metadata(ffprobe, input_path).await.ok().and_then(|m| m.audio_codec);
+        match source {
+            Some(ref codec) if MP4_COPYABLE_AUDIO.contains(&codec.as_str()) => "copy",
+            _ => "alac",
+        }
+    };
+    vec!["-c:a".to_string(), codec.to_string()]
+}
+
+/// Lossless H.264: NVENC's lossless tuning when available, falling back to libx264 -qp 0
+async fn convert_video_lossless(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let x264_args: Vec<String> = [
+        "-c:v", "libx264",
+        "-qp", "0",
+        "-preset", if options.efficient() { "ultrafast" } else { "veryfast" },
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+
+    if check_nvenc_h264_available(&ffmpeg, options).await {
+        let mut nvenc_args: Vec<String> = ["-c:v", "h264_nvenc", "-preset", "p7", "-tune", "lossless"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        nvenc_args.extend(nvenc_gpu_args(options));
+        if let Ok(result) = convert_video_intermediate(app, id, input_path, output_path, trim_start, trim_duration, markers.clone(), options, settings, nvenc_args).await {
+            return Ok(result);
+        }
+        // Older drivers/GPUs without lossless support
+        let mut result = convert_video_intermediate(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings, x264_args).await?;
+        result.warnings.push("GPU lossless encoding failed; used x264 on the CPU instead".to_string());
+        return Ok(result);
+    }
+
+    convert_video_intermediate(app, id, input_path, output_path, trim_start, trim_duration, markers, options, settings, x264_args).await
+}
+
+/// Edit-friendly intermediate (MOV) or lossless archive (MP4): full resolution and the
+/// given video codec args. There's no size target, only trims, markers and speed ramps.
+async fn convert_video_intermediate(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+    codec_args: Vec<String>,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+    let output_duration = if ramp.is_some() { Some(effective_duration) } else { trim_duration };
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    // No downscaling: editors and archives want the source resolution; chroma
+    // subsampling needs even dimensions
+    let scale_filter = "scale=trunc(iw/2)*2:trunc(ih/2)*2";
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, ramp.as_ref());
+    let video_filter = marker_video_filter(scale_filter, &adjusted, ramp.as_ref(), options);
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = output_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    let is_mp4 = output_path.extension().is_some_and(|ext| ext == "mp4");
+    let audio_filtered = ramp.is_some() || options.audio_offset_ms.is_some() || !options.audio_mix.is_empty();
+
+    args.extend(codec_args);
+    args.extend(["-vf".to_string(), video_filter]);
+    args.extend(intermediate_audio_args(&ffprobe, input_path, is_mp4, audio_filtered).await);
+    if is_mp4 {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+    match ramp {
+        Some(ref ramp) => {
+            let has_audio = !get_audio_streams(&ffprobe, input_path).await.unwrap_or_default().is_empty();
+            args.extend(speed_ramp_args(ramp, has_audio, options));
+        }
+        None => args.extend(audio_args(options)),
+    }
+    args.extend(video_output_args(settings, options)?);
+    args.push(output_str.clone());
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await?;
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        ..Default::default()
+    })
+}
+
+/// Input args decoding on the GPU chosen by gpu_index, so the decode doesn't land on
+/// the primary card either; sources NVDEC can't decode fall back to the CPU. With
+/// `cuda_frames` the decoded frames stay in GPU memory for scale_cuda.
+fn nvenc_decode_args(options: &ConversionOptions, cuda_frames: bool) -> Vec<String> {
+    let mut args = Vec::new();
+    if cuda_frames || options.gpu_index.is_some() {
+        args.extend(["-hwaccel".to_string(), "cuda".to_string()]);
+    }
+    if cuda_frames {
+        args.extend(["-hwaccel_output_format".to_string(), "cuda".to_string()]);
+    }
+    if let Some(index) = options.gpu_index {
+        args.extend(["-hwaccel_device".to_string(), index.to_string()]);
+    }
+    args
+}
+
+/// Filter chain for NVENC encodes that need nothing but scaling: frames stay in GPU
+/// memory from decode to encode and scale_cuda resizes them to what web_scale_filter
+/// would give. None when a speed ramp, titles, captions or fix-ups need the CPU.
+fn cuda_video_filter(
+    info: &VideoInfo,
+    markers: &[Marker],
+    ramp: Option<&SpeedRamp>,
+    fixups: &PlatformFixups,
+    options: &ConversionOptions,
+    ten_bit: bool,
+) -> Option<String> {
+    let software = filters::chain(&marker_video_filter("null", markers, ramp, options), fixups.filter().as_deref());
+    if software != "null" {
+        return None;
+    }
+    let (width, height) = web_output_dimensions(info.width, info.height, options.max_height);
+    Some(format!(
+        "scale_cuda=w={}:h={}:interp_algo={}:format={}",
+        width,
+        height,
+        filters::cuda_interp_algo(options.scale_algorithm.as_deref()),
+        if ten_bit { "p010le" } else { "nv12" }
+    ))
+}
+
+/// Args without their -pix_fmt pairs, for encodes fed GPU frames: a software pixel
+/// format (e.g. the one for MPEG-TS) can't be applied to them
+fn without_pix_fmt(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "-pix_fmt" {
+            args.next();
+        } else {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Encoder arg pinning NVENC to the GPU chosen by gpu_index
+fn nvenc_gpu_args(options: &ConversionOptions) -> Vec<String> {
+    match options.gpu_index {
+        Some(index) => vec!["-gpu".to_string(), index.to_string()],
+        None => Vec::new(),
+    }
+}
+
+async fn convert_video_nvenc(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    metadata_path: Option<&PathBuf>,
+    extra_args: &[String],
+    cuda_frames: bool,
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+
+    // Build args with optional trim parameters
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    // Add trim start (seek) before input for fast seeking
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(nvenc_decode_args(options, cuda_frames));
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    // Add chapter metadata file as second input (for MKV)
+    if let Some(meta_path) = metadata_path {
+        args.push("-i".to_string());
+        args.push(meta_path.to_string_lossy().to_string());
+    }
+
+    // Add trim duration after input
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // NVENC with high quality preset and its own two-pass rate control
+    args.extend(["-c:v".to_string(), "h264_nvenc".to_string()]);
+    args.extend(nvenc_tuning_args(options));
+    args.extend(nvenc_rate_args(options, video_bitrate_k, X264_DEFAULT_CRF));
+    args.extend([
+        "-profile:v".to_string(), "high".to_string(),
+        "-vf".to_string(), video_filter.to_string(),
+        "-c:a".to_string(), "aac".to_string(),
+        "-b:a".to_string(), "128k".to_string(),
+    ]);
+    args.extend(nvenc_gpu_args(options));
+
+    // Map metadata from chapter file if provided
+    if metadata_path.is_some() {
+        args.extend(["-map_metadata".to_string(), "1".to_string()]); // Map metadata from second input (chapters)
+    } else {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+
+    if cuda_frames {
+        args.extend(without_pix_fmt(extra_args));
+    } else {
+        args.extend(extra_args.iter().cloned());
+    }
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+/// Filter chain for VAAPI encodes: the software filters (speed ramp, titles, captions,
+/// fix-ups) run at source size, then frames are uploaded and scale_vaapi resizes them
+/// on the GPU to what web_scale_filter would give
+fn vaapi_video_filter(
+    info: &VideoInfo,
+    markers: &[Marker],
+    ramp: Option<&SpeedRamp>,
+    fixups: &PlatformFixups,
+    options: &ConversionOptions,
+    ten_bit: bool,
+) -> String {
+    let (width, height) = web_output_dimensions(info.width, info.height, options.max_height);
+    let software = filters::chain(&marker_video_filter("null", markers, ramp, options), fixups.filter().as_deref());
+    format!(
+        "{},format={},hwupload,scale_vaapi=w={}:h={}:mode=hq",
+        software,
+        if ten_bit { "p010" } else { "nv12" },
+        width,
+        height
+    )
+}
+
+/// Single-pass VAAPI encode (Intel/AMD GPUs on Linux); `codec_args` pick the encoder
+/// and profile
+async fn convert_video_vaapi(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    device: &Path,
+    codec_args: &[String],
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    metadata_path: Option<&PathBuf>,
+    extra_args: &[String],
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    let mut args: Vec<String> = vec![
+        "-y".to_string(),
+        "-vaapi_device".to_string(),
+        device.to_string_lossy().to_string(),
+    ];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(meta_path) = metadata_path {
+        args.push("-i".to_string());
+        args.push(meta_path.to_string_lossy().to_string());
+    }
+
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    args.extend(codec_args.iter().cloned());
+    args.extend([
+        "-rc_mode".to_string(), "VBR".to_string(),
+        "-b:v".to_string(), format!("{}k", video_bitrate_k),
+        "-maxrate".to_string(), format!("{}k", (video_bitrate_k as f64 * 1.5) as u32),
+        "-bufsize".to_string(), format!("{}k", video_bitrate_k * 2),
+        "-vf".to_string(), video_filter.to_string(),
+        "-c:a".to_string(), "aac".to_string(),
+        "-b:a".to_string(), "128k".to_string(),
+    ]);
+
+    if metadata_path.is_some() {
+        args.extend(["-map_metadata".to_string(), "1".to_string()]);
+    } else {
+        args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+
+    // Frames leave the filter chain as GPU surfaces, already 4:2:0
+    args.extend(without_pix_fmt(extra_args));
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+/// How long finished x264 first-pass stats are kept for sibling jobs after their last use
+const PASS1_STATS_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// First-pass stats shared by jobs exporting the same clip at different target sizes
+struct SharedPass1Stats {
+    key: String,
+    /// -passlogfile prefix
+    prefix: String,
+    /// Jobs currently running pass 2 from these stats (x264 streams the mbtree file)
+    users: u32,
+    last_used: Instant,
+}
+
+static PASS1_STATS: Mutex<Vec<SharedPass1Stats>> = Mutex::new(Vec::new());
+
+fn remove_pass_logs(prefix: &str) {
+    let _ = fs::remove_file(format!("{}-0.log", prefix));
+    let _ = fs::remove_file(format!("{}-0.log.mbtree", prefix));
+}
+
+/// Pass-1 stats only depend on the frames going into the encoder and the preset, not
+/// on the bitrate, so x264 can run pass 2 at any target from the same stats
+fn pass1_stats_key(input_path: &str, trim_start: Option<f64>, trim_duration: Option<f64>, video_filter: &str, preset: &str) -> String {
+    let params = format!("{}|{:?}|{:?}|{}|{}", file_cache_key(input_path), trim_start, trim_duration, video_filter, preset);
+    format!("{:016x}", fnv1a_hash(params.as_bytes()))
+}
+
+/// Claim reusable stats for `key`, expiring unused old entries along the way.
+/// A claim must be given back with release_pass1_stats.
+fn claim_pass1_stats(key: &str) -> Option<String> {
+    let mut stats = PASS1_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    stats.retain(|entry| {
+        let keep = entry.users > 0 || entry.last_used.elapsed() < PASS1_STATS_TTL;
+        if !keep {
+            remove_pass_logs(&entry.prefix);
+        }
+        keep
+    });
+    let entry = stats.iter_mut().find(|entry| entry.key == key)?;
+    entry.users += 1;
+    Some(entry.prefix.clone())
+}
+
+/// Offer freshly written stats to later jobs, claimed by the caller. Returns false when
+/// another job already shared stats for the same key.
+fn share_pass1_stats(key: &str, prefix: &str) -> bool {
+    let mut stats = PASS1_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    if stats.iter().any(|entry| entry.key == key) {
+        return false;
+    }
+    stats.push(SharedPass1Stats {
+        key: key.to_string(),
+        prefix: prefix.to_string(),
+        users: 1,
+        last_used: Instant::now(),
+    });
+    true
+}
+
+fn release_pass1_stats(key: &str) {
+    let mut stats = PASS1_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = stats.iter_mut().find(|entry| entry.key == key) {
+        entry.users = entry.users.saturating_sub(1);
+        entry.last_used = Instant::now();
+    }
+}
+
+async fn convert_video_x264(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    metadata_path: Option<&PathBuf>,
+    extra_args: &[String],
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let bitrate_str = format!("{}k", video_bitrate_k);
+    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
+    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+
+    // Siblings exporting the same clip at another size can skip straight to pass 2
+    let preset = encoder_preset(options, "slow", "faster");
+    let stats_key = pass1_stats_key(input_path, trim_start, trim_duration, video_filter, &preset);
+    let cached_stats = if options.constrained_quality { None } else { claim_pass1_stats(&stats_key) };
+    let pass2_start = if cached_stats.is_some() || options.constrained_quality { 5.0 } else { 50.0 };
+
+    // Constrained quality is a single CRF pass and needs no stats
+    let pass_log = match cached_stats {
+        _ if options.constrained_quality => None,
+        Some(prefix) => Some((prefix, true)),
+        None => {
+            let prefix = scratch::scratch_dir(app)
+                .join(format!("x264pass_{}_{}", stats_key, id))
+                .to_string_lossy()
+                .to_string();
+            if let Err(e) = run_x264_pass1(app, id, input_path, ffmpeg, effective_duration, video_filter, trim_start, trim_duration, &preset, &bitrate_str, &maxrate_str, &bufsize_str, &prefix, options).await {
+                remove_pass_logs(&prefix);
+                return Err(e);
+            }
+            let shared = share_pass1_stats(&stats_key, &prefix);
+            Some((prefix, shared))
+        }
+    };
+
+    // Pass 2
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    let mut pass2_args: Vec<String> = vec!["-y".to_string()];
+
+    // Add trim start (seek) before input for fast seeking
+    if let Some(start) = trim_start {
+        pass2_args.push("-ss".to_string());
+        pass2_args.push(format!("{:.3}", start));
+    }
+
+    pass2_args.extend(read_rate_args(options));
+    pass2_args.push("-i".to_string());
+    pass2_args.push(input_path.to_string());
 
     // Add chapter metadata file as second input (for MKV)
     if let Some(meta_path) = metadata_path {
+        pass2_args.push("-i".to_string());
+        pass2_args.push(meta_path.to_string_lossy().to_string());
+    }
+
+    // Add trim duration after input
+    if let Some(duration) = trim_duration {
+        pass2_args.push("-t".to_string());
+        pass2_args.push(format!("{:.3}", duration));
+    }
+
+    pass2_args.extend(["-c:v".to_string(), "libx264".to_string(), "-preset".to_string(), preset]);
+    match pass_log {
+        Some((ref passlog, _)) => pass2_args.extend([
+            "-b:v".to_string(), bitrate_str,
+            "-maxrate".to_string(), maxrate_str,
+            "-bufsize".to_string(), bufsize_str,
+            "-pass".to_string(), "2".to_string(),
+            "-passlogfile".to_string(), passlog.clone(),
+        ]),
+        None => pass2_args.extend(capped_crf_args(options.crf.unwrap_or(X264_DEFAULT_CRF), video_bitrate_k)),
+    }
+    pass2_args.extend([
+        "-vf".to_string(), video_filter.to_string(),
+        "-c:a".to_string(), "aac".to_string(),
+        "-b:a".to_string(), "128k".to_string(),
+    ]);
+
+    // Map metadata from chapter file if provided
+    if metadata_path.is_some() {
+        pass2_args.extend(["-map_metadata".to_string(), "1".to_string()]); // Map metadata from second input (chapters)
+    } else {
+        pass2_args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+    }
+
+    pass2_args.extend(efficiency_thread_args(options));
+    pass2_args.extend(extra_args.iter().cloned());
+    pass2_args.push(output_str.to_string());
+
+    let pass2_refs: Vec<&str> = pass2_args.iter().map(|s| s.as_str()).collect();
+
+    let result = run_ffmpeg_with_progress(ffmpeg, pass2_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, pass2_start + progress * (100.0 - pass2_start) / 100.0, "converting");
+    })
+    .await;
+
+    // Shared stats stay for sibling jobs and are removed once they expire
+    match pass_log {
+        Some((_, true)) => release_pass1_stats(&stats_key),
+        Some((passlog, false)) => remove_pass_logs(&passlog),
+        None => {}
+    }
+
+    result
+}
+
+/// CRF used in constrained-quality mode unless the caller picks one; x265's scale sits
+/// higher for the same look
+const X264_DEFAULT_CRF: u32 = 23;
+const X265_DEFAULT_CRF: u32 = 28;
+
+/// VAAPI encodes at a bitrate only; constrainedQuality and crf don't reach it
+const VAAPI_IGNORES_CQ: &str = "Constrained quality isn't supported by the VAAPI encoder; encoded to fill the target size";
+
+/// NVENC rate control: the target bitrate with VBV room above it or, in constrained-quality
+/// mode, constant quality (-cq, on about the same 0-51 scale as the CRF) held under the
+/// target bitrate like capped_crf_args
+fn nvenc_rate_args(options: &ConversionOptions, video_bitrate_k: u32, default_crf: u32) -> Vec<String> {
+    if options.constrained_quality {
+        return vec![
+            "-cq".to_string(), options.crf.unwrap_or(default_crf).to_string(),
+            "-b:v".to_string(), "0".to_string(),
+            "-maxrate".to_string(), format!("{}k", video_bitrate_k),
+            "-bufsize".to_string(), format!("{}k", video_bitrate_k * 2),
+        ];
+    }
+    vec![
+        "-b:v".to_string(), format!("{}k", video_bitrate_k),
+        "-maxrate".to_string(), format!("{}k", (video_bitrate_k as f64 * 1.5) as u32),
+        "-bufsize".to_string(), format!("{}k", video_bitrate_k * 2),
+    ]
+}
+
+/// Constrained-quality rate control: encode at a CRF, with the target bitrate as the VBV
+/// ceiling. Simple clips (e.g. screen recordings) come out under the budget instead of
+/// being padded up to it; busy ones are held to it.
+fn capped_crf_args(crf: u32, video_bitrate_k: u32) -> Vec<String> {
+    vec![
+        "-crf".to_string(), crf.to_string(),
+        "-maxrate".to_string(), format!("{}k", video_bitrate_k),
+        "-bufsize".to_string(), format!("{}k", video_bitrate_k * 2),
+    ]
+}
+
+/// x264 first pass, writing rate control stats to `passlog`
+async fn run_x264_pass1(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    preset: &str,
+    bitrate_str: &str,
+    maxrate_str: &str,
+    bufsize_str: &str,
+    passlog: &str,
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let null_output = "NUL";
+    #[cfg(not(target_os = "windows"))]
+    let null_output = "/dev/null";
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    // Build args with optional trim parameters
+    let mut pass1_args: Vec<String> = vec!["-y".to_string()];
+
+    // Add trim start (seek) before input for fast seeking
+    if let Some(start) = trim_start {
+        pass1_args.push("-ss".to_string());
+        pass1_args.push(format!("{:.3}", start));
+    }
+
+    pass1_args.extend(read_rate_args(options));
+    pass1_args.push("-i".to_string());
+    pass1_args.push(input_path.to_string());
+
+    // Add trim duration after input
+    if let Some(duration) = trim_duration {
+        pass1_args.push("-t".to_string());
+        pass1_args.push(format!("{:.3}", duration));
+    }
+
+    pass1_args.extend([
+        "-c:v".to_string(), "libx264".to_string(),
+        "-preset".to_string(), preset.to_string(),
+        "-b:v".to_string(), bitrate_str.to_string(),
+        "-maxrate".to_string(), maxrate_str.to_string(),
+        "-bufsize".to_string(), bufsize_str.to_string(),
+        "-vf".to_string(), video_filter.to_string(),
+        "-pass".to_string(), "1".to_string(),
+        "-passlogfile".to_string(), passlog.to_string(),
+        "-an".to_string(),
+    ]);
+    pass1_args.extend(efficiency_thread_args(options));
+    pass1_args.extend(["-f".to_string(), "null".to_string(), null_output.to_string()]);
+
+    let pass1_refs: Vec<&str> = pass1_args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, pass1_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.45, "converting");
+    })
+    .await
+}
+
+/// HEVC profile and pixel format; NVENC takes 10-bit input as P010
+fn hevc_depth_args(ten_bit: bool, nvenc: bool) -> Vec<String> {
+    let (profile, pix_fmt) = match (ten_bit, nvenc) {
+        (true, true) => ("main10", "p010le"),
+        (true, false) => ("main10", "yuv420p10le"),
+        (false, _) => ("main", "yuv420p"),
+    };
+    vec!["-profile:v".to_string(), profile.to_string(), "-pix_fmt".to_string(), pix_fmt.to_string()]
+}
+
+/// Colour signalling flags for the container and bitstream
+fn hdr_color_args(hdr: &HdrMetadata) -> Vec<String> {
+    vec![
+        "-color_primaries".to_string(), hdr.primaries.clone(),
+        "-color_trc".to_string(), hdr.transfer.clone(),
+        "-colorspace".to_string(), hdr.matrix.clone(),
+    ]
+}
+
+/// x265 writes HDR10 SEI itself: colour VUI plus mastering display and light levels,
+/// repeated on every keyframe so players joining mid-stream still see them
+fn x265_hdr_params(hdr: &HdrMetadata) -> String {
+    let mut params = vec![
+        "repeat-headers=1".to_string(),
+        format!("colorprim={}", hdr.primaries),
+        format!("transfer={}", hdr.transfer),
+        format!("colormatrix={}", hdr.matrix),
+    ];
+    if hdr.transfer == "smpte2084" {
+        params.push("hdr10=1".to_string());
+    }
+    if let Some(ref master_display) = hdr.master_display {
+        params.push(format!("master-display={}", master_display));
+    }
+    if let Some(ref max_cll) = hdr.max_cll {
+        params.push(format!("max-cll={}", max_cll));
+    }
+    params.join(":")
+}
+
+async fn convert_video_nvenc_hevc(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    ten_bit: bool,
+    hdr: Option<&HdrMetadata>,
+    extra_args: &[String],
+    cuda_frames: bool,
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(nvenc_decode_args(options, cuda_frames));
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // NVENC HEVC encoding
+    args.extend(["-c:v".to_string(), "hevc_nvenc".to_string()]);
+    args.extend(nvenc_tuning_args(options));
+    args.extend(nvenc_rate_args(options, video_bitrate_k, X265_DEFAULT_CRF));
+    args.extend([
+        "-vf".to_string(), video_filter.to_string(),
+        "-c:a".to_string(), "aac".to_string(),
+        "-b:a".to_string(), "128k".to_string(),
+        "-movflags".to_string(), "+faststart".to_string(),
+        "-tag:v".to_string(), "hvc1".to_string(), // Better Apple compatibility
+    ]);
+    args.extend(nvenc_gpu_args(options));
+    // scale_cuda already converted GPU frames to the bit depth
+    let depth_args = hevc_depth_args(ten_bit, true);
+    args.extend(if cuda_frames { without_pix_fmt(&depth_args) } else { depth_args });
+    if let Some(hdr) = hdr {
+        // NVENC copies mastering metadata from the decoded frames' side data
+        args.extend(hdr_color_args(hdr));
+    }
+    if cuda_frames {
+        args.extend(without_pix_fmt(extra_args));
+    } else {
+        args.extend(extra_args.iter().cloned());
+    }
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+async fn convert_video_x265(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_str: &str,
+    ffmpeg: &PathBuf,
+    effective_duration: f64,
+    video_bitrate_k: u32,
+    video_filter: &str,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    ten_bit: bool,
+    hdr: Option<&HdrMetadata>,
+    extra_args: &[String],
+    options: &ConversionOptions,
+) -> Result<(), String> {
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+
+    let bitrate_str = format!("{}k", video_bitrate_k);
+    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
+    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // CPU x265 encoding (single pass for speed, still good quality)
+    args.extend([
+        "-c:v".to_string(), "libx265".to_string(),
+        "-preset".to_string(), encoder_preset(options, "medium", "faster"),
+    ]);
+    if options.constrained_quality {
+        args.extend(capped_crf_args(options.crf.unwrap_or(X265_DEFAULT_CRF), video_bitrate_k));
+    } else {
+        args.extend([
+            "-b:v".to_string(), bitrate_str,
+            "-maxrate".to_string(), maxrate_str,
+            "-bufsize".to_string(), bufsize_str,
+        ]);
+    }
+    args.extend([
+        "-vf".to_string(), video_filter.to_string(),
+        "-c:a".to_string(), "aac".to_string(),
+        "-b:a".to_string(), "128k".to_string(),
+        "-movflags".to_string(), "+faststart".to_string(),
+        "-tag:v".to_string(), "hvc1".to_string(),
+    ]);
+    args.extend(hevc_depth_args(ten_bit, false));
+    if let Some(hdr) = hdr {
+        args.extend(hdr_color_args(hdr));
+        args.extend(["-x265-params".to_string(), x265_hdr_params(hdr)]);
+    }
+    args.extend(efficiency_thread_args(options));
+    args.extend(extra_args.iter().cloned());
+    args.push(output_str.to_string());
+
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await
+}
+
+/// Rewrap the source into the output's container (.mp4, .mov or .mkv) with `-c copy`.
+/// Trims snap to the keyframe before trim_start, since nothing is decoded; MKV gets chapters
+/// from markers. Filters, speed ramps and audio changes need a re-encode and are rejected.
+async fn convert_remux(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    if !options.speed_curve.is_empty() || !options.audio_mix.is_empty() || options.audio_offset_ms.is_some() || options.burn_in_titles {
+        return Err("Remux copies streams unchanged; speed ramps, audio changes and burned-in titles need a re-encode".to_string());
+    }
+
+    let extension = output_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let is_mkv = match extension.as_str() {
+        "mkv" => true,
+        "mp4" | "mov" => false,
+        _ => return Err("Remux output must be .mp4, .mov or .mkv".to_string()),
+    };
+
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let effective_duration = trim_duration.unwrap_or(info.duration);
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let adjusted = output_markers(markers.as_deref(), trim_start, trim_duration, None);
+    let workspace = scratch::JobWorkspace::new(app, id)?;
+    let metadata_path = if is_mkv && !adjusted.is_empty() {
+        Some(write_chapter_file(&workspace, &adjusted, effective_duration)?)
+    } else {
+        None
+    };
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = trim_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    if let Some(ref meta_path) = metadata_path {
         args.push("-i".to_string());
         args.push(meta_path.to_string_lossy().to_string());
     }
 
-    // Add trim duration after input
-    if let Some(duration) = trim_duration {
+    if let Some(duration) = trim_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
+    }
+
+    // MKV takes any stream; MP4/MOV only get video and audio, since subtitle and data
+    // streams from other containers usually can't be copied into them
+    if is_mkv {
+        args.extend(["-map".to_string(), "0".to_string()]);
+    } else {
+        args.extend([
+            "-map".to_string(), "0:v?".to_string(),
+            "-map".to_string(), "0:a?".to_string(),
+            "-movflags".to_string(), "+faststart".to_string(),
+        ]);
+    }
+    args.extend(["-c".to_string(), "copy".to_string()]);
+    if metadata_path.is_some() {
+        // The source's own chapters would otherwise win over the marker chapters
+        args.extend([
+            "-map_metadata".to_string(), "1".to_string(),
+            "-map_chapters".to_string(), "1".to_string(),
+        ]);
+    }
+    args.extend(video_output_args(settings, options)?);
+    args.push(output_str.clone());
+
+    emit_progress(app, id, 5.0, "converting");
+
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await?;
+
+    let mut warnings = Vec::new();
+    let chapters = if metadata_path.is_some() {
+        check_chapters(&ffprobe, &output_str, &adjusted, &mut warnings).await
+    } else {
+        None
+    };
+
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        chapters,
+        ..Default::default()
+    })
+}
+
+/// Write a video-only copy of the source and its audio as a WAV next to it
+/// ("clip_audio.wav" for "clip.mp4"), in one ffmpeg run. The video stream is copied;
+/// the audio is decoded so editors get sample-accurate PCM.
+async fn convert_split_av(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+) -> Result<ConversionResult, String> {
+    if !options.speed_curve.is_empty() || options.burn_in_titles {
+        return Err("Split copies the video unchanged; speed ramps and burned-in titles need a re-encode".to_string());
+    }
+
+    let extension = output_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !matches!(extension.as_str(), "mp4" | "mov" | "mkv") {
+        return Err("Split video output must be .mp4, .mov or .mkv".to_string());
+    }
+
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    emit_progress(app, id, 0.0, "analyzing");
+
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    if streams.is_empty() {
+        return Err("Source has no audio track to split off".to_string());
+    }
+    let info = get_video_info(&ffprobe, input_path).await?;
+
+    // The copied video can only start on a keyframe; the audio is cut at the same point
+    // so the two files stay in sync when lined up in an editor
+    let mut warnings = Vec::new();
+    let cut_start = match trim_start {
+        Some(start) => {
+            let keyframe = keyframe_before(&ffprobe, input_path, start).await?.unwrap_or(start);
+            if start - keyframe > 0.001 {
+                warnings.push(format!(
+                    "Video is copied, so it can only be cut on a keyframe; both files start {:.2}s before the trim start",
+                    start - keyframe
+                ));
+            }
+            Some(keyframe)
+        }
+        None => None,
+    };
+    let cut_duration = trim_duration.map(|duration| duration + trim_start.unwrap_or(0.0) - cut_start.unwrap_or(0.0));
+    let effective_duration = cut_duration.unwrap_or(info.duration);
+
+    let stem = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    let output_str = output_path.to_string_lossy().to_string();
+    // The audio file is an output like any other: write access checked, an existing file
+    // staged so the overwrite can be undone
+    let audio_location = resolve_output_path(app, &output_str, &format!("{}_audio.wav", stem), false)?;
+    let audio_path = audio_location.path;
+    let audio_str = audio_path.to_string_lossy().to_string();
+    let staged_audio = audio_path.is_file() && undo::stage_overwrite(app, &audio_path).await.is_ok();
+    if staged_audio {
+        job_journal::staged(app, id, &audio_path);
+    }
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
+
+    if let Some(start) = cut_start {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
+    }
+
+    // As an input option the duration limits both outputs; after -i it would only
+    // reach the video file
+    if let Some(duration) = cut_duration {
         args.push("-t".to_string());
         args.push(format!("{:.3}", duration));
     }
 
-    // NVENC single-pass with high quality preset
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
+
+    // Output options apply to the file that follows them, so each output gets its own map
     args.extend([
-        "-c:v".to_string(), "h264_nvenc".to_string(),
-        "-preset".to_string(), "p7".to_string(),
-        "-tune".to_string(), "hq".to_string(),
-        "-rc".to_string(), "vbr".to_string(),
-        "-b:v".to_string(), bitrate_str,
-        "-maxrate".to_string(), maxrate_str,
-        "-bufsize".to_string(), bufsize_str,
-        "-profile:v".to_string(), "high".to_string(),
-        "-vf".to_string(), scale_filter.to_string(),
-        "-c:a".to_string(), "aac".to_string(),
-        "-b:a".to_string(), "128k".to_string(),
+        "-map".to_string(), "0:v:0".to_string(),
+        "-an".to_string(),
+        "-c:v".to_string(), "copy".to_string(),
     ]);
-
-    // Map metadata from chapter file if provided
-    if metadata_path.is_some() {
-        args.extend([
-            "-map".to_string(), "0".to_string(),          // Map all streams from first input (video)
-            "-map_metadata".to_string(), "1".to_string(), // Map metadata from second input (chapters)
-        ]);
-    } else {
+    if extension != "mkv" {
         args.extend(["-movflags".to_string(), "+faststart".to_string()]);
     }
+    args.extend(settings.metadata_args());
+    args.push(output_str.clone());
+
+    match audio_filter_graph(options, None) {
+        Some(graph) => {
+            args.extend([
+                "-filter_complex".to_string(), graph,
+                "-map".to_string(), "[aout]".to_string(),
+            ]);
+        }
+        None => {
+            args.extend(["-map".to_string(), "0:a:0".to_string()]);
+            args.extend(audio_offset_args(options));
+        }
+    }
+    let first_track = options.audio_mix.first().map_or(0, |t| t.track);
+    let codec = pcm_codec(streams.iter().find(|s| s.index == first_track));
+    args.extend(["-vn".to_string(), "-c:a".to_string(), codec.to_string()]);
+    args.extend(settings.metadata_args());
+    if options.deterministic {
+        args.extend(deterministic_args());
+    }
+    args.push(audio_str.clone());
 
-    args.push(output_str.to_string());
+    emit_progress(app, id, 5.0, "converting");
 
+    let app_clone = app.clone();
+    let id_clone = id.to_string();
     let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let started = SystemTime::now();
+    job_journal::created(app, id, &audio_path);
 
-    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, |progress| {
+    let result = run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
         emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
     })
-    .await
+    .await;
+    if result.is_err() {
+        // The video output is cleaned up by the caller; the audio file is ours
+        remove_partial_output(&audio_path, started);
+        if staged_audio {
+            let _ = undo::unstage_overwrite(app, &audio_path);
+        }
+    }
+    result?;
+
+    let output_size = [output_path, audio_path.as_path()]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|m| m.len())
+        .sum();
+
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str.clone()),
+        output_size: Some(output_size),
+        output_paths: vec![output_str, audio_str],
+        warnings,
+        ..Default::default()
+    })
 }
 
-async fn convert_video_x264(
+/// Encoder settings for an audio-only conversion type
+pub(crate) struct AudioFormat {
+    name: &'static str,
+    pub(crate) codec: &'static str,
+    /// Bitrate range the encoder accepts, in kbps; None for lossless formats, which
+    /// ignore the target size
+    pub(crate) bitrate_range_k: Option<(u32, u32)>,
+    pub(crate) args: &'static [&'static str],
+}
+
+impl AudioFormat {
+    /// Encoder for a source track: WAV follows the track's sample format so 24-bit and
+    /// float sources aren't truncated to 16 bits; other formats have one encoder
+    pub(crate) fn codec_for(&self, stream: Option<&AudioStreamInfo>) -> &'static str {
+        if self.codec == "pcm_s16le" {
+            pcm_codec(stream)
+        } else {
+            self.codec
+        }
+    }
+}
+
+/// PCM encoder that keeps a track's sample depth
+fn pcm_codec(stream: Option<&AudioStreamInfo>) -> &'static str {
+    match stream.and_then(|s| s.sample_format.as_deref()) {
+        Some("flt" | "fltp" | "dbl" | "dblp") => "pcm_f32le",
+        Some("s32" | "s32p" | "s64" | "s64p") => "pcm_s24le",
+        _ => "pcm_s16le",
+    }
+}
+
+pub(crate) fn audio_format(conversion_type: &str) -> Option<AudioFormat> {
+    match conversion_type {
+        "mp3" => Some(AudioFormat {
+            name: "MP3",
+            codec: "libmp3lame",
+            bitrate_range_k: Some((32, 320)),
+            args: &["-id3v2_version", "3"],
+        }),
+        // Constrained VBR stays close to the requested bitrate, so the size is predictable
+        "opus" | "ogg" => Some(AudioFormat {
+            name: "Opus",
+            codec: "libopus",
+            bitrate_range_k: Some((6, 510)),
+            args: &["-vbr", "constrained"],
+        }),
+        // faststart so podcast players can begin before the whole file has downloaded
+        "m4a" => Some(AudioFormat {
+            name: "AAC",
+            codec: "aac",
+            bitrate_range_k: Some((16, 320)),
+            args: &["-movflags", "+faststart"],
+        }),
+        "flac" => Some(AudioFormat {
+            name: "FLAC",
+            codec: "flac",
+            bitrate_range_k: None,
+            args: &[],
+        }),
+        // 16-bit unless the source has more; see codec_for
+        "wav" => Some(AudioFormat {
+            name: "WAV",
+            codec: "pcm_s16le",
+            bitrate_range_k: None,
+            args: &[],
+        }),
+        _ => None,
+    }
+}
+
+/// Audio-only export: drop the video and encode the first audio track at the bitrate
+/// that fills the target size, or at the caller's chosen bitrate (lossless formats
+/// keep full quality whatever the target)
+async fn convert_to_audio(
     app: &tauri::AppHandle,
     id: &str,
     input_path: &str,
-    output_str: &str,
-    ffmpeg: &PathBuf,
-    effective_duration: f64,
-    video_bitrate_k: u32,
-    scale_filter: &str,
+    output_path: &Path,
+    target_bytes: u64,
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
-    metadata_path: Option<&PathBuf>,
-) -> Result<(), String> {
-    let bitrate_str = format!("{}k", video_bitrate_k);
-    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
-    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+    options: &ConversionOptions,
+    settings: &EncodeSettings,
+    format: AudioFormat,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
 
-    #[cfg(target_os = "windows")]
-    let null_output = "NUL";
-    #[cfg(not(target_os = "windows"))]
-    let null_output = "/dev/null";
+    emit_progress(app, id, 0.0, "analyzing");
 
-    // Pass 1
-    let app_clone = app.clone();
-    let id_clone = id.to_string();
+    let streams = get_audio_streams(&ffprobe, input_path).await.unwrap_or_default();
+    if streams.is_empty() {
+        return Err("Source has no audio track".to_string());
+    }
 
-    // Build args with optional trim parameters
-    let mut pass1_args: Vec<String> = vec!["-y".to_string()];
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let source_duration = trim_duration.unwrap_or(info.duration);
+    let ramp = SpeedRamp::new(&options.speed_curve, source_duration)?;
+    let effective_duration = ramp.as_ref().map_or(source_duration, |r| r.output_duration());
+
+    let mut warnings = Vec::new();
+    let bitrate_k = match (format.bitrate_range_k, options.audio_bitrate) {
+        (None, _) => None,
+        (Some((min, max)), Some(kbps)) => Some(kbps.clamp(min, max)),
+        (Some((min, max)), None) => {
+            let bitrate_k = ((target_bytes as f64 * 8.0) / effective_duration / 1000.0) as u32;
+            if bitrate_k < min {
+                warnings.push(format!(
+                    "Target size needs {}k, below {}'s minimum; encoded at {}k so the file may exceed the target",
+                    bitrate_k, format.name, min
+                ));
+            }
+            Some(bitrate_k.clamp(min, max))
+        }
+    };
+
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let mut args: Vec<String> = vec!["-y".to_string()];
 
-    // Add trim start (seek) before input for fast seeking
     if let Some(start) = trim_start {
-        pass1_args.push("-ss".to_string());
-        pass1_args.push(format!("{:.3}", start));
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", start));
     }
 
-    pass1_args.push("-i".to_string());
-    pass1_args.push(input_path.to_string());
+    args.extend(read_rate_args(options));
+    args.push("-i".to_string());
+    args.push(input_path.to_string());
 
-    // Add trim duration after input
     if let Some(duration) = trim_duration {
-        pass1_args.push("-t".to_string());
-        pass1_args.push(format!("{:.3}", duration));
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration));
     }
 
-    pass1_args.extend([
-        "-c:v".to_string(), "libx264".to_string(),
-        "-preset".to_string(), "slow".to_string(),
-        "-b:v".to_string(), bitrate_str.clone(),
-        "-maxrate".to_string(), maxrate_str.clone(),
-        "-bufsize".to_string(), bufsize_str.clone(),
-        "-vf".to_string(), scale_filter.to_string(),
-        "-pass".to_string(), "1".to_string(),
-        "-passlogfile".to_string(), output_str.to_string(),
-        "-an".to_string(),
-        "-f".to_string(), "null".to_string(),
-        null_output.to_string(),
-    ]);
+    match audio_filter_graph(options, ramp.as_ref()) {
+        Some(graph) => {
+            args.extend([
+                "-filter_complex".to_string(), graph,
+                "-map".to_string(), "[aout]".to_string(),
+            ]);
+        }
+        None => {
+            args.extend(["-map".to_string(), "0:a:0".to_string()]);
+            args.extend(audio_offset_args(options));
+        }
+    }
 
-    let pass1_refs: Vec<&str> = pass1_args.iter().map(|s| s.as_str()).collect();
+    let first_track = options.audio_mix.first().map_or(0, |t| t.track);
+    let codec = format.codec_for(streams.iter().find(|s| s.index == first_track));
+    args.extend(["-vn".to_string(), "-c:a".to_string(), codec.to_string()]);
+    if options.mono {
+        args.extend(["-ac".to_string(), "1".to_string()]);
+    }
+    if let Some(bitrate_k) = bitrate_k {
+        args.extend(["-b:a".to_string(), format!("{}k", bitrate_k)]);
+    }
+    args.extend(format.args.iter().map(|s| s.to_string()));
+    args.extend(settings.metadata_args());
+    if options.deterministic {
+        args.extend(deterministic_args());
+    }
+    args.push(output_str.clone());
 
-    run_ffmpeg_with_progress(ffmpeg, pass1_refs, effective_duration, |progress| {
-        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.45, "converting");
-    })
-    .await?;
+    emit_progress(app, id, 5.0, "converting");
 
-    // Pass 2
     let app_clone = app.clone();
     let id_clone = id.to_string();
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
 
-    let mut pass2_args: Vec<String> = vec!["-y".to_string()];
+    run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), |progress| {
+        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    })
+    .await?;
 
-    // Add trim start (seek) before input for fast seeking
-    if let Some(start) = trim_start {
-        pass2_args.push("-ss".to_string());
-        pass2_args.push(format!("{:.3}", start));
-    }
+    let output_size = fs::metadata(output_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
 
-    pass2_args.push("-i".to_string());
-    pass2_args.push(input_path.to_string());
+    emit_progress(app, id, 100.0, "completed");
 
-    // Add chapter metadata file as second input (for MKV)
-    if let Some(meta_path) = metadata_path {
-        pass2_args.push("-i".to_string());
-        pass2_args.push(meta_path.to_string_lossy().to_string());
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(output_size),
+        warnings,
+        ..Default::default()
+    })
+}
+
+/// Length of the sample the WebP quality search probes with on long clips
+const PROBE_SAMPLE_SECONDS: f64 = 3.0;
+
+/// Hybrid seek args for frame-accurate trims: fast seek (whole seconds) BEFORE -i,
+/// accurate seek (fractional part) AFTER -i
+fn hybrid_seek_args(start: Option<f64>) -> (Vec<String>, Vec<String>) {
+    let Some(start) = start else {
+        return (Vec::new(), Vec::new());
+    };
+    let fast = start.floor();
+    let accurate = start - fast;
+
+    let before = vec!["-ss".to_string(), format!("{:.0}", fast)];
+    let after = if accurate > 0.001 {
+        vec!["-ss".to_string(), format!("{:.3}", accurate)]
+    } else {
+        Vec::new()
+    };
+    (before, after)
+}
+
+/// Input args shared by the animated formats, covering the range [start, start + duration).
+/// `extra_input` (e.g. a palette image) is added right after the source so the trim options
+/// that follow apply to the output rather than to it.
+fn animated_input_args(
+    input_path: &str,
+    extra_input: Option<&str>,
+    start: Option<f64>,
+    duration: Option<f64>,
+    read_rate: &[String],
+) -> Vec<String> {
+    let (fast_seek, accurate_seek) = hybrid_seek_args(start);
+    let mut args = vec!["-y".to_string()];
+
+    // Fast seek BEFORE input (seeks to nearest keyframe - fast but approximate)
+    args.extend(fast_seek);
+    args.extend(read_rate.iter().cloned());
+    args.extend(["-i".to_string(), input_path.to_string()]);
+
+    if let Some(extra) = extra_input {
+        args.extend(["-i".to_string(), extra.to_string()]);
     }
 
-    // Add trim duration after input
-    if let Some(duration) = trim_duration {
-        pass2_args.push("-t".to_string());
-        pass2_args.push(format!("{:.3}", duration));
+    // Accurate seek AFTER input (decodes frames for exact positioning)
+    args.extend(accurate_seek);
+
+    // Add duration AFTER input
+    if let Some(duration) = duration {
+        args.extend(["-t".to_string(), format!("{:.3}", duration)]);
     }
 
-    pass2_args.extend([
-        "-c:v".to_string(), "libx264".to_string(),
-        "-preset".to_string(), "slow".to_string(),
-        "-b:v".to_string(), bitrate_str,
-        "-maxrate".to_string(), maxrate_str,
-        "-bufsize".to_string(), bufsize_str,
-        "-vf".to_string(), scale_filter.to_string(),
-        "-pass".to_string(), "2".to_string(),
-        "-passlogfile".to_string(), output_str.to_string(),
-        "-c:a".to_string(), "aac".to_string(),
-        "-b:a".to_string(), "128k".to_string(),
+    args
+}
+
+/// Scale to fit within max_dim x max_dim (lanczos), ensure even dimensions, set fps
+fn animated_scale_filter(max_dim: u32, fps: u32) -> String {
+    format!(
+        "scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease:{flags},scale=trunc(iw/2)*2:trunc(ih/2)*2,fps={1}",
+        max_dim,
+        fps,
+        flags = filters::downscale_flags(None)
+    )
+}
+
+/// ffmpeg args for one WebP tier: (max_dimension, fps, quality). `alpha` keeps the
+/// source's transparency instead of flattening it onto black.
+fn webp_tier_args(
+    input_path: &str,
+    output: &str,
+    tier: (u32, u32, u32),
+    start: Option<f64>,
+    duration: Option<f64>,
+    read_rate: &[String],
+    alpha: bool,
+) -> Vec<String> {
+    let (max_dim, fps, quality) = tier;
+    let mut args = animated_input_args(input_path, None, start, duration, read_rate);
+    args.extend([
+        "-vf".to_string(), animated_scale_filter(max_dim, fps),
+        "-vcodec".to_string(), "libwebp".to_string(),
+        "-lossless".to_string(), "0".to_string(),
+        "-compression_level".to_string(), "4".to_string(),
+        "-quality".to_string(), quality.to_string(),
+        "-pix_fmt".to_string(), if alpha { "yuva420p" } else { "yuv420p" }.to_string(),
+        "-loop".to_string(), "0".to_string(),
+        "-an".to_string(),
+        output.to_string(),
+    ]);
+    args
+}
+
+/// ffmpeg args for one APNG tier: (max_dimension, fps). Full color with alpha, no palette
+fn apng_tier_args(
+    input_path: &str,
+    output: &str,
+    tier: (u32, u32),
+    start: Option<f64>,
+    duration: Option<f64>,
+    read_rate: &[String],
+) -> Vec<String> {
+    let (max_dim, fps) = tier;
+    let mut args = animated_input_args(input_path, None, start, duration, read_rate);
+    args.extend([
+        "-vf".to_string(), animated_scale_filter(max_dim, fps),
+        "-f".to_string(), "apng".to_string(),
+        "-pix_fmt".to_string(), "rgba".to_string(),
+        // Per-row filter choice compresses UI captures noticeably better
+        "-pred".to_string(), "mixed".to_string(),
+        "-plays".to_string(), "0".to_string(),
+        "-an".to_string(),
+        output.to_string(),
+    ]);
+    args
+}
+
+/// Scale filter used for GIF palettes: depends only on the dimension, so one palette serves every fps
+fn gif_palette_scale_filter(max_dim: u32) -> String {
+    format!(
+        "scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease:{flags},scale=trunc(iw/2)*2:trunc(ih/2)*2",
+        max_dim,
+        flags = filters::downscale_flags(None)
+    )
+}
+
+/// ffmpeg args generating a palette image for a GIF dimension over the trimmed range
+fn gif_palette_args(
+    input_path: &str,
+    palette: &str,
+    max_dim: u32,
+    start: Option<f64>,
+    duration: Option<f64>,
+    read_rate: &[String],
+) -> Vec<String> {
+    let mut args = animated_input_args(input_path, None, start, duration, read_rate);
+    args.extend([
+        "-vf".to_string(), format!("{},palettegen=stats_mode=diff", gif_palette_scale_filter(max_dim)),
+        palette.to_string(),
+    ]);
+    args
+}
+
+/// ffmpeg args for one GIF tier: (max_dimension, fps).
+/// With a pre-generated palette the encode only runs paletteuse; without one the palette is
+/// generated inline (used for short probe samples).
+fn gif_tier_args(
+    input_path: &str,
+    output: &str,
+    tier: (u32, u32),
+    palette: Option<&str>,
+    start: Option<f64>,
+    duration: Option<f64>,
+    read_rate: &[String],
+) -> Vec<String> {
+    let (max_dim, fps) = tier;
+
+    let (filter_flag, filter) = match palette {
+        Some(_) => (
+            "-lavfi",
+            format!(
+                "{},fps={}[x];[x][1:v]paletteuse=dither=bayer:bayer_scale=5",
+                gif_palette_scale_filter(max_dim),
+                fps
+            ),
+        ),
+        // For GIF, we use the split/palettegen/paletteuse filter for better quality
+        None => (
+            "-vf",
+            format!(
+                "{},split[s0][s1];[s0]palettegen=stats_mode=diff[p];[s1][p]paletteuse=dither=bayer:bayer_scale=5",
+                animated_scale_filter(max_dim, fps)
+            ),
+        ),
+    };
+
+    let mut args = animated_input_args(input_path, palette, start, duration, read_rate);
+    args.extend([
+        filter_flag.to_string(), filter,
+        "-loop".to_string(), "0".to_string(),
+        "-an".to_string(),
+        output.to_string(),
     ]);
+    args
+}
 
-    // Map metadata from chapter file if provided
-    if metadata_path.is_some() {
-        pass2_args.extend([
-            "-map".to_string(), "0".to_string(),          // Map all streams from first input (video)
-            "-map_metadata".to_string(), "1".to_string(), // Map metadata from second input (chapters)
-        ]);
-    } else {
-        pass2_args.extend(["-movflags".to_string(), "+faststart".to_string()]);
+/// Samples spread over the clip that animated-format tiers are probed with; several short
+/// ones catch a busy stretch a single sample from the middle would miss
+const TIER_PROBE_SAMPLES: usize = 3;
+const TIER_PROBE_SAMPLE_SECONDS: f64 = 2.0;
+
+/// Starts of the tier probe samples, centered in equal slices of the clip; None when the
+/// clip is short enough that encoding it in full is about as cheap
+fn tier_probe_starts(trim_start: Option<f64>, effective_duration: f64) -> Option<Vec<f64>> {
+    let sampled = TIER_PROBE_SAMPLES as f64 * TIER_PROBE_SAMPLE_SECONDS;
+    if effective_duration < sampled * 2.0 {
+        return None;
     }
+    let slice = effective_duration / TIER_PROBE_SAMPLES as f64;
+    Some(
+        (0..TIER_PROBE_SAMPLES)
+            .map(|k| trim_start.unwrap_or(0.0) + slice * (k as f64 + 0.5) - TIER_PROBE_SAMPLE_SECONDS / 2.0)
+            .collect(),
+    )
+}
 
-    pass2_args.push(output_str.to_string());
+/// Sample files for every tier from `first_tier`: (tier, sample start, scratch path)
+fn tier_probe_candidates(scratch_dir: &Path, id: &str, extension: &str, first_tier: usize, tier_count: usize, starts: &[f64]) -> Vec<(usize, f64, PathBuf)> {
+    (first_tier..tier_count)
+        .flat_map(|i| starts.iter().enumerate().map(move |(k, &start)| (i, k, start)))
+        .map(|(i, k, start)| (i, start, scratch_dir.join(format!("probe_{}_{}_{}.{}", id, i, k, extension))))
+        .collect()
+}
 
-    let pass2_refs: Vec<&str> = pass2_args.iter().map(|s| s.as_str()).collect();
+/// Estimate each tier's bytes per second from its samples (removing them) and return the
+/// first tier whose full-length estimate fits the target; None when every probe failed
+fn tier_from_samples(
+    candidates: &[(usize, f64, PathBuf)],
+    results: Vec<bool>,
+    first_tier: usize,
+    tier_count: usize,
+    effective_duration: f64,
+    target_bytes: u64,
+) -> Option<usize> {
+    // Sampled bytes per tier; a tier with a failed sample has no estimate
+    let mut sampled: HashMap<usize, Option<u64>> = HashMap::new();
+    let mut samples = 0;
+    for ((i, _, path), succeeded) in candidates.iter().zip(results) {
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let _ = fs::remove_file(path);
+        if *i == first_tier {
+            samples += 1;
+        }
 
-    run_ffmpeg_with_progress(ffmpeg, pass2_refs, effective_duration, |progress| {
-        emit_progress(&app_clone, &id_clone, 50.0 + progress * 0.50, "converting");
-    })
-    .await?;
+        let total = sampled.entry(*i).or_insert(Some(0));
+        *total = match (*total, succeeded && size > 0) {
+            (Some(total), true) => Some(total + size),
+            _ => None,
+        };
+    }
 
-    // Clean up pass log files
-    let _ = fs::remove_file(format!("{}-0.log", output_str));
-    let _ = fs::remove_file(format!("{}-0.log.mbtree", output_str));
+    let seconds_sampled = samples as f64 * TIER_PROBE_SAMPLE_SECONDS;
+    let estimates: Vec<(usize, f64)> = (first_tier..tier_count)
+        .filter_map(|i| sampled.get(&i).copied().flatten().map(|bytes| (i, bytes as f64 / seconds_sampled * effective_duration)))
+        .collect();
+    if estimates.is_empty() {
+        return None;
+    }
+    let chosen = estimates.iter().find(|(_, estimate)| *estimate <= target_bytes as f64).map(|(i, _)| *i);
+    // Nothing is predicted to fit: go straight to the smallest tier
+    Some(chosen.unwrap_or(tier_count - 1))
+}
 
-    Ok(())
+/// Encode short samples from across the clip at every candidate tier in parallel, estimate
+/// each tier's bytes per second from them, and return the first tier whose full-length
+/// estimate fits the target, so the full encode usually runs once.
+/// Returns None when probing isn't worthwhile or every probe failed.
+async fn probe_first_fitting_tier<F>(
+    ffmpeg: &PathBuf,
+    scratch_dir: &Path,
+    id: &str,
+    extension: &str,
+    first_tier: usize,
+    tier_count: usize,
+    trim_start: Option<f64>,
+    effective_duration: f64,
+    target_bytes: u64,
+    tier_args: F,
+) -> Option<usize>
+where
+    F: Fn(usize, &str, f64, f64) -> Vec<String>,
+{
+    if first_tier + 1 >= tier_count {
+        return None;
+    }
+    let starts = tier_probe_starts(trim_start, effective_duration)?;
+    let candidates = tier_probe_candidates(scratch_dir, id, extension, first_tier, tier_count, &starts);
+    let arg_sets = candidates
+        .iter()
+        .map(|(i, start, path)| tier_args(*i, &path.to_string_lossy(), *start, TIER_PROBE_SAMPLE_SECONDS))
+        .collect();
+    let results = run_ffmpeg_parallel(ffmpeg, arg_sets).await;
+    tier_from_samples(&candidates, results, first_tier, tier_count, effective_duration, target_bytes)
 }
 
-async fn convert_video_nvenc_hevc(
-    app: &tauri::AppHandle,
+/// probe_first_fitting_tier for gifski: the same samples, each decoded by ffmpeg and
+/// piped through gifski, sharing the parallel slots
+async fn probe_first_fitting_gifski_tier(
+    ffmpeg: &Path,
+    gifski_path: &Path,
+    scratch_dir: &Path,
     id: &str,
+    first_tier: usize,
     input_path: &str,
-    output_str: &str,
-    ffmpeg: &PathBuf,
-    effective_duration: f64,
-    video_bitrate_k: u32,
-    scale_filter: &str,
     trim_start: Option<f64>,
-    trim_duration: Option<f64>,
-) -> Result<(), String> {
-    let app_clone = app.clone();
-    let id_clone = id.to_string();
-
-    let bitrate_str = format!("{}k", video_bitrate_k);
-    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
-    let bufsize_str = format!("{}k", video_bitrate_k * 2);
-
-    let mut args: Vec<String> = vec!["-y".to_string()];
+    effective_duration: f64,
+    target_bytes: u64,
+    read_rate: &[String],
+) -> Option<usize> {
+    if first_tier + 1 >= GIF_TIERS.len() {
+        return None;
+    }
+    let starts = tier_probe_starts(trim_start, effective_duration)?;
+    let candidates = tier_probe_candidates(scratch_dir, id, "gif", first_tier, GIF_TIERS.len(), &starts);
+    let runs: Vec<_> = candidates
+        .iter()
+        .map(|(i, start, path)| {
+            let (max_dim, fps) = GIF_TIERS[*i];
+            let mut decode_args = animated_input_args(input_path, None, Some(*start), Some(TIER_PROBE_SAMPLE_SECONDS), read_rate);
+            decode_args.extend(["-vf".to_string(), animated_scale_filter(max_dim, fps)]);
+            let (ffmpeg, gifski_path, id) = (ffmpeg.to_path_buf(), gifski_path.to_path_buf(), id.to_string());
+            let output = path.to_string_lossy().to_string();
+            tauri::async_runtime::spawn(async move {
+                let _slot = parallel_slot().await;
+                gifski::encode(&ffmpeg, &gifski_path, &decode_args, fps, GIFSKI_QUALITY, &output, TIER_PROBE_SAMPLE_SECONDS, &id, |_| {})
+                    .await
+                    .is_ok()
+            })
+        })
+        .collect();
+    let mut results = Vec::with_capacity(runs.len());
+    for run in runs {
+        results.push(run.await.unwrap_or(false));
+    }
+    tier_from_samples(&candidates, results, first_tier, GIF_TIERS.len(), effective_duration, target_bytes)
+}
 
-    if let Some(start) = trim_start {
-        args.push("-ss".to_string());
-        args.push(format!("{:.3}", start));
+fn remove_palettes(palettes: &HashMap<u32, String>) {
+    for palette in palettes.values() {
+        let _ = fs::remove_file(palette);
     }
+}
 
-    args.push("-i".to_string());
-    args.push(input_path.to_string());
+/// WebP sizes tried largest first, each searched for the highest quality that fits:
+/// (max_dimension, fps). Never below 20fps.
+const WEBP_SIZES: &[(u32, u32)] = &[(600, 30), (600, 24), (500, 20), (400, 20), (300, 20)];
+
+/// WebP quality range searched at each size, in steps of WEBP_QUALITY_STEP
+const WEBP_MIN_QUALITY: u32 = 40;
+const WEBP_MAX_QUALITY: u32 = 80;
+const WEBP_QUALITY_STEP: u32 = 5;
+
+/// Encodes a WebP conversion may spend searching, on samples and on the whole clip;
+/// when they run out the best fit so far stands
+const WEBP_MAX_SAMPLE_ENCODES: u32 = 12;
+const WEBP_MAX_FULL_ENCODES: u32 = 6;
+
+/// Start of the sample probes are encoded from, when the clip is long enough for a
+/// sample to be cheaper than encoding it in full
+fn probe_sample_start(trim_start: Option<f64>, effective_duration: f64) -> Option<f64> {
+    if effective_duration < PROBE_SAMPLE_SECONDS * 3.0 {
+        return None;
+    }
+    Some(trim_start.unwrap_or(0.0) + (effective_duration - PROBE_SAMPLE_SECONDS) / 2.0)
+}
 
-    if let Some(duration) = trim_duration {
-        args.push("-t".to_string());
-        args.push(format!("{:.3}", duration));
+/// Highest quality from WEBP_MIN_QUALITY up whose `measure`d size fits `target_bytes`,
+/// with its size; None when even the lowest quality overshoots or `budget` ran out first.
+/// A `guess` is tried first and accepted if it fits, so an estimate from samples costs
+/// one measurement. Each measurement takes one from `budget`.
+async fn highest_fitting_quality<F, Fut>(guess: Option<u32>, target_bytes: u64, budget: &mut u32, mut measure: F) -> Result<Option<(u32, u64)>, String>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: std::future::Future<Output = Result<u64, String>>,
+{
+    let mut measure = |quality: u32, budget: &mut u32| {
+        *budget = budget.saturating_sub(1);
+        measure(quality)
+    };
+    let mut hi = WEBP_MAX_QUALITY;
+    if let (Some(guess), true) = (guess, *budget > 0) {
+        let size = measure(guess, budget).await?;
+        if size <= target_bytes {
+            return Ok(Some((guess, size)));
+        }
+        if guess <= WEBP_MIN_QUALITY {
+            return Ok(None);
+        }
+        hi = guess - WEBP_QUALITY_STEP;
     }
 
-    // NVENC HEVC encoding
-    args.extend([
-        "-c:v".to_string(), "hevc_nvenc".to_string(),
-        "-preset".to_string(), "p7".to_string(),
-        "-tune".to_string(), "hq".to_string(),
-        "-rc".to_string(), "vbr".to_string(),
-        "-b:v".to_string(), bitrate_str,
-        "-maxrate".to_string(), maxrate_str,
-        "-bufsize".to_string(), bufsize_str,
-        "-profile:v".to_string(), "main".to_string(),
-        "-vf".to_string(), scale_filter.to_string(),
-        "-c:a".to_string(), "aac".to_string(),
-        "-b:a".to_string(), "128k".to_string(),
-        "-movflags".to_string(), "+faststart".to_string(),
-        "-tag:v".to_string(), "hvc1".to_string(), // Better Apple compatibility
-        output_str.to_string(),
-    ]);
+    if *budget == 0 {
+        return Ok(None);
+    }
+    let floor = measure(WEBP_MIN_QUALITY, budget).await?;
+    if floor > target_bytes {
+        return Ok(None);
+    }
+    let mut best = (WEBP_MIN_QUALITY, floor);
+
+    // Search the steps above the floor
+    let (mut lo_step, mut hi_step) = (1, (hi - WEBP_MIN_QUALITY) / WEBP_QUALITY_STEP);
+    while lo_step <= hi_step && *budget > 0 {
+        let mid = (lo_step + hi_step) / 2;
+        let quality = WEBP_MIN_QUALITY + mid * WEBP_QUALITY_STEP;
+        let size = measure(quality, budget).await?;
+        if size <= target_bytes {
+            best = (quality, size);
+            lo_step = mid + 1;
+        } else {
+            hi_step = mid - 1;
+        }
+    }
+    Ok(Some(best))
+}
 
+/// Size of the output an ffmpeg encode wrote
+async fn encoded_size(ffmpeg: &PathBuf, id: &str, args: &[String], output: &Path, duration: f64) -> Result<u64, String> {
+    let _ = fs::remove_file(output);
     let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-
-    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, |progress| {
-        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
-    })
-    .await
+    run_ffmpeg_with_progress(ffmpeg, args_refs, duration, Some(id), |_| {}).await?;
+    Ok(fs::metadata(output).map(|m| m.len()).unwrap_or(0))
 }
 
-async fn convert_video_x265(
+async fn convert_to_webp(
     app: &tauri::AppHandle,
     id: &str,
     input_path: &str,
-    output_str: &str,
-    ffmpeg: &PathBuf,
-    effective_duration: f64,
-    video_bitrate_k: u32,
-    scale_filter: &str,
+    output_path: &Path,
+    target_bytes: u64,
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
-) -> Result<(), String> {
-    let app_clone = app.clone();
-    let id_clone = id.to_string();
+    options: &ConversionOptions,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
 
-    let bitrate_str = format!("{}k", video_bitrate_k);
-    let maxrate_str = format!("{}k", (video_bitrate_k as f64 * 1.5) as u32);
-    let bufsize_str = format!("{}k", video_bitrate_k * 2);
+    emit_progress(app, id, 0.0, "analyzing");
 
-    let mut args: Vec<String> = vec!["-y".to_string()];
+    let info = get_video_info(&ffprobe, input_path).await?;
 
-    if let Some(start) = trim_start {
-        args.push("-ss".to_string());
-        args.push(format!("{:.3}", start));
-    }
+    // Use trimmed duration if provided, otherwise use full video duration
+    let effective_duration = trim_duration.unwrap_or(info.duration);
 
-    args.push("-i".to_string());
-    args.push(input_path.to_string());
+    // Overlays captured with transparency (ProRes 4444, PNG, QuickTime Animation) keep it
+    let alpha = get_media_metadata(&ffprobe, input_path)
+        .await
+        .ok()
+        .and_then(|m| m.pixel_format)
+        .is_some_and(|pix_fmt| has_alpha(&pix_fmt));
 
-    if let Some(duration) = trim_duration {
-        args.push("-t".to_string());
-        args.push(format!("{:.3}", duration));
+    let output_str = output_path.to_string_lossy().to_string();
+    let read_rate = read_rate_args(options);
+    let workspace = scratch::JobWorkspace::new(app, id)?;
+
+    // Resume past sizes whose lowest quality an earlier attempt of this job found too large
+    let hint_key = tier_hints::hint_key("webp_sizes", input_path, trim_start, trim_duration, target_bytes);
+    let min_tier = tier_hints::first_promising_tier(&tier_hints::known_sizes(app, &hint_key), WEBP_SIZES.len(), target_bytes)
+        .max(options.min_tier.min(WEBP_SIZES.len() - 1));
+
+    // Long clips: search on a sample from the middle first, scaled to the full length,
+    // so the full encodes start at (close to) the right size and quality
+    let mut first_size = min_tier;
+    let mut guess = None;
+    if let Some(sample_start) = probe_sample_start(trim_start, effective_duration) {
+        let sample = workspace.file("sample.webp");
+        let sample_str = sample.to_string_lossy().to_string();
+        let scale = effective_duration / PROBE_SAMPLE_SECONDS;
+        let mut budget = WEBP_MAX_SAMPLE_ENCODES;
+        for (i, &(max_dim, fps)) in WEBP_SIZES.iter().enumerate().skip(min_tier) {
+            if budget == 0 {
+                break;
+            }
+            emit_progress(app, id, i as f64 / WEBP_SIZES.len() as f64 * 10.0, "analyzing");
+            let found = highest_fitting_quality(None, target_bytes, &mut budget, |quality| {
+                let args = webp_tier_args(input_path, &sample_str, (max_dim, fps, quality), Some(sample_start), Some(PROBE_SAMPLE_SECONDS), &read_rate, alpha);
+                let (ffmpeg, sample) = (&ffmpeg, &sample);
+                async move { encoded_size(ffmpeg, id, &args, sample, PROBE_SAMPLE_SECONDS).await.map(|size| (size as f64 * scale) as u64) }
+            })
+            .await;
+            match found {
+                Ok(Some((quality, _))) => {
+                    first_size = i;
+                    guess = Some(quality);
+                    break;
+                }
+                // Nothing fits even at this size; the full search starts at the smallest
+                Ok(None) => first_size = i,
+                // A sample that won't encode says nothing; search in full from the top
+                Err(_) => {
+                    first_size = min_tier;
+                    break;
+                }
+            }
+        }
     }
 
-    // CPU x265 encoding (single pass for speed, still good quality)
-    args.extend([
-        "-c:v".to_string(), "libx265".to_string(),
-        "-preset".to_string(), "medium".to_string(),
-        "-b:v".to_string(), bitrate_str,
-        "-maxrate".to_string(), maxrate_str,
-        "-bufsize".to_string(), bufsize_str,
-        "-vf".to_string(), scale_filter.to_string(),
-        "-c:a".to_string(), "aac".to_string(),
-        "-b:a".to_string(), "128k".to_string(),
-        "-movflags".to_string(), "+faststart".to_string(),
-        "-tag:v".to_string(), "hvc1".to_string(),
-        output_str.to_string(),
-    ]);
+    // Full encodes go to the workspace, the best fit so far set aside there; only the
+    // result is moved to the output path
+    let encode = workspace.file("encode.webp");
+    let encode_str = encode.to_string_lossy().to_string();
+    let kept = workspace.file("fit.webp");
+    let mut budget = WEBP_MAX_FULL_ENCODES;
+    let mut encodes = 0;
+    let mut fitted = None;
+    let mut tier = first_size;
+
+    for (i, &(max_dim, fps)) in WEBP_SIZES.iter().enumerate().skip(first_size) {
+        if budget == 0 {
+            break;
+        }
+        tier = i;
+        let found = highest_fitting_quality(guess.take(), target_bytes, &mut budget, |quality| {
+            let progress_chunk = 85.0 / WEBP_MAX_FULL_ENCODES as f64;
+            let progress_base = 10.0 + encodes as f64 * progress_chunk;
+            encodes += 1;
+            let args = webp_tier_args(input_path, &encode_str, (max_dim, fps, quality), trim_start, trim_duration, &read_rate, alpha);
+            let (app, ffmpeg, encode, kept, hint_key) = (app.clone(), &ffmpeg, &encode, &kept, &hint_key);
+            async move {
+                emit_progress(&app, id, progress_base, "converting");
+                let _ = fs::remove_file(encode);
+                let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+                let app_progress = app.clone();
+                run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, Some(id), move |progress| {
+                    emit_progress(&app_progress, id, progress_base + (progress / 100.0) * progress_chunk, "converting");
+                })
+                .await?;
+                let size = fs::metadata(encode).map(|m| m.len()).unwrap_or(0);
+                if quality == WEBP_MIN_QUALITY {
+                    tier_hints::record_size(&app, hint_key, i, size);
+                }
+                if size <= target_bytes {
+                    fs::rename(encode, kept).map_err(|e| format!("Failed to keep encode: {}", e))?;
+                }
+                Ok(size)
+            }
+        })
+        .await?;
+        // Nothing fits at this size; try the next one down
+        if let Some((_, size)) = found {
+            fitted = Some(size);
+            break;
+        }
+    }
 
-    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+    let mut warnings = Vec::new();
+    let final_size = match fitted {
+        Some(size) => {
+            move_file(&kept, output_path)?;
+            size
+        }
+        // Nothing fit: the last encode (the smallest size's lowest quality, unless the
+        // budget ran out first) is the closest there is
+        None => {
+            if budget == 0 && tier + 1 < WEBP_SIZES.len() {
+                warnings.push(format!("Stopped searching after {} encodes without reaching the target size", WEBP_MAX_FULL_ENCODES));
+            }
+            move_file(&encode, output_path)?;
+            fs::metadata(output_path).map(|m| m.len()).unwrap_or(0)
+        }
+    };
 
-    run_ffmpeg_with_progress(ffmpeg, args_refs, effective_duration, |progress| {
-        emit_progress(&app_clone, &id_clone, 5.0 + progress * 0.95, "converting");
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(final_size),
+        tier: Some(tier),
+        warnings,
+        ..Default::default()
     })
-    .await
 }
 
-async fn convert_to_webp(
+/// Quality tiers for GIF: (max_dimension, fps)
+/// GIF files get large quickly, so we're more aggressive with scaling
+const GIF_TIERS: &[(u32, u32)] = &[
+    (480, 15),
+    (400, 12),
+    (320, 10),
+    (280, 10),
+    (240, 8),
+    (200, 8),
+];
+
+/// gifski's own default; below it banding creeps back in, which is what gifski is for
+const GIFSKI_QUALITY: u32 = 90;
+
+async fn convert_to_gif(
     app: &tauri::AppHandle,
     id: &str,
     input_path: &str,
-    output_name: &str,
+    output_path: &Path,
     target_bytes: u64,
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
+    options: &ConversionOptions,
 ) -> Result<ConversionResult, String> {
     let ffmpeg = get_ffmpeg_path(app);
     let ffprobe = get_ffprobe_path(app);
 
     emit_progress(app, id, 0.0, "analyzing");
 
+    let mut warnings = Vec::new();
+    match options.gif_encoder.as_deref() {
+        None | Some("ffmpeg") => {}
+        Some("gifski") => {
+            let gifski = get_gifski_path(app);
+            if gifski::available(&gifski).await {
+                return convert_to_gif_gifski(app, id, input_path, output_path, target_bytes, trim_start, trim_duration, options, &gifski).await;
+            }
+            warnings.push("gifski isn't installed; used ffmpeg's GIF encoder instead".to_string());
+        }
+        Some(other) => return Err(format!("Unknown GIF encoder: {}", other)),
+    }
+
     let info = get_video_info(&ffprobe, input_path).await?;
 
     // Use trimmed duration if provided, otherwise use full video duration
     let effective_duration = trim_duration.unwrap_or(info.duration);
 
-    // Build output path using the provided output_name
-    let input_pathbuf = PathBuf::from(input_path);
-    let parent = input_pathbuf.parent().unwrap_or(&input_pathbuf);
-    let output_path = parent.join(output_name);
     let output_str = output_path.to_string_lossy().to_string();
 
-    // Quality tiers: (max_dimension, fps, quality)
-    // Start high quality, progressively reduce size/fps to hit target
-    // Never go below 20fps
-    let tiers: &[(u32, u32, u32)] = &[
-        (600, 30, 70),
-        (600, 24, 65),
-        (500, 20, 60),
-        (400, 20, 55),
-        (350, 20, 50),
-        (300, 20, 45),
-    ];
+    let tiers = GIF_TIERS;
 
+    let read_rate = read_rate_args(options);
     let mut final_size = 0u64;
+    let mut used_tier = None;
+
+    // Resume past tiers that an earlier attempt of this job already found too large
+    let size_limit = target_bytes * 11 / 10;
+    let hint_key = tier_hints::hint_key("gif", input_path, trim_start, trim_duration, target_bytes);
+    let known_start = tier_hints::first_promising_tier(&tier_hints::known_sizes(app, &hint_key), tiers.len(), size_limit)
+        .max(options.min_tier.min(tiers.len() - 1));
+
+    // Pick the most promising tier from parallel sample encodes instead of walking down one by one
+    let start_tier = probe_first_fitting_tier(
+        &ffmpeg, &scratch::scratch_dir(app), id, "gif", known_start, tiers.len(), trim_start, effective_duration, target_bytes,
+        |i, output, start, duration| gif_tier_args(input_path, output, tiers[i], None, Some(start), Some(duration), &read_rate),
+    )
+    .await
+    .unwrap_or(known_start);
+
+    let mut palettes: HashMap<u32, String> = HashMap::new();
 
-    for (i, &(max_dim, fps, quality)) in tiers.iter().enumerate() {
+    for (i, &tier) in tiers.iter().enumerate().skip(start_tier) {
         let progress_base = (i as f64 / tiers.len() as f64) * 90.0;
         let progress_chunk = 90.0 / tiers.len() as f64;
 
         emit_progress(app, id, progress_base, "converting");
 
-        let _ = fs::remove_file(&output_path);
-
-        // Build filter: scale to fit within max_dim x max_dim, ensure even dimensions, set fps
-        let vf_filter = format!(
-            "scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease,scale=trunc(iw/2)*2:trunc(ih/2)*2,fps={1}",
-            max_dim, fps
-        );
-        let quality_str = quality.to_string();
+        let _ = fs::remove_file(output_path);
 
         let app_clone = app.clone();
         let id_clone = id.to_string();
 
-        // Build args with optional trim parameters using hybrid seeking for frame-accuracy
-        // Hybrid seeking: fast seek (whole seconds) BEFORE -i, accurate seek (fraction) AFTER -i
-        let trim_duration_str = trim_duration.map(|d| format!("{:.3}", d));
-
-        // Split trim_start into fast seek (whole seconds) and accurate seek (fractional part)
-        let (fast_seek_str, accurate_seek_str) = if let Some(start) = trim_start {
-            let fast = start.floor();
-            let accurate = start - fast;
-            (
-                Some(format!("{:.0}", fast)),
-                if accurate > 0.001 { Some(format!("{:.3}", accurate)) } else { None }
-            )
-        } else {
-            (None, None)
+        // Generate the palette once per dimension and reuse it across fps variations
+        let max_dim = tier.0;
+        let palette = match palettes.get(&max_dim) {
+            Some(palette) => palette.clone(),
+            None => {
+                let palette = scratch::scratch_dir(app).join(format!("palette_{}_{}.png", id, max_dim));
+                let palette_str = palette.to_string_lossy().to_string();
+                let args = gif_palette_args(input_path, &palette_str, max_dim, trim_start, trim_duration, &read_rate);
+                let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+                let app_palette = app.clone();
+                let id_palette = id.to_string();
+                let palette_result = run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), move |progress| {
+                    emit_progress(&app_palette, &id_palette, progress_base + (progress / 100.0) * progress_chunk * 0.3, "converting");
+                })
+                .await;
+
+                if let Err(e) = palette_result {
+                    let _ = fs::remove_file(&palette);
+                    remove_palettes(&palettes);
+                    return Err(e);
+                }
+
+                palettes.insert(max_dim, palette_str.clone());
+                palette_str
+            }
         };
 
-        let mut args: Vec<&str> = vec!["-y"];
+        let args = gif_tier_args(input_path, &output_str, tier, Some(&palette), trim_start, trim_duration, &read_rate);
+        let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+        let encode_result = run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), move |progress| {
+            emit_progress(&app_clone, &id_clone, progress_base + (0.3 + progress / 100.0 * 0.7) * progress_chunk, "converting");
+        })
+        .await;
 
-        // Fast seek BEFORE input (seeks to nearest keyframe - fast but approximate)
-        if let Some(ref fast) = fast_seek_str {
-            args.extend(["-ss", fast.as_str()]);
+        if let Err(e) = encode_result {
+            remove_palettes(&palettes);
+            return Err(e);
         }
 
-        args.extend(["-i", input_path]);
+        final_size = fs::metadata(output_path).map(|m| m.len()).unwrap_or(0);
+        used_tier = Some(i);
+        tier_hints::record_size(app, &hint_key, i, final_size);
 
-        // Accurate seek AFTER input (decodes frames for exact positioning)
-        if let Some(ref accurate) = accurate_seek_str {
-            args.extend(["-ss", accurate.as_str()]);
+        // If within target (or 10% over), we're done
+        if final_size <= size_limit {
+            break;
         }
+    }
 
-        // Add duration AFTER input
-        if let Some(ref duration) = trim_duration_str {
-            args.extend(["-t", duration.as_str()]);
-        }
+    remove_palettes(&palettes);
 
-        args.extend([
-            "-vf", &vf_filter,
-            "-vcodec", "libwebp",
-            "-lossless", "0",
-            "-compression_level", "4",
-            "-quality", &quality_str,
-            "-loop", "0",
-            "-an",
-            &output_str,
-        ]);
+    emit_progress(app, id, 100.0, "completed");
+
+    Ok(ConversionResult {
+        success: true,
+        output_path: Some(output_str),
+        output_size: Some(final_size),
+        tier: used_tier,
+        warnings,
+        ..Default::default()
+    })
+}
+
+/// GIF through gifski, walking the same tiers as the ffmpeg encoder. gifski is slow, so
+/// tiers aren't probed in parallel; earlier attempts' sizes still let a retry skip ahead.
+async fn convert_to_gif_gifski(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    output_path: &Path,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: &ConversionOptions,
+    gifski_path: &PathBuf,
+) -> Result<ConversionResult, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    let info = get_video_info(&ffprobe, input_path).await?;
+    let effective_duration = trim_duration.unwrap_or(info.duration);
+    let output_str = output_path.to_string_lossy().to_string();
+
+    let read_rate = read_rate_args(options);
+    let mut final_size = 0u64;
+    let mut used_tier = None;
+
+    let size_limit = target_bytes * 11 / 10;
+    let hint_key = tier_hints::hint_key("gif_gifski", input_path, trim_start, trim_duration, target_bytes);
+    let known_start = tier_hints::first_promising_tier(&tier_hints::known_sizes(app, &hint_key), GIF_TIERS.len(), size_limit)
+        .max(options.min_tier.min(GIF_TIERS.len() - 1));
+
+    // Same sampling as the ffmpeg path: each tier is a full gifski encode, the costliest kind
+    emit_progress(app, id, 0.0, "analyzing");
+    let start_tier = probe_first_fitting_gifski_tier(
+        &ffmpeg, gifski_path, &scratch::scratch_dir(app), id, known_start, input_path, trim_start, effective_duration, target_bytes, &read_rate,
+    )
+    .await
+    .unwrap_or(known_start);
+
+    for (i, &(max_dim, fps)) in GIF_TIERS.iter().enumerate().skip(start_tier) {
+        let progress_base = (i as f64 / GIF_TIERS.len() as f64) * 90.0;
+        let progress_chunk = 90.0 / GIF_TIERS.len() as f64;
 
-        run_ffmpeg_with_progress(&ffmpeg, args, effective_duration, move |progress| {
+        emit_progress(app, id, progress_base, "converting");
+
+        let _ = fs::remove_file(output_path);
+
+        let mut decode_args = animated_input_args(input_path, None, trim_start, trim_duration, &read_rate);
+        decode_args.extend(["-vf".to_string(), animated_scale_filter(max_dim, fps)]);
+
+        let app_clone = app.clone();
+        let id_clone = id.to_string();
+        gifski::encode(&ffmpeg, gifski_path, &decode_args, fps, GIFSKI_QUALITY, &output_str, effective_duration, id, move |progress| {
             emit_progress(&app_clone, &id_clone, progress_base + (progress / 100.0) * progress_chunk, "converting");
         })
         .await?;
 
-        final_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        final_size = fs::metadata(output_path).map(|m| m.len()).unwrap_or(0);
+        used_tier = Some(i);
+        tier_hints::record_size(app, &hint_key, i, final_size);
 
-        // If within target (or 10% over), we're done
-        if final_size <= target_bytes * 11 / 10 {
+        if final_size <= size_limit {
             break;
         }
     }
@@ -786,18 +5565,20 @@ async fn convert_to_webp(
         success: true,
         output_path: Some(output_str),
         output_size: Some(final_size),
-        error: None,
+        tier: used_tier,
+        ..Default::default()
     })
 }
 
-async fn convert_to_gif(
+async fn convert_to_apng(
     app: &tauri::AppHandle,
     id: &str,
     input_path: &str,
-    output_name: &str,
+    output_path: &Path,
     target_bytes: u64,
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
+    options: &ConversionOptions,
 ) -> Result<ConversionResult, String> {
     let ffmpeg = get_ffmpeg_path(app);
     let ffprobe = get_ffprobe_path(app);
@@ -809,14 +5590,10 @@ async fn convert_to_gif(
     // Use trimmed duration if provided, otherwise use full video duration
     let effective_duration = trim_duration.unwrap_or(info.duration);
 
-    // Build output path using the provided output_name
-    let input_pathbuf = PathBuf::from(input_path);
-    let parent = input_pathbuf.parent().unwrap_or(&input_pathbuf);
-    let output_path = parent.join(output_name);
     let output_str = output_path.to_string_lossy().to_string();
 
-    // Quality tiers for GIF: (max_dimension, fps)
-    // GIF files get large quickly, so we're more aggressive with scaling
+    // Quality tiers for APNG: (max_dimension, fps)
+    // Frames are stored losslessly, so only size and frame rate can give
     let tiers: &[(u32, u32)] = &[
         (480, 15),
         (400, 12),
@@ -826,82 +5603,49 @@ async fn convert_to_gif(
         (200, 8),
     ];
 
+    let read_rate = read_rate_args(options);
     let mut final_size = 0u64;
+    let mut used_tier = None;
+
+    // Resume past tiers that an earlier attempt of this job already found too large
+    let size_limit = target_bytes * 11 / 10;
+    let hint_key = tier_hints::hint_key("apng", input_path, trim_start, trim_duration, target_bytes);
+    let known_start = tier_hints::first_promising_tier(&tier_hints::known_sizes(app, &hint_key), tiers.len(), size_limit)
+        .max(options.min_tier.min(tiers.len() - 1));
+
+    // Pick the most promising tier from parallel sample encodes instead of walking down one by one
+    let start_tier = probe_first_fitting_tier(
+        &ffmpeg, &scratch::scratch_dir(app), id, "apng", known_start, tiers.len(), trim_start, effective_duration, target_bytes,
+        |i, output, start, duration| apng_tier_args(input_path, output, tiers[i], Some(start), Some(duration), &read_rate),
+    )
+    .await
+    .unwrap_or(known_start);
 
-    for (i, &(max_dim, fps)) in tiers.iter().enumerate() {
+    for (i, &tier) in tiers.iter().enumerate().skip(start_tier) {
         let progress_base = (i as f64 / tiers.len() as f64) * 90.0;
         let progress_chunk = 90.0 / tiers.len() as f64;
 
         emit_progress(app, id, progress_base, "converting");
 
-        let _ = fs::remove_file(&output_path);
-
-        // Build filter for scaling and fps
-        // GIF requires palette generation for good quality
-        let scale_filter = format!(
-            "scale='min({0},iw)':'min({0},ih)':force_original_aspect_ratio=decrease,scale=trunc(iw/2)*2:trunc(ih/2)*2,fps={1}",
-            max_dim, fps
-        );
-
-        // For GIF, we use the split/palettegen/paletteuse filter for better quality
-        let vf_filter = format!(
-            "{},split[s0][s1];[s0]palettegen=stats_mode=diff[p];[s1][p]paletteuse=dither=bayer:bayer_scale=5",
-            scale_filter
-        );
+        let _ = fs::remove_file(output_path);
 
         let app_clone = app.clone();
         let id_clone = id.to_string();
 
-        // Build args with optional trim parameters
-        let trim_duration_str = trim_duration.map(|d| format!("{:.3}", d));
-
-        // Split trim_start into fast seek and accurate seek
-        let (fast_seek_str, accurate_seek_str) = if let Some(start) = trim_start {
-            let fast = start.floor();
-            let accurate = start - fast;
-            (
-                Some(format!("{:.0}", fast)),
-                if accurate > 0.001 { Some(format!("{:.3}", accurate)) } else { None }
-            )
-        } else {
-            (None, None)
-        };
-
-        let mut args: Vec<&str> = vec!["-y"];
-
-        // Fast seek BEFORE input
-        if let Some(ref fast) = fast_seek_str {
-            args.extend(["-ss", fast.as_str()]);
-        }
-
-        args.extend(["-i", input_path]);
-
-        // Accurate seek AFTER input
-        if let Some(ref accurate) = accurate_seek_str {
-            args.extend(["-ss", accurate.as_str()]);
-        }
-
-        // Add duration AFTER input
-        if let Some(ref duration) = trim_duration_str {
-            args.extend(["-t", duration.as_str()]);
-        }
-
-        args.extend([
-            "-vf", &vf_filter,
-            "-loop", "0",
-            "-an",
-            &output_str,
-        ]);
+        let args = apng_tier_args(input_path, &output_str, tier, trim_start, trim_duration, &read_rate);
+        let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
 
-        run_ffmpeg_with_progress(&ffmpeg, args, effective_duration, move |progress| {
+        run_ffmpeg_with_progress(&ffmpeg, args_refs, effective_duration, Some(id), move |progress| {
             emit_progress(&app_clone, &id_clone, progress_base + (progress / 100.0) * progress_chunk, "converting");
         })
         .await?;
 
-        final_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        final_size = fs::metadata(output_path).map(|m| m.len()).unwrap_or(0);
+        used_tier = Some(i);
+        tier_hints::record_size(app, &hint_key, i, final_size);
 
         // If within target (or 10% over), we're done
-        if final_size <= target_bytes * 11 / 10 {
+        if final_size <= size_limit {
             break;
         }
     }
@@ -912,6 +5656,95 @@ async fn convert_to_gif(
         success: true,
         output_path: Some(output_str),
         output_size: Some(final_size),
-        error: None,
+        tier: used_tier,
+        ..Default::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(id: u32, time: f64, name: Option<&str>) -> Marker {
+        Marker { id, time, name: name.map(str::to_string) }
+    }
+
+    /// (start ms, end ms, title) of each chapter, undoing the value escaping
+    fn parse_chapter_metadata(content: &str) -> Vec<(u64, u64, String)> {
+        let mut chapters = Vec::new();
+        for block in content.split("[CHAPTER]").skip(1) {
+            let mut chapter = (0, 0, String::new());
+            for line in block.lines() {
+                if let Some(start) = line.strip_prefix("START=") {
+                    chapter.0 = start.parse().unwrap();
+                } else if let Some(end) = line.strip_prefix("END=") {
+                    chapter.1 = end.parse().unwrap();
+                } else if let Some(title) = line.strip_prefix("title=") {
+                    let mut unescaped = String::new();
+                    let mut chars = title.chars();
+                    while let Some(c) = chars.next() {
+                        unescaped.push(if c == '\\' { chars.next().unwrap() } else { c });
+                    }
+                    chapter.2 = unescaped;
+                }
+            }
+            chapters.push(chapter);
+        }
+        chapters
+    }
+
+    #[test]
+    fn trim_keeps_markers_in_range_relative_to_start() {
+        let markers = [marker(1, 1.0, None), marker(2, 5.0, None), marker(3, 12.0, None), marker(4, 20.0, None)];
+        let adjusted = adjust_markers_for_trim(&markers, Some(4.0), Some(10.0));
+        let times: Vec<(u32, f64)> = adjusted.iter().map(|m| (m.id, m.time)).collect();
+        assert_eq!(times, vec![(2, 1.0), (3, 8.0)]);
+    }
+
+    #[test]
+    fn untrimmed_markers_are_unchanged() {
+        let markers = [marker(1, 0.0, Some("Intro")), marker(2, 7.5, None)];
+        let adjusted = adjust_markers_for_trim(&markers, None, None);
+        let times: Vec<f64> = adjusted.iter().map(|m| m.time).collect();
+        assert_eq!(times, vec![0.0, 7.5]);
+        assert_eq!(adjusted[0].name.as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn chapter_metadata_round_trips() {
+        let markers = [marker(1, 30.0, Some("Outro")), marker(2, 10.0, Some("Main")), marker(3, 65.0, None)];
+        let adjusted = adjust_markers_for_trim(&markers, Some(5.0), None);
+        let chapters = parse_chapter_metadata(&generate_chapter_metadata(&adjusted, 90.0));
+        assert_eq!(
+            chapters,
+            vec![
+                (5000, 25000, "Main".to_string()),
+                (25000, 60000, "Outro".to_string()),
+                (60000, 90000, "Chapter 3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn chapter_titles_with_metadata_syntax_round_trip() {
+        let title = "Q&A; part=2 #1 \\ end";
+        let content = generate_chapter_metadata(&[marker(1, 0.0, Some(title))], 10.0);
+        assert_eq!(parse_chapter_metadata(&content)[0].2, title);
+    }
+
+    #[test]
+    fn no_markers_no_metadata() {
+        assert_eq!(generate_chapter_metadata(&[], 10.0), "");
+    }
+
+    #[test]
+    fn chapter_file_lives_in_the_workspace() {
+        let parent = tempfile::tempdir().unwrap();
+        let workspace = scratch::JobWorkspace::create_in(parent.path(), "job1").unwrap();
+        let path = write_chapter_file(&workspace, &[marker(1, 1.0, Some("One"))], 5.0).unwrap();
+        assert_eq!(path.parent(), Some(workspace.file("").as_path()));
+        assert_eq!(parse_chapter_metadata(&fs::read_to_string(&path).unwrap()), vec![(1000, 5000, "One".to_string())]);
+        drop(workspace);
+        assert!(!path.exists());
+    }
+}
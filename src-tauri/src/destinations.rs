@@ -0,0 +1,509 @@
+use crate::converter::{container_bytes, reduced_frame_rates, MIN_SCALED_BITS_PER_PIXEL};
+use crate::ffmpeg::{get_media_metadata, MediaMetadata};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bytes per MB, matching the frontend's size inputs
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+
+/// Output heights tried from best to smallest
+const CANDIDATE_HEIGHTS: &[u32] = &[1080, 720, 540, 480, 360];
+
+/// Frame rate assumed for a source that doesn't report one
+const DEFAULT_FPS: f64 = 30.0;
+
+const AUDIO_BITRATE: f64 = 128_000.0;
+
+/// Silent clips up to this length are better shared as animated WebP
+const ANIMATED_MAX_DURATION: f64 = 6.0;
+
+/// Properties of the source the recommendation is based on
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceProperties {
+    pub width: u32,
+    pub height: u32,
+    pub fps: Option<f64>,
+    #[serde(rename = "hasAudio", default)]
+    pub has_audio: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DestinationRecommendation {
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    pub width: u32,
+    pub height: u32,
+    pub fps: Option<f64>,
+    /// Set when even the smallest resolution is below the quality floor
+    pub warning: Option<String>,
+}
+
+/// Upload constraints of a destination; unset or empty fields aren't limited
+struct PlatformLimits {
+    max_mb: f64,
+    /// Seconds
+    max_duration: Option<f64>,
+    /// Longest and shortest side, so portrait and landscape are treated alike
+    max_long_side: Option<u32>,
+    max_short_side: Option<u32>,
+    containers: &'static [&'static str],
+    video_codecs: &'static [&'static str],
+    audio_codecs: &'static [&'static str],
+    /// Required pixel format, e.g. "yuv420p" (players reject 4:4:4 and 10-bit H.264)
+    pixel_format: Option<&'static str>,
+    even_dimensions: bool,
+    max_fps: Option<f64>,
+}
+
+impl PlatformLimits {
+    fn size_only(max_mb: f64) -> Self {
+        PlatformLimits {
+            max_mb,
+            max_duration: None,
+            max_long_side: None,
+            max_short_side: None,
+            containers: &[],
+            video_codecs: &[],
+            audio_codecs: &[],
+            pixel_format: None,
+            even_dimensions: false,
+            max_fps: None,
+        }
+    }
+}
+
+/// The named destinations, for get_platform_presets ("custom" takes a size instead)
+const PLATFORMS: &[&str] = &[
+    "discord-free",
+    "discord-nitro-basic",
+    "discord-nitro",
+    "email",
+    "twitter",
+    "instagram",
+    "whatsapp",
+    "slack",
+];
+
+/// Constraints for a named destination
+fn platform_limits(destination: &str, custom_mb: Option<f64>) -> Result<PlatformLimits, String> {
+    match destination {
+        "discord-free" => Ok(PlatformLimits::size_only(10.0)),
+        "discord-nitro-basic" => Ok(PlatformLimits::size_only(25.0)),
+        "discord-nitro" => Ok(PlatformLimits::size_only(500.0)),
+        // 25 MB attachment limit, but base64 encoding inflates attachments by a third
+        "email" => Ok(PlatformLimits::size_only(18.0)),
+        "twitter" => Ok(PlatformLimits {
+            max_mb: 512.0,
+            max_duration: Some(140.0),
+            max_long_side: Some(1920),
+            max_short_side: Some(1200),
+            containers: &["mp4", "mov"],
+            video_codecs: &["h264"],
+            audio_codecs: &["aac"],
+            pixel_format: Some("yuv420p"),
+            even_dimensions: true,
+            max_fps: Some(60.0),
+        }),
+        // Reels
+        "instagram" => Ok(PlatformLimits {
+            max_mb: 650.0,
+            max_duration: Some(180.0),
+            max_long_side: Some(1920),
+            max_short_side: Some(1080),
+            containers: &["mp4", "mov"],
+            video_codecs: &["h264", "hevc"],
+            audio_codecs: &["aac"],
+            pixel_format: Some("yuv420p"),
+            even_dimensions: true,
+            max_fps: Some(60.0),
+        }),
+        // Sent as a video rather than a document, so it plays in the chat
+        "whatsapp" => Ok(PlatformLimits {
+            max_mb: 16.0,
+            max_duration: None,
+            max_long_side: None,
+            max_short_side: None,
+            containers: &["mp4"],
+            video_codecs: &["h264"],
+            audio_codecs: &["aac"],
+            pixel_format: Some("yuv420p"),
+            even_dimensions: true,
+            max_fps: None,
+        }),
+        // Any file uploads, but only H.264 in MP4/MOV plays inline
+        "slack" => Ok(PlatformLimits {
+            max_mb: 1024.0,
+            max_duration: None,
+            max_long_side: None,
+            max_short_side: None,
+            containers: &["mp4", "mov"],
+            video_codecs: &["h264"],
+            audio_codecs: &["aac"],
+            pixel_format: None,
+            even_dimensions: false,
+            max_fps: None,
+        }),
+        "custom" => match custom_mb {
+            Some(mb) if mb > 0.0 => Ok(PlatformLimits::size_only(mb)),
+            _ => Err("A custom destination needs a size in MB".to_string()),
+        },
+        _ => Err(format!("Unknown destination: {}", destination)),
+    }
+}
+
+/// A destination's upload constraints, for the UI; unset or empty fields aren't limited
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformPreset {
+    pub name: String,
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: u64,
+    /// Seconds
+    #[serde(rename = "maxDuration")]
+    pub max_duration: Option<f64>,
+    #[serde(rename = "maxLongSide")]
+    pub max_long_side: Option<u32>,
+    #[serde(rename = "maxShortSide")]
+    pub max_short_side: Option<u32>,
+    #[serde(rename = "maxFps")]
+    pub max_fps: Option<f64>,
+    pub containers: Vec<String>,
+    #[serde(rename = "videoCodecs")]
+    pub video_codecs: Vec<String>,
+    #[serde(rename = "audioCodecs")]
+    pub audio_codecs: Vec<String>,
+    #[serde(rename = "pixelFormat")]
+    pub pixel_format: Option<String>,
+}
+
+/// Every named destination with its constraints
+pub fn platform_presets() -> Vec<PlatformPreset> {
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    PLATFORMS
+        .iter()
+        .filter_map(|name| {
+            let limits = platform_limits(name, None).ok()?;
+            Some(PlatformPreset {
+                name: name.to_string(),
+                max_bytes: (limits.max_mb * BYTES_PER_MB) as u64,
+                max_duration: limits.max_duration,
+                max_long_side: limits.max_long_side,
+                max_short_side: limits.max_short_side,
+                max_fps: limits.max_fps,
+                containers: strings(limits.containers),
+                video_codecs: strings(limits.video_codecs),
+                audio_codecs: strings(limits.audio_codecs),
+                pixel_format: limits.pixel_format.map(|f| f.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// What a conversion is going to write, as ffprobe will report it; None where unknown
+pub struct PlannedOutput<'a> {
+    pub extension: &'a str,
+    pub video_codec: Option<&'a str>,
+    pub audio_codec: Option<&'a str>,
+    /// Seconds
+    pub duration: f64,
+}
+
+/// Check a conversion against a destination before encoding: the format must be one it
+/// takes and the clip short enough. Returns the target capped to the destination's size
+/// limit (a custom destination's limit is the target itself).
+pub fn check_planned(destination: &str, target_bytes: u64, planned: &PlannedOutput) -> Result<u64, String> {
+    if destination == "custom" {
+        return Ok(target_bytes);
+    }
+    let limits = platform_limits(destination, None)?;
+    let rejected = |what: &str, allowed: &[&str], actual: Option<&str>| match actual {
+        Some(actual) if !allowed.is_empty() && !allowed.contains(&actual) => Some(format!(
+            "{} doesn't accept {} {} (allowed: {})",
+            destination,
+            actual,
+            what,
+            allowed.join(", ")
+        )),
+        _ => None,
+    };
+    let format_error = rejected("files", limits.containers, Some(planned.extension))
+        .or_else(|| rejected("video", limits.video_codecs, planned.video_codec))
+        .or_else(|| rejected("audio", limits.audio_codecs, planned.audio_codec));
+    if let Some(error) = format_error {
+        return Err(error);
+    }
+    if let Some(max) = limits.max_duration {
+        if planned.duration > max + 0.5 {
+            return Err(format!(
+                "{} takes videos up to {:.0}s; trim the clip (it is {:.0}s)",
+                destination, max, planned.duration
+            ));
+        }
+    }
+    Ok(target_bytes.min((limits.max_mb * BYTES_PER_MB) as u64))
+}
+
+/// Filters a conversion needs to meet a destination's format requirements
+#[derive(Debug, Clone, Default)]
+pub struct PlatformFixups {
+    /// Appended to the video filter chain
+    pub filters: Vec<String>,
+    /// Short descriptions of the fix-ups, reported in the conversion result
+    pub applied: Vec<String>,
+    /// Pixel format the destination requires, even when the encode already produces it
+    pub pixel_format: Option<&'static str>,
+}
+
+/// What a conversion path produces before any fix-up (after its own scaling and frame
+/// rate choice); requirements it already meets are neither applied nor reported
+pub struct BaseOutput<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: Option<f64>,
+    pub pixel_format: &'a str,
+}
+
+impl PlatformFixups {
+    pub fn filter(&self) -> Option<String> {
+        if self.filters.is_empty() {
+            None
+        } else {
+            Some(self.filters.join(","))
+        }
+    }
+}
+
+/// Work out which of a destination's format requirements the output would miss
+pub fn platform_fixups(destination: &str, source: &MediaMetadata, base: &BaseOutput) -> Result<PlatformFixups, String> {
+    // Custom destinations only limit size
+    if destination == "custom" {
+        return Ok(PlatformFixups::default());
+    }
+    let limits = platform_limits(destination, None)?;
+    let mut fixups = PlatformFixups {
+        pixel_format: limits.pixel_format,
+        ..Default::default()
+    };
+
+    if let (Some(long), Some(short)) = (limits.max_long_side, limits.max_short_side) {
+        // Rotated phone video is displayed (and encoded) with the sides swapped
+        let (width, height) = match source.rotation {
+            Some(90) | Some(-90) | Some(270) | Some(-270) => (base.height, base.width),
+            _ => (base.width, base.height),
+        };
+        if width.max(height) > long || width.min(height) > short {
+            let (max_width, max_height) = if width >= height { (long, short) } else { (short, long) };
+            fixups.filters.push(format!(
+                "scale={}:{}:force_original_aspect_ratio=decrease:force_divisible_by=2",
+                max_width, max_height
+            ));
+            fixups.applied.push(format!("resolution capped at {}x{}", max_width, max_height));
+        }
+    }
+    if limits.even_dimensions && (!base.width.is_multiple_of(2) || !base.height.is_multiple_of(2)) {
+        fixups.filters.push("scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string());
+        fixups.applied.push("even dimensions".to_string());
+    }
+    if let Some(max) = limits.max_fps {
+        if base.frame_rate.is_some_and(|fps| fps > max + 0.01) {
+            fixups.filters.push(format!("fps={}", max));
+            fixups.applied.push(format!("frame rate capped at {}", max));
+        }
+    }
+    if let Some(format) = limits.pixel_format {
+        if base.pixel_format != format {
+            fixups.filters.push(format!("format={}", format));
+            fixups.applied.push(format!("pixel format {}", format));
+        }
+    }
+
+    Ok(fixups)
+}
+
+/// Width for `height` keeping the source aspect ratio, rounded to an even number
+fn scaled_width(source: &SourceProperties, height: u32) -> u32 {
+    if source.height == 0 {
+        return height * 16 / 9;
+    }
+    let width = (source.width as f64 * height as f64 / source.height as f64).round() as u32;
+    width + width % 2
+}
+
+/// Recommend conversion type, target size, resolution and frame rate for a destination
+pub fn recommend(
+    destination: &str,
+    custom_mb: Option<f64>,
+    duration: f64,
+    source: &SourceProperties,
+) -> Result<DestinationRecommendation, String> {
+    if duration <= 0.0 {
+        return Err("Duration must be positive".to_string());
+    }
+
+    let limits = platform_limits(destination, custom_mb)?;
+    let limit_mb = limits.max_mb;
+    let target_bytes = (limit_mb * BYTES_PER_MB) as u64;
+
+    // Only where the destination takes any file type
+    if !source.has_audio && duration <= ANIMATED_MAX_DURATION && limits.containers.is_empty() {
+        return Ok(DestinationRecommendation {
+            conversion_type: "webp".to_string(),
+            target_bytes,
+            width: source.width,
+            height: source.height,
+            fps: source.fps,
+            warning: None,
+        });
+    }
+
+    let source_fps = source.fps.unwrap_or(DEFAULT_FPS);
+    let audio = if source.has_audio { AUDIO_BITRATE } else { 0.0 };
+    let container = container_bytes("mp4", duration, source_fps, source.has_audio as usize, 0);
+    let video_bps = target_bytes.saturating_sub(container) as f64 * 8.0 / duration - audio;
+
+    // Lower rates only where frames drop evenly, as reduceFrameRate does
+    let mut fps_options = vec![source_fps];
+    fps_options.extend(reduced_frame_rates(source_fps));
+
+    // Never upscale: the source height is the first candidate when it's below 1080p
+    let mut heights: Vec<u32> = CANDIDATE_HEIGHTS
+        .iter()
+        .copied()
+        .filter(|&h| h < source.height)
+        .collect();
+    heights.insert(0, source.height.min(CANDIDATE_HEIGHTS[0]));
+
+    // Keep resolution before frame rate: 1080p30 beats 720p60 for most shared clips
+    for &height in &heights {
+        let width = scaled_width(source, height);
+        for &fps in &fps_options {
+            let bpp = video_bps / (width as f64 * height as f64 * fps);
+            if bpp >= MIN_SCALED_BITS_PER_PIXEL {
+                return Ok(DestinationRecommendation {
+                    conversion_type: "mp4".to_string(),
+                    target_bytes,
+                    width,
+                    height,
+                    fps: Some(fps),
+                    warning: None,
+                });
+            }
+        }
+    }
+
+    let height = *heights.last().unwrap_or(&CANDIDATE_HEIGHTS[CANDIDATE_HEIGHTS.len() - 1]);
+    Ok(DestinationRecommendation {
+        conversion_type: "mp4".to_string(),
+        target_bytes,
+        width: scaled_width(source, height),
+        height,
+        fps: fps_options.last().copied(),
+        warning: Some(format!(
+            "{:.0}s is long for {} MB; expect low quality or trim the clip",
+            duration, limit_mb
+        )),
+    })
+}
+
+/// Result of checking one platform constraint against a finished output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConstraintCheck {
+    /// "size", "duration", "dimensions", "frameRate", "evenDimensions", "pixelFormat",
+    /// "container", "videoCodec" or "audioCodec"
+    pub constraint: String,
+    pub passed: bool,
+    pub limit: String,
+    pub actual: String,
+}
+
+fn check(constraint: &str, passed: bool, limit: String, actual: String) -> ConstraintCheck {
+    ConstraintCheck {
+        constraint: constraint.to_string(),
+        passed,
+        limit,
+        actual,
+    }
+}
+
+/// Whitelist check; streams the output doesn't have (e.g. no audio) pass
+fn allowed_check(constraint: &str, allowed: &[&str], actual: Option<&str>) -> Option<ConstraintCheck> {
+    if allowed.is_empty() {
+        return None;
+    }
+    let actual = actual?;
+    Some(check(constraint, allowed.contains(&actual), allowed.join(", "), actual.to_string()))
+}
+
+/// Check a finished output against a destination's upload constraints. A custom
+/// destination's size limit is the conversion target.
+pub async fn validate_output(
+    ffprobe_path: &PathBuf,
+    output_path: &Path,
+    destination: &str,
+    target_bytes: u64,
+) -> Result<Vec<ConstraintCheck>, String> {
+    let limits = platform_limits(destination, Some(target_bytes as f64 / BYTES_PER_MB))?;
+    let output_str = output_path.to_string_lossy().to_string();
+    let metadata = get_media_metadata(ffprobe_path, &output_str).await?;
+    let size = std::fs::metadata(output_path)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read output size: {}", e))?;
+
+    let max_bytes = (limits.max_mb * BYTES_PER_MB) as u64;
+    let mut checks = vec![check(
+        "size",
+        size <= max_bytes,
+        format!("{} bytes", max_bytes),
+        format!("{} bytes", size),
+    )];
+
+    if let Some(max) = limits.max_duration {
+        checks.push(check(
+            "duration",
+            metadata.duration <= max,
+            format!("{:.0}s", max),
+            format!("{:.1}s", metadata.duration),
+        ));
+    }
+
+    if let (Some(long), Some(short)) = (limits.max_long_side, limits.max_short_side) {
+        if metadata.width > 0 && metadata.height > 0 {
+            let (w, h) = (metadata.width, metadata.height);
+            checks.push(check(
+                "dimensions",
+                w.max(h) <= long && w.min(h) <= short,
+                format!("{}x{}", long, short),
+                format!("{}x{}", w, h),
+            ));
+        }
+    }
+
+    if let Some(max) = limits.max_fps {
+        if let Some(fps) = metadata.frame_rate_decimal {
+            checks.push(check("frameRate", fps <= max + 0.01, format!("{}", max), format!("{:.2}", fps)));
+        }
+    }
+    if limits.even_dimensions && metadata.width > 0 {
+        checks.push(check(
+            "evenDimensions",
+            metadata.width.is_multiple_of(2) && metadata.height.is_multiple_of(2),
+            "even".to_string(),
+            format!("{}x{}", metadata.width, metadata.height),
+        ));
+    }
+    if let Some(format) = limits.pixel_format {
+        checks.extend(allowed_check("pixelFormat", &[format], metadata.pixel_format.as_deref()));
+    }
+
+    // ffprobe reports mp4 and mov as the same format, so go by the extension
+    let extension = output_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    checks.extend(allowed_check("container", limits.containers, Some(&extension)));
+    checks.extend(allowed_check("videoCodec", limits.video_codecs, metadata.video_codec.as_deref()));
+    checks.extend(allowed_check("audioCodec", limits.audio_codecs, metadata.audio_codec.as_deref()));
+
+    Ok(checks)
+}
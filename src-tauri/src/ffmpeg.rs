@@ -1,11 +1,18 @@
 #![allow(unused_imports)]
 
+use crate::cache::file_cache_key;
+use crate::job_log;
+use crate::resources;
 use regex::Regex;
+use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use tauri::Manager;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 #[cfg(target_os = "windows")]
 const FFMPEG_NAME: &str = "ffmpeg.exe";
@@ -17,6 +24,11 @@ const FFMPEG_NAME: &str = "ffmpeg";
 #[cfg(not(target_os = "windows"))]
 const FFPROBE_NAME: &str = "ffprobe";
 
+#[cfg(target_os = "windows")]
+const GIFSKI_NAME: &str = "gifski.exe";
+#[cfg(not(target_os = "windows"))]
+const GIFSKI_NAME: &str = "gifski";
+
 fn find_binary(app: &tauri::AppHandle, name: &str) -> PathBuf {
     // 1. Check development path (src-tauri/ffmpeg/)
     if let Ok(exe_path) = std::env::current_exe() {
@@ -55,6 +67,11 @@ pub fn get_ffprobe_path(app: &tauri::AppHandle) -> PathBuf {
     find_binary(app, FFPROBE_NAME)
 }
 
+/// Optional gifski binary, bundled next to ffmpeg
+pub fn get_gifski_path(app: &tauri::AppHandle) -> PathBuf {
+    find_binary(app, GIFSKI_NAME)
+}
+
 #[derive(Debug, Clone)]
 pub struct VideoInfo {
     pub duration: f64,
@@ -62,7 +79,39 @@ pub struct VideoInfo {
     pub height: u32,
 }
 
+/// Probes kept for reuse, e.g. by the renditions of one input; keyed by path, size and
+/// mtime so a changed file is probed again
+const PROBE_CACHE_ENTRIES: usize = 16;
+
+static VIDEO_INFO_CACHE: Mutex<VecDeque<(String, VideoInfo)>> = Mutex::new(VecDeque::new());
+static METADATA_CACHE: Mutex<VecDeque<(String, MediaMetadata)>> = Mutex::new(VecDeque::new());
+
+fn cached<T: Clone>(cache: &Mutex<VecDeque<(String, T)>>, key: &str) -> Option<T> {
+    let cache = cache.lock().ok()?;
+    cache.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+}
+
+fn store<T>(cache: &Mutex<VecDeque<(String, T)>>, key: String, value: T) {
+    if let Ok(mut cache) = cache.lock() {
+        cache.retain(|(k, _)| *k != key);
+        if cache.len() == PROBE_CACHE_ENTRIES {
+            cache.pop_front();
+        }
+        cache.push_back((key, value));
+    }
+}
+
 pub async fn get_video_info(ffprobe_path: &PathBuf, input: &str) -> Result<VideoInfo, String> {
+    let key = file_cache_key(input);
+    if let Some(info) = cached(&VIDEO_INFO_CACHE, &key) {
+        return Ok(info);
+    }
+    let info = probe_video_info(ffprobe_path, input).await?;
+    store(&VIDEO_INFO_CACHE, key, info.clone());
+    Ok(info)
+}
+
+async fn probe_video_info(ffprobe_path: &PathBuf, input: &str) -> Result<VideoInfo, String> {
     // Debug: show which ffprobe we're using
     let ffprobe_exists = ffprobe_path.exists();
 
@@ -149,6 +198,8 @@ pub struct MediaMetadata {
     pub video_bitrate: Option<u64>,
     pub pixel_format: Option<String>,
     pub color_space: Option<String>,
+    /// Display rotation in degrees (phones store portrait video as rotated landscape)
+    pub rotation: Option<i32>,
     pub duration: f64,
 
     // Audio stream info
@@ -166,6 +217,16 @@ pub struct MediaMetadata {
 }
 
 pub async fn get_media_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<MediaMetadata, String> {
+    let key = file_cache_key(input);
+    if let Some(metadata) = cached(&METADATA_CACHE, &key) {
+        return Ok(metadata);
+    }
+    let metadata = probe_media_metadata(ffprobe_path, input).await?;
+    store(&METADATA_CACHE, key, metadata.clone());
+    Ok(metadata)
+}
+
+async fn probe_media_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<MediaMetadata, String> {
     let output = Command::new(ffprobe_path)
         .args([
             "-v", "quiet",
@@ -196,6 +257,7 @@ pub async fn get_media_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<M
         video_bitrate: None,
         pixel_format: None,
         color_space: None,
+        rotation: None,
         duration: 0.0,
         audio_codec: None,
         audio_codec_long: None,
@@ -238,6 +300,18 @@ pub async fn get_media_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<M
                         .and_then(|v| v.as_str())
                         .and_then(|s| s.parse().ok());
 
+                    // Rotation is either a legacy "rotate" tag or display matrix side data
+                    metadata.rotation = stream.get("tags")
+                        .and_then(|t| t.get("rotate"))
+                        .and_then(|v| v.as_str())
+                        .and_then(|s| s.parse().ok())
+                        .or_else(|| {
+                            stream.get("side_data_list")
+                                .and_then(|v| v.as_array())
+                                .and_then(|list| list.iter().find_map(|sd| sd.get("rotation").and_then(|r| r.as_i64())))
+                                .map(|r| r as i32)
+                        });
+
                     // Parse frame rate (usually in format "30000/1001" or "30/1")
                     if let Some(fps_str) = stream.get("r_frame_rate").and_then(|v| v.as_str()) {
                         metadata.frame_rate = Some(fps_str.to_string());
@@ -280,10 +354,328 @@ pub async fn get_media_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<M
     Ok(metadata)
 }
 
+/// Per-stream audio details, used when every audio track is carried over
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioStreamInfo {
+    /// Index among the file's audio streams (matches ffmpeg's `a:N` specifier)
+    pub index: u32,
+    pub codec: Option<String>,
+    pub bitrate: Option<u64>,
+    pub channels: Option<u32>,
+    /// Decoded sample format, e.g. "s16", "s32p" (24-bit FLAC/PCM) or "fltp"
+    #[serde(rename = "sampleFormat")]
+    pub sample_format: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    /// MP4/MOV handler name; OBS writes its track names here
+    pub handler: Option<String>,
+}
+
+pub async fn get_audio_streams(ffprobe_path: &PathBuf, input: &str) -> Result<Vec<AudioStreamInfo>, String> {
+    let output = Command::new(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-select_streams", "a",
+            "-show_entries", "stream=codec_name,bit_rate,channels,sample_fmt:stream_tags",
+            input,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err("ffprobe failed to analyze file".to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    let streams = json.get("streams").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+
+    Ok(streams
+        .iter()
+        .enumerate()
+        .map(|(i, stream)| {
+            let tags = stream.get("tags");
+            let tag = |name: &str| tags.and_then(|t| t.get(name)).and_then(|v| v.as_str()).map(String::from);
+
+            AudioStreamInfo {
+                index: i as u32,
+                codec: stream.get("codec_name").and_then(|v| v.as_str()).map(String::from),
+                // MKV files usually only carry bitrate in mkvmerge's BPS statistics tag
+                bitrate: stream.get("bit_rate")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| s.parse().ok())
+                    .or_else(|| tag("BPS").and_then(|s| s.parse().ok())),
+                channels: stream.get("channels").and_then(|v| v.as_u64()).map(|v| v as u32),
+                sample_format: stream.get("sample_fmt").and_then(|v| v.as_str()).map(String::from),
+                language: tag("language"),
+                title: tag("title"),
+                handler: tag("handler_name"),
+            }
+        })
+        .collect())
+}
+
+/// Read the container-level metadata tags (title, comment, encoder, ...) of a file
+pub async fn get_format_tags(ffprobe_path: &PathBuf, input: &str) -> Result<Vec<(String, String)>, String> {
+    let output = Command::new(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-show_entries", "format_tags",
+            input,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err("ffprobe failed to analyze file".to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    let tags = json
+        .get("format")
+        .and_then(|f| f.get("tags"))
+        .and_then(|t| t.as_object())
+        .map(|obj| {
+            obj.iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(tags)
+}
+
+/// Chapter start times in seconds, as written in the container
+pub async fn get_chapter_starts(ffprobe_path: &PathBuf, input: &str) -> Result<Vec<f64>, String> {
+    let output = Command::new(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-show_chapters",
+            input,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err("ffprobe failed to analyze file".to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+
+    let starts = json
+        .get("chapters")
+        .and_then(|c| c.as_array())
+        .map(|chapters| {
+            chapters
+                .iter()
+                .filter_map(|c| c.get("start_time").and_then(|v| v.as_str()).and_then(|s| s.parse().ok()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(starts)
+}
+
+/// How far back keyframe_before looks; longer GOPs than this are rare outside broadcasts
+const KEYFRAME_SEARCH_SECONDS: f64 = 30.0;
+
+/// Time of the last video keyframe at or before `time`, which is where a stream copy
+/// seeking to `time` actually starts
+pub async fn keyframe_before(ffprobe_path: &PathBuf, input: &str, time: f64) -> Result<Option<f64>, String> {
+    let interval = format!("{:.3}%{:.3}", (time - KEYFRAME_SEARCH_SECONDS).max(0.0), time + 0.001);
+    let output = Command::new(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-select_streams", "v:0",
+            "-read_intervals", &interval,
+            "-show_entries", "packet=pts_time,flags",
+            "-of", "csv=p=0",
+            input,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err("ffprobe failed to analyze file".to_string());
+    }
+
+    // Lines look like "12.345000,K__"
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(','))
+        .filter(|(_, flags)| flags.starts_with('K'))
+        .filter_map(|(pts, _)| pts.parse::<f64>().ok())
+        .filter(|&pts| pts <= time + 0.001)
+        .reduce(f64::max))
+}
+
+/// Colour signalling and mastering metadata of an HDR video stream, in x265's formats
+#[derive(Debug, Clone)]
+pub struct HdrMetadata {
+    /// "smpte2084" (HDR10/PQ) or "arib-std-b67" (HLG)
+    pub transfer: String,
+    pub primaries: String,
+    pub matrix: String,
+    /// "G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)"
+    pub master_display: Option<String>,
+    /// "max_content,max_average"
+    pub max_cll: Option<String>,
+}
+
+/// ffprobe rationals ("34000/50000") as a number
+fn parse_rational(value: &serde_json::Value) -> Option<f64> {
+    let s = value.as_str()?;
+    match s.split_once('/') {
+        Some((num, den)) => {
+            let den: f64 = den.parse().ok()?;
+            (den != 0.0).then_some(num.parse::<f64>().ok()? / den)
+        }
+        None => s.parse().ok(),
+    }
+}
+
+/// x265 master-display string: chromaticities in 0.00002 units, luminance in 0.0001 cd/m2
+fn master_display(side_data: &serde_json::Value) -> Option<String> {
+    let chroma = |key: &str| parse_rational(&side_data[key]).map(|v| (v * 50000.0).round() as u32);
+    let luma = |key: &str| parse_rational(&side_data[key]).map(|v| (v * 10000.0).round() as u32);
+    Some(format!(
+        "G({},{})B({},{})R({},{})WP({},{})L({},{})",
+        chroma("green_x")?, chroma("green_y")?,
+        chroma("blue_x")?, chroma("blue_y")?,
+        chroma("red_x")?, chroma("red_y")?,
+        chroma("white_point_x")?, chroma("white_point_y")?,
+        luma("max_luminance")?, luma("min_luminance")?,
+    ))
+}
+
+/// HDR signalling of the first video stream; None for SDR sources. Mastering metadata is
+/// looked up on the stream (MP4/MKV boxes) and on the first frame (HEVC SEI).
+pub async fn get_hdr_metadata(ffprobe_path: &PathBuf, input: &str) -> Result<Option<HdrMetadata>, String> {
+    let output = Command::new(ffprobe_path)
+        .args([
+            "-v", "quiet",
+            "-print_format", "json",
+            "-select_streams", "v:0",
+            "-read_intervals", "%+#1",
+            "-show_streams",
+            "-show_frames",
+            input,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err("ffprobe failed to analyze file".to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+    let stream = &json["streams"][0];
+
+    let transfer = match stream["color_transfer"].as_str() {
+        Some(t @ ("smpte2084" | "arib-std-b67")) => t.to_string(),
+        _ => return Ok(None),
+    };
+
+    let side_data: Vec<&serde_json::Value> = [&stream["side_data_list"], &json["frames"][0]["side_data_list"]]
+        .iter()
+        .filter_map(|list| list.as_array())
+        .flatten()
+        .collect();
+    let find = |kind: &str| side_data.iter().copied().find(|d| d["side_data_type"].as_str() == Some(kind));
+
+    let max_cll = find("Content light level metadata").and_then(|d| {
+        Some(format!("{},{}", d["max_content"].as_u64()?, d["max_average"].as_u64()?))
+    });
+
+    Ok(Some(HdrMetadata {
+        transfer,
+        primaries: stream["color_primaries"].as_str().unwrap_or("bt2020").to_string(),
+        matrix: stream["color_space"].as_str().unwrap_or("bt2020nc").to_string(),
+        master_display: find("Mastering display metadata").and_then(master_display),
+        max_cll,
+    }))
+}
+
+/// Number CUDA devices by PCI bus, as nvidia-smi does, so a gpuIndex means the same card
+/// to -gpu and -hwaccel_device; CUDA's default order puts the fastest card first
+pub fn pci_device_order(cmd: &mut Command) {
+    cmd.env("CUDA_DEVICE_ORDER", "PCI_BUS_ID");
+}
+
+/// Slots for sample encodes run side by side, across all jobs: one per CPU, so probing
+/// many tiers doesn't start dozens of processes fighting over the cores
+static PARALLEL_SLOTS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Wait for a free parallel slot; it's held until the permit drops
+pub async fn parallel_slot() -> Option<OwnedSemaphorePermit> {
+    let slots = PARALLEL_SLOTS.get_or_init(|| {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+        Arc::new(Semaphore::new(cores))
+    });
+    slots.clone().acquire_owned().await.ok()
+}
+
+/// Run several ffmpeg invocations concurrently (no progress), as parallel slots free up,
+/// returning whether each succeeded
+pub async fn run_ffmpeg_parallel(ffmpeg_path: &PathBuf, arg_sets: Vec<Vec<String>>) -> Vec<bool> {
+    let runs: Vec<_> = arg_sets
+        .into_iter()
+        .map(|args| {
+            let mut cmd = Command::new(ffmpeg_path);
+            cmd.args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .kill_on_drop(true);
+            pci_device_order(&mut cmd);
+
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::process::CommandExt;
+                cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            }
+
+            tauri::async_runtime::spawn(async move {
+                let _slot = parallel_slot().await;
+                match cmd.spawn() {
+                    Ok(mut child) => child.wait().await.is_ok_and(|s| s.success()),
+                    Err(_) => false,
+                }
+            })
+        })
+        .collect();
+
+    // The runs are already going; waiting in order doesn't serialize them
+    let mut results = Vec::with_capacity(runs.len());
+    for run in runs {
+        results.push(run.await.unwrap_or(false));
+    }
+    results
+}
+
+/// Progress keys worth keeping in the job log, one summary line per progress update
+const LOGGED_PROGRESS_KEYS: &[&str] = &["frame=", "fps=", "bitrate=", "total_size=", "out_time=", "speed="];
+
+/// Run ffmpeg, reporting progress as a percentage of `duration`. With a `log_id`, stderr
+/// and a summary of each progress update are captured for get_job_log_tail.
 pub async fn run_ffmpeg_with_progress<F: FnMut(f64) + Send>(
     ffmpeg_path: &PathBuf,
     args: Vec<&str>,
     duration: f64,
+    log_id: Option<&str>,
     mut on_progress: F,
 ) -> Result<(), String> {
     // Add progress flag to get structured output
@@ -294,7 +686,8 @@ pub async fn run_ffmpeg_with_progress<F: FnMut(f64) + Send>(
     cmd.args(&full_args)
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+        .stderr(if log_id.is_some() { Stdio::piped() } else { Stdio::null() });
+    pci_device_order(&mut cmd);
 
     #[cfg(target_os = "windows")]
     {
@@ -304,12 +697,31 @@ pub async fn run_ffmpeg_with_progress<F: FnMut(f64) + Send>(
 
     let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn ffmpeg: {}", e))?;
 
+    // Drain stderr into the job log alongside the progress reader
+    if let (Some(stderr), Some(id)) = (child.stderr.take(), log_id) {
+        let id = id.to_string();
+        tauri::async_runtime::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                job_log::push(&id, line);
+            }
+        });
+    }
+
+    // Job runs also record CPU, memory and GPU usage
+    let done = Arc::new(AtomicBool::new(false));
+    let monitor = match (child.id(), log_id) {
+        (Some(pid), Some(id)) => Some(tauri::async_runtime::spawn(resources::monitor(id.to_string(), pid, done.clone()))),
+        _ => None,
+    };
+
     // Read progress from stdout (where -progress pipe:1 sends it)
     let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
     let mut reader = BufReader::new(stdout).lines();
 
     // FFmpeg progress output format: out_time_us=microseconds
     let time_regex = Regex::new(r"out_time_us=(\d+)").unwrap();
+    let mut progress_block = Vec::new();
 
     while let Ok(Some(line)) = reader.next_line().await {
         if let Some(caps) = time_regex.captures(&line) {
@@ -319,9 +731,23 @@ pub async fn run_ffmpeg_with_progress<F: FnMut(f64) + Send>(
                 on_progress(progress);
             }
         }
+        if let Some(id) = log_id {
+            // Each update ends with a progress=continue/end line
+            if line.starts_with("progress=") {
+                job_log::push(id, progress_block.join(" "));
+                progress_block.clear();
+            } else if LOGGED_PROGRESS_KEYS.iter().any(|key| line.starts_with(key)) {
+                progress_block.push(line);
+            }
+        }
     }
 
-    let status = child.wait().await.map_err(|e| format!("FFmpeg process error: {}", e))?;
+    let status = child.wait().await;
+    done.store(true, Ordering::Relaxed);
+    if let Some(monitor) = monitor {
+        let _ = monitor.await;
+    }
+    let status = status.map_err(|e| format!("FFmpeg process error: {}", e))?;
 
     if !status.success() {
         // Try to get error from stderr
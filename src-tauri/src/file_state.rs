@@ -0,0 +1,73 @@
+use crate::cache::{app_data_file, file_cache_key, write_atomic};
+use crate::converter::Marker;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Oldest entries are dropped beyond this many files
+const MAX_SAVED_FILES: usize = 500;
+
+// Serializes read-modify-write of the state file
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// In-progress edit of one file, restored when it is opened again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileState {
+    pub playhead: f64,
+    #[serde(rename = "trimStart")]
+    pub trim_start: Option<f64>,
+    #[serde(rename = "trimEnd")]
+    pub trim_end: Option<f64>,
+    pub markers: Vec<Marker>,
+    /// Unix seconds of the last save (set by save_file_state)
+    #[serde(rename = "savedAt")]
+    pub saved_at: u64,
+}
+
+fn state_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "file_state.json")
+}
+
+fn load_all(path: &PathBuf) -> HashMap<String, FileState> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Persist the edit state of a file. The key includes size and mtime, so a replaced
+/// or re-recorded file at the same path starts fresh.
+pub fn save_file_state(app: &tauri::AppHandle, path: &str, mut state: FileState) -> Result<(), String> {
+    let file = state_file(app)?;
+    let _guard = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    state.saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut all = load_all(&file);
+    all.insert(file_cache_key(path), state);
+
+    if all.len() > MAX_SAVED_FILES {
+        let mut by_age: Vec<(String, u64)> = all.iter().map(|(k, v)| (k.clone(), v.saved_at)).collect();
+        by_age.sort_by_key(|(_, saved_at)| *saved_at);
+        for (key, _) in by_age.into_iter().take(all.len() - MAX_SAVED_FILES) {
+            all.remove(&key);
+        }
+    }
+
+    let json = serde_json::to_string(&all).map_err(|e| format!("Failed to serialize file state: {}", e))?;
+    write_atomic(&file, json).map_err(|e| format!("Failed to save file state: {}", e))
+}
+
+/// Saved edit state for the file as it is now, if any
+pub fn load_file_state(app: &tauri::AppHandle, path: &str) -> Result<Option<FileState>, String> {
+    let file = state_file(app)?;
+    let _guard = STATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(load_all(&file).remove(&file_cache_key(path)))
+}
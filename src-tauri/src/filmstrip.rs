@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Frames whose hashes differ by at most this many bits are treated as identical
+const DUPLICATE_HASH_DISTANCE: u32 = 3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilmstripFrame {
+    pub timestamp: f64,
+    /// JPEG data URL; absent for duplicates and for frames that couldn't be extracted
+    pub image: Option<String>,
+    /// Index of the earlier frame this one is visually identical to
+    #[serde(rename = "duplicateOf")]
+    pub duplicate_of: Option<usize>,
+}
+
+/// A run of consecutive identical frames the UI can render compactly
+#[derive(Debug, Clone, Serialize)]
+pub struct StaticRegion {
+    #[serde(rename = "startIndex")]
+    pub start_index: usize,
+    #[serde(rename = "endIndex")]
+    pub end_index: usize,
+    #[serde(rename = "startTime")]
+    pub start_time: f64,
+    #[serde(rename = "endTime")]
+    pub end_time: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DedupedFilmstrip {
+    pub frames: Vec<FilmstripFrame>,
+    #[serde(rename = "staticRegions")]
+    pub static_regions: Vec<StaticRegion>,
+}
+
+/// Compute a 64-bit average hash of the frame at `timestamp` (8x8 grayscale thumbnail)
+pub async fn frame_hash(ffmpeg_path: &PathBuf, path: &str, timestamp: f64) -> Result<u64, String> {
+    let timestamp_str = format!("{:.3}", timestamp);
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args([
+        "-ss", &timestamp_str,
+        "-i", path,
+        "-vframes", "1",
+        "-vf", "scale=8:8:flags=area,format=gray",
+        "-f", "rawvideo",
+        "pipe:1",
+    ]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if !output.status.success() || output.stdout.len() < 64 {
+        return Err("Failed to hash frame".to_string());
+    }
+
+    let pixels = &output.stdout[..64];
+    let mean = pixels.iter().map(|&p| p as u32).sum::<u32>() / 64;
+
+    let mut hash = 0u64;
+    for (i, &p) in pixels.iter().enumerate() {
+        if p as u32 > mean {
+            hash |= 1 << i;
+        }
+    }
+    Ok(hash)
+}
+
+/// Decide which frames are duplicates of their predecessor.
+/// Returns, for each frame, the index of the earlier frame it repeats (if any).
+pub fn find_duplicates(hashes: &[Option<u64>]) -> Vec<Option<usize>> {
+    let mut duplicates = Vec::with_capacity(hashes.len());
+    let mut last_unique: Option<(usize, u64)> = None;
+
+    for (i, hash) in hashes.iter().enumerate() {
+        match (hash, last_unique) {
+            (Some(h), Some((idx, prev))) if (h ^ prev).count_ones() <= DUPLICATE_HASH_DISTANCE => {
+                duplicates.push(Some(idx));
+            }
+            (Some(h), _) => {
+                last_unique = Some((i, *h));
+                duplicates.push(None);
+            }
+            // Unhashable frames are always extracted and break any static run
+            (None, _) => {
+                last_unique = None;
+                duplicates.push(None);
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Group duplicate runs (original frame plus its repeats) into static regions
+pub fn static_regions(frames: &[FilmstripFrame]) -> Vec<StaticRegion> {
+    let mut regions: Vec<StaticRegion> = Vec::new();
+
+    for (i, frame) in frames.iter().enumerate() {
+        let Some(original) = frame.duplicate_of else {
+            continue;
+        };
+        match regions.last_mut() {
+            Some(region) if region.start_index == original => {
+                region.end_index = i;
+                region.end_time = frame.timestamp;
+            }
+            _ => regions.push(StaticRegion {
+                start_index: original,
+                end_index: i,
+                start_time: frames[original].timestamp,
+                end_time: frame.timestamp,
+            }),
+        }
+    }
+
+    regions
+}
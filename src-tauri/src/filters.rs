@@ -0,0 +1,296 @@
+use crate::converter::{Marker, SpeedPoint, TrackGain};
+
+/// How long each marker title card stays on screen (seconds)
+const TITLE_CARD_SECONDS: f64 = 3.0;
+
+/// Escape text for use as a drawtext option value inside a -vf filtergraph.
+/// Two levels are needed: one for the filter option parser, one for the graph parser.
+pub fn escape_drawtext(text: &str) -> String {
+    let mut option_level = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '\'' | ':') {
+            option_level.push('\\');
+        }
+        option_level.push(c);
+    }
+
+    let mut graph_level = String::with_capacity(option_level.len());
+    for c in option_level.chars() {
+        if matches!(c, '\\' | '\'' | '[' | ']' | ',' | ';') {
+            graph_level.push('\\');
+        }
+        graph_level.push(c);
+    }
+    graph_level
+}
+
+/// Build a drawtext chain that shows each named marker as a title card.
+/// Markers should be relative to the output video (already adjusted for trim_start).
+pub fn marker_titles_filter(markers: &[Marker]) -> Option<String> {
+    let cards: Vec<String> = markers
+        .iter()
+        .filter_map(|m| {
+            let name = m.name.as_deref()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            Some(format!(
+                "drawtext=text={}:expansion=none:fontsize=h/16:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=12:x=(w-text_w)/2:y=h/10:enable='between(t,{:.3},{:.3})'",
+                escape_drawtext(name),
+                m.time,
+                m.time + TITLE_CARD_SECONDS
+            ))
+        })
+        .collect();
+
+    if cards.is_empty() {
+        None
+    } else {
+        Some(cards.join(","))
+    }
+}
+
+/// Burn a subtitle file into the video; the path needs the same two-level escaping as
+/// drawtext text (Windows drive colons and backslashes included)
+pub fn subtitles_filter(path: &str) -> String {
+    format!("subtitles=filename={}", escape_drawtext(path))
+}
+
+/// Scaling algorithms the scaleAlgorithm option accepts
+pub const SCALE_ALGORITHMS: &[&str] = &["lanczos", "spline", "bicubic"];
+
+/// swscale flags for a downscale: lanczos unless another algorithm is chosen, with
+/// accurate rounding. Noticeably sharper than ffmpeg's bicubic default for 4K to 1080p.
+pub fn downscale_flags(algorithm: Option<&str>) -> String {
+    format!("flags={}+accurate_rnd", algorithm.unwrap_or("lanczos"))
+}
+
+/// scale_cuda interpolation for the chosen algorithm; it has no spline, lanczos is closest
+pub fn cuda_interp_algo(algorithm: Option<&str>) -> &'static str {
+    match algorithm {
+        Some("bicubic") => "bicubic",
+        _ => "lanczos",
+    }
+}
+
+/// What fills the bars when a picture is padded out to a canvas
+#[derive(Debug, Clone, PartialEq)]
+pub enum PadBackground {
+    /// An ffmpeg color: a name, #RRGGBB or 0xRRGGBB, optionally @alpha
+    Color(String),
+    /// A blurred copy of the video scaled to fill the canvas, as phone apps do
+    Blur,
+}
+
+impl PadBackground {
+    /// Parse the padBackground option; black when unset
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::trim) {
+            None | Some("") => Ok(PadBackground::Color("black".to_string())),
+            Some("blur") => Ok(PadBackground::Blur),
+            // Anything else ends up inside the filtergraph, so only color syntax passes
+            Some(color) if color.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '#' | '@' | '.')) => {
+                Ok(PadBackground::Color(color.to_string()))
+            }
+            Some(other) => Err(format!("Invalid pad background: {}", other)),
+        }
+    }
+}
+
+/// Fit the picture inside a width x height canvas without cropping, centered on the
+/// background. Shared by everything that changes the frame's aspect ratio.
+pub fn fit_with_padding(width: u32, height: u32, background: &PadBackground, scale_flags: &str) -> String {
+    let fit = format!(
+        "scale={}:{}:force_original_aspect_ratio=decrease:{}",
+        width, height, scale_flags
+    );
+    match background {
+        PadBackground::Color(color) => format!(
+            "{},pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:color={c}",
+            fit,
+            w = width,
+            h = height,
+            c = color
+        ),
+        // Blurring a small copy is much cheaper than blurring at full size and looks the same
+        PadBackground::Blur => format!(
+            "split[padfg][padbg];\
+             [padbg]scale={sw}:{sh}:force_original_aspect_ratio=increase,crop={sw}:{sh},boxblur=luma_radius='min(w,h)/8':luma_power=2,scale={w}:{h},setsar=1[padblur];\
+             [padfg]{fit}[padfit];\
+             [padblur][padfit]overlay=(W-w)/2:(H-h)/2",
+            sw = (width / 8).max(2) / 2 * 2,
+            sh = (height / 8).max(2) / 2 * 2,
+            w = width,
+            h = height,
+            fit = fit
+        ),
+    }
+}
+
+/// Append an optional filter chain to a base filter
+pub fn chain(base: &str, extra: Option<&str>) -> String {
+    match extra {
+        Some(extra) if !extra.is_empty() => format!("{},{}", base, extra),
+        _ => base.to_string(),
+    }
+}
+
+/// Shift audio against video: positive values delay the audio, negative values
+/// drop its start so it plays earlier
+pub fn audio_offset_filter(offset_ms: i64) -> Option<String> {
+    match offset_ms {
+        0 => None,
+        ms if ms > 0 => Some(format!("adelay={}:all=1", ms)),
+        ms => Some(format!("atrim=start={:.3},asetpts=PTS-STARTPTS", -ms as f64 / 1000.0)),
+    }
+}
+
+/// filter_complex graph mixing audio tracks of the first input, each at its own volume,
+/// into `[output_label]`. `post_filter` runs on the mix (e.g. a sync offset).
+pub fn audio_mix_graph(tracks: &[TrackGain], output_label: &str, post_filter: Option<&str>) -> String {
+    let mut graph: Vec<String> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("[0:a:{}]volume={:.3}[m{}]", t.track, t.volume, i))
+        .collect();
+    let inputs: String = (0..tracks.len()).map(|i| format!("[m{}]", i)).collect();
+    // normalize=0 keeps the volumes as given instead of dividing by the track count
+    let mix = format!("{}amix=inputs={}:duration=longest:normalize=0", inputs, tracks.len());
+    graph.push(format!("{}[{}]", chain(&mix, post_filter), output_label));
+    graph.join(";")
+}
+
+/// Playback speeds accepted in a speed curve
+const MIN_SPEED: f64 = 0.1;
+const MAX_SPEED: f64 = 10.0;
+
+/// A speed curve compiled into constant-speed segments over the (trimmed) source timeline
+pub struct SpeedRamp {
+    /// (source start, source end, speed)
+    segments: Vec<(f64, f64, f64)>,
+}
+
+impl SpeedRamp {
+    /// Build segments from keyframes; each point's speed holds until the next point.
+    /// Times are relative to the trimmed clip. Returns None when the curve is a no-op.
+    pub fn new(points: &[SpeedPoint], source_duration: f64) -> Result<Option<Self>, String> {
+        if points.is_empty() {
+            return Ok(None);
+        }
+
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for p in &points {
+            if !(MIN_SPEED..=MAX_SPEED).contains(&p.speed) {
+                return Err(format!("Speed must be between {} and {}: {}", MIN_SPEED, MAX_SPEED, p.speed));
+            }
+            if p.time < 0.0 {
+                return Err(format!("Invalid speed point time: {}", p.time));
+            }
+        }
+
+        // Normal speed until the first keyframe
+        let mut segments: Vec<(f64, f64, f64)> = Vec::new();
+        let mut cursor = 0.0;
+        let mut speed = 1.0;
+        for p in points.iter().filter(|p| p.time < source_duration) {
+            if p.time > cursor {
+                segments.push((cursor, p.time, speed));
+                cursor = p.time;
+            }
+            speed = p.speed;
+        }
+        segments.push((cursor, source_duration, speed));
+
+        // Merge neighbours with the same speed so the filtergraph stays small
+        let mut merged: Vec<(f64, f64, f64)> = Vec::with_capacity(segments.len());
+        for seg in segments {
+            match merged.last_mut() {
+                Some(last) if last.2 == seg.2 => last.1 = seg.1,
+                _ => merged.push(seg),
+            }
+        }
+
+        if merged.iter().all(|s| s.2 == 1.0) {
+            return Ok(None);
+        }
+        Ok(Some(SpeedRamp { segments: merged }))
+    }
+
+    /// Length of the output after retiming
+    pub fn output_duration(&self) -> f64 {
+        self.segments.iter().map(|(start, end, speed)| (end - start) / speed).sum()
+    }
+
+    /// Map a source time (relative to the trimmed clip) onto the output timeline
+    pub fn map_time(&self, t: f64) -> f64 {
+        let mut out = 0.0;
+        for &(start, end, speed) in &self.segments {
+            if t <= end {
+                return out + (t.max(start) - start) / speed;
+            }
+            out += (end - start) / speed;
+        }
+        out
+    }
+
+    /// setpts filter retiming video frames with a piecewise-linear expression
+    pub fn setpts_filter(&self) -> String {
+        let mut offset = 0.0;
+        let pieces: Vec<(f64, String)> = self
+            .segments
+            .iter()
+            .map(|&(start, end, speed)| {
+                let piece = format!("{:.6}+(T-{:.6})/{:.6}", offset, start, speed);
+                offset += (end - start) / speed;
+                (end, piece)
+            })
+            .collect();
+
+        // if(lt(T,end0),piece0,if(lt(T,end1),piece1,...,pieceN))
+        let (_, last) = pieces.last().cloned().unwrap_or_default();
+        let expr = pieces[..pieces.len().saturating_sub(1)]
+            .iter()
+            .rev()
+            .fold(last, |rest, (end, piece)| format!("if(lt(T,{:.6}),{},{})", end, piece, rest));
+
+        format!("setpts='({})/TB'", expr)
+    }
+
+    /// filter_complex graph cutting the `source` audio (e.g. "[0:a:0]") into segments,
+    /// retiming each with atempo and joining them again as `[output_label]`. `pre_filter`
+    /// runs on the source audio before it is cut (e.g. a sync offset).
+    pub fn audio_graph(&self, source: &str, output_label: &str, pre_filter: Option<&str>) -> String {
+        let n = self.segments.len();
+        let splits: String = (0..n).map(|i| format!("[s{}]", i)).collect();
+        let source = chain(&format!("{}anull", source), pre_filter);
+        let mut graph = vec![format!("{},asplit={}{}", source, n, splits)];
+
+        for (i, &(start, end, speed)) in self.segments.iter().enumerate() {
+            graph.push(format!(
+                "[s{}]atrim=start={:.6}:end={:.6},asetpts=PTS-STARTPTS,{}[r{}]",
+                i, start, end, atempo_chain(speed), i
+            ));
+        }
+
+        let joined: String = (0..n).map(|i| format!("[r{}]", i)).collect();
+        graph.push(format!("{}concat=n={}:v=0:a=1[{}]", joined, n, output_label));
+        graph.join(";")
+    }
+}
+
+/// atempo only accepts 0.5-2.0 per instance in older ffmpeg builds, so chain several
+fn atempo_chain(speed: f64) -> String {
+    let mut remaining = speed;
+    let mut stages = Vec::new();
+    while remaining > 2.0 {
+        stages.push("atempo=2.0".to_string());
+        remaining /= 2.0;
+    }
+    while remaining < 0.5 {
+        stages.push("atempo=0.5".to_string());
+        remaining /= 0.5;
+    }
+    stages.push(format!("atempo={:.6}", remaining));
+    stages.join(",")
+}
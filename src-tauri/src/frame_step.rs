@@ -0,0 +1,147 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// How far around the current position to look for neighbouring frames; wide enough
+/// for variable frame rate screen recordings that hold a frame for several seconds
+const SEARCH_WINDOW_SECONDS: f64 = 5.0;
+
+/// Timestamps closer than this count as the same frame
+const FRAME_EPSILON: f64 = 0.0005;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdjacentFrame {
+    /// JPEG data URL
+    pub image: String,
+    /// Exact presentation time of the frame, in seconds from the start of the file
+    pub timestamp: f64,
+}
+
+/// Container start time and the video frame timestamps (absolute, sorted) in a window
+async fn frame_timestamps(ffprobe_path: &PathBuf, path: &str, from: f64, to: f64) -> Result<(f64, Vec<f64>), String> {
+    let interval = format!("{:.3}%{:.3}", from.max(0.0), to);
+    let mut cmd = Command::new(ffprobe_path);
+    cmd.args([
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-read_intervals", &interval,
+        "-show_entries", "packet=pts_time:format=start_time",
+        "-of", "json",
+        path,
+    ]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to read frame timestamps".to_string());
+    }
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse ffprobe output: {}", e))?;
+    let start_time = json["format"]["start_time"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0.0);
+
+    // Packets come in decode order; B-frames make that differ from display order
+    let mut timestamps: Vec<f64> = json["packets"]
+        .as_array()
+        .map(|packets| {
+            packets
+                .iter()
+                .filter_map(|p| p["pts_time"].as_str().and_then(|s| s.parse().ok()))
+                .collect()
+        })
+        .unwrap_or_default();
+    timestamps.sort_by(|a, b| a.total_cmp(b));
+    Ok((start_time, timestamps))
+}
+
+/// Decode exactly the frame at `timestamp` (relative to the file start) as a JPEG
+async fn decode_frame(ffmpeg_path: &PathBuf, scratch_dir: &Path, path: &str, timestamp: f64) -> Result<Vec<u8>, String> {
+    // Accurate seek a little before the frame, then select it by its timestamp relative
+    // to the seek point, so rounding can't land on a neighbour
+    let seek = (timestamp - 1.0).max(0.0);
+    let select = format!("select='gte(t\\,{:.6})'", timestamp - seek - FRAME_EPSILON);
+
+    let unique_id = format!("{}_{}", std::process::id(), std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos());
+    let frame_path = scratch_dir.join(format!("step_{}.jpg", unique_id));
+    let frame_str = frame_path.to_string_lossy().to_string();
+    let seek_str = format!("{:.6}", seek);
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args([
+        "-ss", &seek_str,
+        "-i", path,
+        "-vf", &select,
+        "-vframes", "1",
+        "-q:v", "5",
+        "-y", &frame_str,
+    ]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to extract frame".to_string());
+    }
+
+    let data = fs::read(&frame_path).map_err(|e| format!("Failed to read frame: {}", e))?;
+    let _ = fs::remove_file(&frame_path);
+    Ok(data)
+}
+
+/// The frame right after ("next") or before ("previous") `timestamp`, decoded from the
+/// original file so stepping lands on real frames rather than guessed timestamps
+pub async fn adjacent_frame(
+    ffmpeg_path: &PathBuf,
+    ffprobe_path: &PathBuf,
+    scratch_dir: &Path,
+    path: &str,
+    timestamp: f64,
+    direction: &str,
+) -> Result<AdjacentFrame, String> {
+    let forward = match direction {
+        "next" => true,
+        "previous" => false,
+        _ => return Err(format!("Unknown direction: {}", direction)),
+    };
+
+    // The window is wide enough to absorb the container start time (usually well under
+    // a second); packet timestamps include it, the UI's don't
+    let (start_time, timestamps) = frame_timestamps(
+        ffprobe_path,
+        path,
+        timestamp - SEARCH_WINDOW_SECONDS,
+        timestamp + SEARCH_WINDOW_SECONDS,
+    )
+    .await?;
+    let absolute = timestamp + start_time;
+
+    let found = if forward {
+        timestamps.iter().copied().find(|&t| t > absolute + FRAME_EPSILON)
+    } else {
+        timestamps.iter().copied().rev().find(|&t| t < absolute - FRAME_EPSILON)
+    };
+    let frame_time = found.ok_or_else(|| format!("No {} frame", direction))? - start_time;
+
+    let data = decode_frame(ffmpeg_path, scratch_dir, path, frame_time).await?;
+    Ok(AdjacentFrame {
+        image: format!("data:image/jpeg;base64,{}", BASE64.encode(&data)),
+        timestamp: frame_time,
+    })
+}
@@ -0,0 +1,174 @@
+use crate::ffmpeg::get_media_metadata;
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// A frame interval this many times the nominal one counts as a gap (dropped frames)
+const GAP_FACTOR: f64 = 1.5;
+
+/// Gaps listed individually in the report; the counts still cover all of them
+const MAX_REPORTED_GAPS: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimestampGap {
+    /// Timestamp of the frame before the gap
+    pub time: f64,
+    /// Length of the gap in seconds
+    pub duration: f64,
+    /// Frames missing in the gap at the nominal frame rate
+    #[serde(rename = "missingFrames")]
+    pub missing_frames: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameTimingReport {
+    /// Video frames in the analysed range
+    pub frames: u32,
+    #[serde(rename = "nominalFps")]
+    pub nominal_fps: f64,
+    /// Frames missing according to timestamp gaps
+    pub dropped: u32,
+    /// Frames visually identical to the previous one (mpdecimate)
+    pub duplicated: u32,
+    pub gaps: Vec<TimestampGap>,
+    /// Frame intervals vary, so a CFR conversion will change timing
+    #[serde(rename = "variableFrameRate")]
+    pub variable_frame_rate: bool,
+}
+
+/// Presentation timestamps of every video packet in the range, in display order
+async fn video_timestamps(ffprobe_path: &PathBuf, path: &str, sample_seconds: Option<f64>) -> Result<Vec<f64>, String> {
+    let mut args: Vec<String> = vec![
+        "-v".to_string(), "error".to_string(),
+        "-select_streams".to_string(), "v:0".to_string(),
+        "-show_entries".to_string(), "packet=pts_time".to_string(),
+        "-of".to_string(), "csv=p=0".to_string(),
+    ];
+    if let Some(seconds) = sample_seconds {
+        args.extend(["-read_intervals".to_string(), format!("%+{:.3}", seconds)]);
+    }
+    args.push(path.to_string());
+
+    let mut cmd = Command::new(ffprobe_path);
+    cmd.args(&args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to read frame timestamps".to_string());
+    }
+
+    let mut timestamps: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|l| l.trim().trim_end_matches(',').parse().ok())
+        .collect();
+    // Packets come in decode order; B-frames make that differ from display order
+    timestamps.sort_by(|a, b| a.total_cmp(b));
+    Ok(timestamps)
+}
+
+/// Frames mpdecimate keeps after dropping near-identical repeats
+async fn frames_after_decimate(ffmpeg_path: &PathBuf, path: &str, sample_seconds: Option<f64>) -> Result<u32, String> {
+    let mut args: Vec<String> = vec!["-i".to_string(), path.to_string()];
+    if let Some(seconds) = sample_seconds {
+        args.extend(["-t".to_string(), format!("{:.3}", seconds)]);
+    }
+    args.extend([
+        "-an".to_string(),
+        "-vf".to_string(), "mpdecimate".to_string(),
+        "-vsync".to_string(), "passthrough".to_string(),
+        "-f".to_string(), "null".to_string(),
+        "-".to_string(),
+    ]);
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(&args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to analyse duplicate frames".to_string());
+    }
+
+    // The last stats line holds the final count: "frame=  123 fps=..."; stats use \r between updates
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let last = stderr
+        .rsplit(['\r', '\n'])
+        .find(|l| l.trim_start().starts_with("frame="))
+        .ok_or("No frame statistics in ffmpeg output")?;
+    last.trim_start()
+        .trim_start_matches("frame=")
+        .split_whitespace()
+        .next()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| "Failed to parse frame count".to_string())
+}
+
+/// Report dropped frames (timestamp gaps) and duplicated frames, over the whole file
+/// or the first `sample_seconds`
+pub async fn analyze_frame_timing(
+    ffmpeg_path: &PathBuf,
+    ffprobe_path: &PathBuf,
+    path: &str,
+    sample_seconds: Option<f64>,
+) -> Result<FrameTimingReport, String> {
+    let metadata = get_media_metadata(ffprobe_path, path).await?;
+    let timestamps = video_timestamps(ffprobe_path, path, sample_seconds).await?;
+    if timestamps.len() < 2 {
+        return Err("Not enough video frames to analyse".to_string());
+    }
+
+    let intervals: Vec<f64> = timestamps.windows(2).map(|w| w[1] - w[0]).collect();
+
+    // Prefer the container's frame rate; fall back to the median interval
+    let nominal_interval = match metadata.frame_rate_decimal {
+        Some(fps) if fps > 0.0 => 1.0 / fps,
+        _ => {
+            let mut sorted = intervals.clone();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            sorted[sorted.len() / 2]
+        }
+    };
+
+    let mut dropped = 0;
+    let mut gaps = Vec::new();
+    let mut variable_frame_rate = false;
+    for (i, &interval) in intervals.iter().enumerate() {
+        if (interval - nominal_interval).abs() > nominal_interval * 0.25 {
+            variable_frame_rate = true;
+        }
+        if interval > nominal_interval * GAP_FACTOR {
+            let missing = ((interval / nominal_interval).round() as u32).saturating_sub(1);
+            dropped += missing;
+            if gaps.len() < MAX_REPORTED_GAPS {
+                gaps.push(TimestampGap {
+                    time: timestamps[i],
+                    duration: interval,
+                    missing_frames: missing,
+                });
+            }
+        }
+    }
+
+    let frames = timestamps.len() as u32;
+    let kept = frames_after_decimate(ffmpeg_path, path, sample_seconds).await?;
+
+    Ok(FrameTimingReport {
+        frames,
+        nominal_fps: 1.0 / nominal_interval,
+        dropped,
+        duplicated: frames.saturating_sub(kept),
+        gaps,
+        variable_frame_rate,
+    })
+}
@@ -0,0 +1,123 @@
+use crate::capabilities::{binary_key, BinaryKey};
+use crate::job_log;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+fn command(path: &PathBuf) -> Command {
+    #[allow(unused_mut)]
+    let mut cmd = Command::new(path);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    cmd
+}
+
+/// Availability of the gifski binaries checked this session
+static AVAILABLE: OnceLock<Mutex<HashMap<BinaryKey, bool>>> = OnceLock::new();
+
+fn available_cache() -> &'static Mutex<HashMap<BinaryKey, bool>> {
+    AVAILABLE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Whether a gifski binary is bundled or on the PATH; checked once per binary path and
+/// modification time, so installing or updating gifski is noticed
+pub async fn available(gifski_path: &PathBuf) -> bool {
+    let key = binary_key(gifski_path);
+    if let Some(known) = available_cache().lock().ok().and_then(|c| c.get(&key).copied()) {
+        return known;
+    }
+    let found = command(gifski_path)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success());
+    if let Ok(mut cache) = available_cache().lock() {
+        cache.insert(key, found);
+    }
+    found
+}
+
+/// Pipe frames from ffmpeg into gifski as a yuv4mpeg stream. `decode_args` are ffmpeg's
+/// input and filter args (trim, scale, fps); progress comes from ffmpeg's side of the pipe.
+pub async fn encode<F: FnMut(f64) + Send>(
+    ffmpeg_path: &PathBuf,
+    gifski_path: &PathBuf,
+    decode_args: &[String],
+    fps: u32,
+    quality: u32,
+    output: &str,
+    duration: f64,
+    log_id: &str,
+    mut on_progress: F,
+) -> Result<(), String> {
+    let mut decoder = command(ffmpeg_path);
+    decoder
+        .args(["-progress", "pipe:2", "-nostats", "-loglevel", "error"])
+        .args(decode_args)
+        // Full chroma: GIF frames are RGB, so 4:2:0 would only blur colour edges
+        .args(["-an", "-pix_fmt", "yuv444p", "-f", "yuv4mpegpipe", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut decoder = decoder.spawn().map_err(|e| format!("Failed to spawn ffmpeg: {}", e))?;
+
+    let frames: Stdio = decoder
+        .stdout
+        .take()
+        .ok_or("Failed to capture ffmpeg output")?
+        .try_into()
+        .map_err(|e| format!("Failed to connect ffmpeg to gifski: {}", e))?;
+
+    let fps_str = fps.to_string();
+    let quality_str = quality.clamp(1, 100).to_string();
+    let mut encoder = command(gifski_path);
+    encoder
+        .args(["--fps", &fps_str, "--quality", &quality_str, "-o", output, "-"])
+        .stdin(frames)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let mut encoder = match encoder.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = decoder.kill().await;
+            return Err(format!("Failed to spawn gifski: {}", e));
+        }
+    };
+
+    // With -progress on stderr, progress keys and errors share one stream
+    let stderr = decoder.stderr.take().ok_or("Failed to capture ffmpeg progress")?;
+    let mut reader = BufReader::new(stderr).lines();
+    let time_regex = Regex::new(r"out_time_us=(\d+)").unwrap();
+    while let Ok(Some(line)) = reader.next_line().await {
+        if let Some(caps) = time_regex.captures(&line) {
+            if let Ok(microseconds) = caps[1].parse::<f64>() {
+                on_progress((microseconds / 1_000_000.0 / duration * 100.0).min(100.0));
+            }
+        } else if !line.contains('=') {
+            job_log::push(log_id, line);
+        }
+    }
+
+    let decoded = decoder.wait().await.map_err(|e| format!("FFmpeg process error: {}", e))?;
+    let encoded = encoder.wait().await.map_err(|e| format!("gifski process error: {}", e))?;
+    if !decoded.success() {
+        return Err("FFmpeg decoding failed".to_string());
+    }
+    if !encoded.success() {
+        return Err("gifski encoding failed".to_string());
+    }
+
+    on_progress(100.0);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crate::cache::{app_data_file, write_atomic};
+use crate::converter::EncodeSettings;
+use crate::job_journal::now_secs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Oldest entries are dropped beyond this many exports
+const MAX_ENTRIES: usize = 1000;
+
+// Serializes read-modify-write of the history file between concurrent jobs
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// One finished export and the settings that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRecord {
+    #[serde(rename = "inputPath")]
+    pub input_path: String,
+    #[serde(rename = "outputPath")]
+    pub output_path: String,
+    #[serde(rename = "outputSize")]
+    pub output_size: Option<u64>,
+    /// Unix seconds
+    #[serde(rename = "finishedAt")]
+    pub finished_at: u64,
+    pub settings: EncodeSettings,
+}
+
+fn history_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "export_history.json")
+}
+
+/// Every recorded export, oldest first
+pub fn load(app: &tauri::AppHandle) -> Vec<ExportRecord> {
+    history_file(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Add a finished export. History is best effort: a failure here never fails the export.
+pub fn record(app: &tauri::AppHandle, input_path: &str, output_path: &Path, output_size: Option<u64>, settings: &EncodeSettings) {
+    let _guard = HISTORY_LOCK.lock();
+    let output_path = output_path.to_string_lossy().to_string();
+    let mut records = load(app);
+    // A re-export to the same path replaces the old entry
+    records.retain(|r| r.output_path != output_path);
+    records.push(ExportRecord {
+        input_path: input_path.to_string(),
+        output_path,
+        output_size,
+        finished_at: now_secs(),
+        settings: settings.clone(),
+    });
+    let excess = records.len().saturating_sub(MAX_ENTRIES);
+    records.drain(..excess);
+
+    let (Ok(path), Ok(json)) = (history_file(app), serde_json::to_string(&records)) else {
+        return;
+    };
+    let _ = write_atomic(&path, json);
+}
+
+/// Settings of the latest export written to `output_path`
+pub fn settings_for(app: &tauri::AppHandle, output_path: &str) -> Option<EncodeSettings> {
+    load(app)
+        .into_iter()
+        .rev()
+        .find(|r| r.output_path == output_path)
+        .map(|r| r.settings)
+}
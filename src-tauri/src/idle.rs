@@ -0,0 +1,130 @@
+use serde::Serialize;
+use std::time::Duration;
+use sysinfo::System;
+use tokio::process::Command;
+
+/// Gap between the two CPU usage samples
+const CPU_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleStatus {
+    /// Seconds since the last keyboard/mouse input, if the platform exposes it
+    #[serde(rename = "idleSeconds")]
+    pub idle_seconds: Option<f64>,
+    /// System-wide CPU usage (0-100) over a short sample
+    #[serde(rename = "cpuPercent")]
+    pub cpu_percent: Option<f64>,
+    /// Whether both thresholds are met (unknown values count as not idle)
+    pub idle: bool,
+    /// Why idle time or CPU usage couldn't be read (e.g. Wayland, or xprintidle missing);
+    /// `idle` then stays false however quiet the machine is
+    pub reason: Option<String>,
+}
+
+#[cfg(target_os = "windows")]
+mod win {
+    #[repr(C)]
+    pub struct LastInputInfo {
+        pub cb_size: u32,
+        pub dw_time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        pub fn GetLastInputInfo(plii: *mut LastInputInfo) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetTickCount() -> u32;
+    }
+}
+
+/// System CPU usage from two samples; sysinfo needs the gap to compute a rate
+async fn cpu_percent() -> Option<f64> {
+    let mut system = System::new();
+    system.refresh_cpu_usage();
+    tokio::time::sleep(CPU_SAMPLE_INTERVAL.max(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL)).await;
+    system.refresh_cpu_usage();
+    if system.cpus().is_empty() {
+        return None;
+    }
+    Some(system.global_cpu_usage() as f64)
+}
+
+/// Time since the last user input, or why it can't be read
+async fn input_idle_seconds() -> Result<f64, String> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut info = win::LastInputInfo {
+            cb_size: std::mem::size_of::<win::LastInputInfo>() as u32,
+            dw_time: 0,
+        };
+        // SAFETY: info is a correctly sized LASTINPUTINFO with cbSize set
+        let ok = unsafe { win::GetLastInputInfo(&mut info) };
+        if ok == 0 {
+            return Err("Windows didn't report the last input time".to_string());
+        }
+        // SAFETY: GetTickCount has no preconditions
+        let now = unsafe { win::GetTickCount() };
+        return Ok(now.wrapping_sub(info.dw_time) as f64 / 1000.0);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // "HIDIdleTime" = 123456789 (nanoseconds)
+        let unavailable = || "ioreg didn't report the input idle time".to_string();
+        let output = Command::new("ioreg")
+            .args(["-c", "IOHIDSystem", "-d", "4"])
+            .output()
+            .await
+            .map_err(|e| format!("Failed to run ioreg: {}", e))?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let line = text.lines().find(|l| l.contains("HIDIdleTime")).ok_or_else(unavailable)?;
+        let nanos: f64 = line.rsplit('=').next().and_then(|v| v.trim().parse().ok()).ok_or_else(unavailable)?;
+        return Ok(nanos / 1_000_000_000.0);
+    }
+
+    // X11 only; Wayland compositors don't expose idle time to ordinary clients
+    #[allow(unreachable_code)]
+    {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let output = match Command::new("xprintidle").output().await {
+            Ok(output) => output,
+            Err(_) if wayland => return Err("Input idle time isn't available on Wayland".to_string()),
+            Err(_) => return Err("Input idle time needs xprintidle, which isn't installed".to_string()),
+        };
+        if !output.status.success() {
+            return Err(if wayland {
+                "Input idle time isn't available on Wayland".to_string()
+            } else {
+                "xprintidle couldn't read the input idle time".to_string()
+            });
+        }
+        let millis: f64 = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|_| "xprintidle returned an unreadable idle time".to_string())?;
+        Ok(millis / 1000.0)
+    }
+}
+
+/// Report whether the machine is idle enough to run background jobs
+pub async fn idle_status(min_idle_seconds: f64, max_cpu_percent: f64) -> IdleStatus {
+    let (idle_seconds, idle_error) = match input_idle_seconds().await {
+        Ok(seconds) => (Some(seconds), None),
+        Err(e) => (None, Some(e)),
+    };
+    let cpu_percent = cpu_percent().await;
+
+    let idle = idle_seconds.is_some_and(|s| s >= min_idle_seconds)
+        && cpu_percent.is_some_and(|c| c <= max_cpu_percent);
+    let reason = idle_error.or_else(|| cpu_percent.is_none().then(|| "CPU usage isn't available".to_string()));
+
+    IdleStatus {
+        idle_seconds,
+        cpu_percent,
+        idle,
+        reason,
+    }
+}
@@ -0,0 +1,170 @@
+//! End-to-end encoding matrix: a synthetic lavfi clip goes through convert_file_impl for
+//! every conversion type, and each output is checked for size, duration, chapters and
+//! stream layout. Types whose encoders the ffmpeg build lacks are skipped.
+//!
+//! Uses the dev ffmpeg in src-tauri/ffmpeg, else the one on PATH:
+//!     cargo test --features integration-tests
+//! The app's event loop needs a display on Linux, so run it under xvfb-run on CI.
+
+use crate::capabilities;
+use crate::converter::{audio_format, convert_file_impl, output_extension, required_encoders, targets_size, ConversionResult, Marker, CONVERSION_TYPES};
+use crate::ffmpeg::{get_audio_streams, get_ffmpeg_path, get_ffprobe_path, get_media_metadata};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+const SOURCE_SECONDS: f64 = 4.0;
+const TRIM_START: f64 = 0.5;
+const TRIM_DURATION: f64 = 3.0;
+const TARGET_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Encodes may land a little over the target before strict mode would retry
+const SIZE_TOLERANCE: f64 = 1.15;
+/// Animated formats round to whole frames at low frame rates
+const DURATION_TOLERANCE: f64 = 0.5;
+/// split_av cuts both files on a keyframe (the source has one at the trim start), so
+/// they land within a few frames; an untrimmed audio file would be 0.5 s longer
+const SPLIT_DURATION_TOLERANCE: f64 = 0.1;
+
+/// Identifier for the test app, so its data, cache and undo staging dirs are separate
+/// from the user's real profile
+const TEST_IDENTIFIER: &str = "com.torchio.app.integration-tests";
+
+/// One app for the whole run; a second event loop in the same process would panic
+fn app() -> tauri::AppHandle {
+    static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+    APP.get_or_init(|| {
+        let mut context = tauri::generate_context!();
+        context.config_mut().app.windows.clear();
+        context.config_mut().identifier = TEST_IDENTIFIER.to_string();
+        let app = tauri::Builder::default()
+            .any_thread()
+            .build(context)
+            .expect("failed to build the test app");
+        let handle = app.handle().clone();
+        Box::leak(Box::new(app));
+        handle
+    })
+    .clone()
+}
+
+/// 640x360 test pattern with a sine tone, keyframes every half second so stream-copy
+/// trims land where asked
+fn synthetic_source(ffmpeg: &Path, dir: &Path) -> PathBuf {
+    let source = dir.join("source.mp4");
+    let duration = SOURCE_SECONDS.to_string();
+    let status = Command::new(ffmpeg)
+        .args(["-y", "-hide_banner", "-loglevel", "error"])
+        .args(["-f", "lavfi", "-i", &format!("testsrc2=size=640x360:rate=30:duration={}", duration)])
+        .args(["-f", "lavfi", "-i", &format!("sine=frequency=440:sample_rate=48000:duration={}", duration)])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-g", "15", "-c:a", "aac", "-shortest"])
+        .arg(&source)
+        .status()
+        .expect("failed to run ffmpeg");
+    assert!(status.success(), "ffmpeg couldn't generate the synthetic source");
+    source
+}
+
+/// (has video, has audio) expected in the main output
+fn expected_layout(conversion_type: &str) -> (bool, bool) {
+    match conversion_type {
+        _ if audio_format(conversion_type).is_some() => (false, true),
+        "webp" | "gif" | "apng" | "split_av" => (true, false),
+        _ => (true, true),
+    }
+}
+
+fn markers() -> Vec<Marker> {
+    vec![
+        Marker { id: 1, time: 1.0, name: Some("One".to_string()) },
+        Marker { id: 2, time: 2.5, name: Some("Two".to_string()) },
+    ]
+}
+
+async fn check_output(ffprobe: &PathBuf, path: &str, (video, audio): (bool, bool), tolerance: f64) -> Result<(), String> {
+    let metadata = get_media_metadata(ffprobe, path).await?;
+    if (metadata.duration - TRIM_DURATION).abs() > tolerance {
+        return Err(format!("{} lasts {:.2}s, expected {:.2}s", path, metadata.duration, TRIM_DURATION));
+    }
+    if metadata.video_codec.is_some() != video {
+        return Err(format!("{}: video stream present = {}, expected {}", path, metadata.video_codec.is_some(), video));
+    }
+    let audio_tracks = get_audio_streams(ffprobe, path).await.unwrap_or_default().len();
+    if audio_tracks != usize::from(audio) {
+        return Err(format!("{} has {} audio tracks, expected {}", path, audio_tracks, usize::from(audio)));
+    }
+    Ok(())
+}
+
+async fn check_conversion(app: &tauri::AppHandle, source: &Path, conversion_type: &str) -> Result<(), String> {
+    let ffprobe = get_ffprobe_path(app);
+    let result: ConversionResult = convert_file_impl(
+        app.clone(),
+        format!("matrix_{}", conversion_type),
+        source.to_string_lossy().to_string(),
+        format!("matrix_{}.{}", conversion_type, output_extension(conversion_type)),
+        TARGET_BYTES,
+        conversion_type.to_string(),
+        Some(TRIM_START),
+        Some(TRIM_DURATION),
+        Some(markers()),
+        None,
+    )
+    .await?;
+
+    if !result.success {
+        return Err(result.error.unwrap_or_else(|| "failed without an error".to_string()));
+    }
+    let output = result.output_path.ok_or("no output path")?;
+    let size = result.output_size.unwrap_or(0);
+    if size == 0 {
+        return Err("empty output".to_string());
+    }
+    if targets_size(conversion_type) && size as f64 > TARGET_BYTES as f64 * SIZE_TOLERANCE {
+        return Err(format!("{} bytes is over the {} byte target", size, TARGET_BYTES));
+    }
+
+    let tolerance = if conversion_type == "split_av" { SPLIT_DURATION_TOLERANCE } else { DURATION_TOLERANCE };
+    check_output(&ffprobe, &output, expected_layout(conversion_type), tolerance).await?;
+    if conversion_type == "split_av" {
+        let audio = result.output_paths.get(1).ok_or("no audio output listed")?;
+        check_output(&ffprobe, audio, (false, true), tolerance).await?;
+    }
+
+    // MKV outputs carry the markers as chapters
+    match result.chapters {
+        Some(chapters) if !chapters.ok => {
+            Err(format!("{} of {} chapters written", chapters.written, chapters.requested))
+        }
+        None if output_extension(conversion_type) == "mkv" => Err("no chapters written".to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn encoding_matrix() {
+    let app = app();
+    let ffmpeg = get_ffmpeg_path(&app);
+    let dir = tempfile::tempdir().expect("failed to create a temp dir");
+    let source = synthetic_source(&ffmpeg, dir.path());
+
+    let failures = tauri::async_runtime::block_on(async {
+        let encoders = capabilities::encoders(&ffmpeg).await;
+        let mut failures = Vec::new();
+        for conversion_type in CONVERSION_TYPES {
+            let supported = required_encoders(conversion_type)
+                .iter()
+                .all(|group| group.iter().any(|name| encoders.iter().any(|e| e == name)));
+            if !supported {
+                eprintln!("skipping {}: encoder not in this ffmpeg build", conversion_type);
+                continue;
+            }
+            if let Err(e) = check_conversion(&app, &source, conversion_type).await {
+                failures.push(format!("{}: {}", conversion_type, e));
+            }
+        }
+        failures
+    });
+
+    assert!(failures.is_empty(), "encoding matrix failures:\n{}", failures.join("\n"));
+}
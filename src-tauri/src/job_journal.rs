@@ -0,0 +1,264 @@
+use crate::cache::{app_data_file, write_atomic};
+use crate::converter::{ConversionOptions, Marker};
+use crate::output::remove_partial_output;
+use crate::undo;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Serializes journal writes so concurrent jobs never interleave a line
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// Jobs started but not finished in this session; the journal is emptied once none are left
+static ACTIVE: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn active() -> &'static Mutex<HashSet<String>> {
+    ACTIVE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// A conversion as submitted, with enough to run it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournaledJob {
+    pub id: String,
+    #[serde(rename = "inputPath")]
+    pub input_path: String,
+    #[serde(rename = "outputName")]
+    pub output_name: String,
+    #[serde(rename = "outputPath")]
+    pub output_path: String,
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    #[serde(rename = "trimStart")]
+    pub trim_start: Option<f64>,
+    #[serde(rename = "trimDuration")]
+    pub trim_duration: Option<f64>,
+    pub markers: Option<Vec<Marker>>,
+    pub options: ConversionOptions,
+    /// Unix seconds; 0 for jobs still waiting in the queue
+    #[serde(rename = "startedAt", default)]
+    pub started_at: u64,
+}
+
+/// One state transition, appended (and synced) before the work it describes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event")]
+enum Entry {
+    #[serde(rename = "started")]
+    Started(Box<JournaledJob>),
+    /// A temporary or extra output file the job is about to write
+    #[serde(rename = "created")]
+    Created { id: String, path: String },
+    /// An existing file at `path` was moved to the undo staging folder so the job could
+    /// write there
+    #[serde(rename = "staged")]
+    Staged { id: String, path: String },
+    #[serde(rename = "finished")]
+    Finished { id: String },
+}
+
+/// A job the app was killed in the middle of, found at startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterruptedJob {
+    pub job: JournaledJob,
+    /// Partial outputs and temp files that were deleted
+    #[serde(rename = "removedFiles")]
+    pub removed_files: Vec<String>,
+    /// Files the job was replacing, put back from the undo staging folder
+    #[serde(rename = "restoredFiles", default)]
+    pub restored_files: Vec<String>,
+}
+
+fn journal_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "job_journal.jsonl")
+}
+
+fn interrupted_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "interrupted_jobs.json")
+}
+
+fn queue_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "pending_queue.json")
+}
+
+/// Append one entry; the caller holds JOURNAL_LOCK
+fn append(app: &tauri::AppHandle, entry: &Entry) {
+    let Ok(path) = journal_file(app) else {
+        return;
+    };
+    let Ok(mut line) = serde_json::to_string(entry) else {
+        return;
+    };
+    line.push('\n');
+
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        // sync so the entry survives a power cut, not just an app crash
+        let _ = file.write_all(line.as_bytes()).and_then(|_| file.sync_data());
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Record a job before it writes anything
+pub fn started(app: &tauri::AppHandle, job: JournaledJob) {
+    let _guard = JOURNAL_LOCK.lock();
+    if let Ok(mut active) = active().lock() {
+        active.insert(job.id.clone());
+    }
+    append(app, &Entry::Started(Box::new(job)));
+}
+
+/// Record a file the job is about to create besides its output
+pub fn created(app: &tauri::AppHandle, id: &str, path: &Path) {
+    let _guard = JOURNAL_LOCK.lock();
+    append(app, &Entry::Created {
+        id: id.to_string(),
+        path: path.to_string_lossy().to_string(),
+    });
+}
+
+/// Record that a file the job is about to overwrite was staged for undo
+pub fn staged(app: &tauri::AppHandle, id: &str, path: &Path) {
+    let _guard = JOURNAL_LOCK.lock();
+    append(app, &Entry::Staged {
+        id: id.to_string(),
+        path: path.to_string_lossy().to_string(),
+    });
+}
+
+/// Record that a job ended, successfully or not; its files are no longer at risk
+pub fn finished(app: &tauri::AppHandle, id: &str) {
+    let _guard = JOURNAL_LOCK.lock();
+    append(app, &Entry::Finished { id: id.to_string() });
+
+    let idle = active()
+        .lock()
+        .map(|mut active| {
+            active.remove(id);
+            active.is_empty()
+        })
+        .unwrap_or(false);
+    if idle {
+        // Nothing in flight, so nothing to recover: keep the journal from growing
+        if let Ok(path) = journal_file(app) {
+            let _ = fs::write(path, "");
+        }
+    }
+}
+
+/// Replay the journal at startup: delete what unfinished jobs left behind and remember
+/// them so the UI can offer to run them again. Returns the newly found jobs.
+pub fn recover(app: &tauri::AppHandle) -> Vec<InterruptedJob> {
+    let Ok(path) = journal_file(app) else {
+        return Vec::new();
+    };
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+
+    let mut jobs: Vec<JournaledJob> = Vec::new();
+    let mut created: HashMap<String, Vec<String>> = HashMap::new();
+    let mut staged: HashMap<String, Vec<String>> = HashMap::new();
+    let mut finished: HashSet<String> = HashSet::new();
+    // A torn last line from a crash mid-write just fails to parse
+    for entry in contents.lines().filter_map(|line| serde_json::from_str::<Entry>(line).ok()) {
+        match entry {
+            Entry::Started(job) => {
+                finished.remove(&job.id);
+                created.remove(&job.id);
+                staged.remove(&job.id);
+                jobs.retain(|j| j.id != job.id);
+                jobs.push(*job);
+            }
+            Entry::Created { id, path } => created.entry(id).or_default().push(path),
+            Entry::Staged { id, path } => staged.entry(id).or_default().push(path),
+            Entry::Finished { id } => {
+                finished.insert(id);
+            }
+        }
+    }
+
+    let found: Vec<InterruptedJob> = jobs
+        .into_iter()
+        .filter(|job| !finished.contains(&job.id))
+        .map(|job| {
+            // Only files written after the job started are its own
+            let since = UNIX_EPOCH + Duration::from_secs(job.started_at);
+            let removed_files = std::iter::once(job.output_path.clone())
+                .chain(created.remove(&job.id).unwrap_or_default())
+                .filter(|file| remove_partial_output(Path::new(file), since))
+                .collect();
+            // With the partial output gone, the file it was replacing can go back
+            let restored_files = staged
+                .remove(&job.id)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|file| undo::unstage_overwrite(app, Path::new(file)).is_ok())
+                .collect();
+            InterruptedJob { job, removed_files, restored_files }
+        })
+        .collect();
+
+    if !found.is_empty() {
+        let mut all = interrupted_jobs(app);
+        all.extend(found.iter().cloned());
+        if let (Ok(file), Ok(json)) = (interrupted_file(app), serde_json::to_string(&all)) {
+            let _ = fs::write(file, json);
+        }
+    }
+    let _ = fs::write(&path, "");
+    found
+}
+
+/// Jobs cut short by a crash or power loss that haven't been resumed or dismissed
+pub fn interrupted_jobs(app: &tauri::AppHandle) -> Vec<InterruptedJob> {
+    interrupted_file(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Remove one interrupted job from the list and return it, to run it again
+pub fn take_interrupted(app: &tauri::AppHandle, id: &str) -> Result<JournaledJob, String> {
+    let job = interrupted_jobs(app)
+        .into_iter()
+        .find(|j| j.job.id == id)
+        .map(|j| j.job)
+        .ok_or_else(|| format!("No interrupted job {}", id))?;
+    dismiss_interrupted(app, Some(vec![id.to_string()]))?;
+    Ok(job)
+}
+
+/// Save the jobs waiting in the queue, so a crash or power cut doesn't lose them
+pub fn save_queue(app: &tauri::AppHandle, jobs: &[JournaledJob]) -> Result<(), String> {
+    let json = serde_json::to_string(jobs).map_err(|e| format!("Failed to serialize queue: {}", e))?;
+    write_atomic(&queue_file(app)?, json).map_err(|e| format!("Failed to save queue: {}", e))
+}
+
+/// The queue as last saved
+pub fn saved_queue(app: &tauri::AppHandle) -> Vec<JournaledJob> {
+    queue_file(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Forget interrupted jobs, all of them or just the given ids (e.g. once resubmitted)
+pub fn dismiss_interrupted(app: &tauri::AppHandle, ids: Option<Vec<String>>) -> Result<(), String> {
+    let remaining: Vec<InterruptedJob> = match ids {
+        Some(ids) => interrupted_jobs(app).into_iter().filter(|j| !ids.contains(&j.job.id)).collect(),
+        None => Vec::new(),
+    };
+    let json = serde_json::to_string(&remaining).map_err(|e| format!("Failed to serialize interrupted jobs: {}", e))?;
+    fs::write(interrupted_file(app)?, json).map_err(|e| format!("Failed to save interrupted jobs: {}", e))
+}
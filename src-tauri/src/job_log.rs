@@ -0,0 +1,62 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// Lines kept per job; enough to see what the encoder did recently without growing unbounded
+const MAX_LINES: usize = 500;
+
+/// Jobs whose output is kept; a finished job's log stays readable (e.g. after a failure)
+/// until this many newer jobs have logged
+const MAX_JOBS: usize = 32;
+
+/// Recent ffmpeg output of each job, by job id
+#[derive(Default)]
+struct Logs {
+    lines: HashMap<String, VecDeque<String>>,
+    /// Job ids, oldest first, for eviction
+    order: VecDeque<String>,
+}
+
+static LOGS: OnceLock<Mutex<Logs>> = OnceLock::new();
+
+fn logs() -> &'static Mutex<Logs> {
+    LOGS.get_or_init(|| Mutex::new(Logs::default()))
+}
+
+/// Forget a job's output, e.g. when its id is reused for a new run
+pub fn clear(id: &str) {
+    if let Ok(mut logs) = logs().lock() {
+        logs.lines.remove(id);
+        logs.order.retain(|job| job != id);
+    }
+}
+
+pub fn push(id: &str, line: String) {
+    if let Ok(mut logs) = logs().lock() {
+        if !logs.lines.contains_key(id) {
+            logs.order.push_back(id.to_string());
+            while logs.order.len() > MAX_JOBS {
+                if let Some(oldest) = logs.order.pop_front() {
+                    logs.lines.remove(&oldest);
+                }
+            }
+        }
+        let lines = logs.lines.entry(id.to_string()).or_default();
+        if lines.len() == MAX_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// The last `count` lines captured for a job, oldest first
+pub fn tail(id: &str, count: usize) -> Vec<String> {
+    logs()
+        .lock()
+        .ok()
+        .and_then(|logs| {
+            logs.lines
+                .get(id)
+                .map(|lines| lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect())
+        })
+        .unwrap_or_default()
+}
@@ -1,11 +1,58 @@
 #![allow(unused_imports)]
 
+mod benchmark;
+mod build_info;
+mod cache;
+mod capabilities;
+mod captions;
+mod clipboard;
+mod contact_sheet;
 mod converter;
+mod destinations;
 mod ffmpeg;
+mod file_state;
+mod filmstrip;
+mod frame_step;
+mod frame_timing;
+mod filters;
+mod gifski;
+mod history;
+mod idle;
+#[cfg(all(test, feature = "integration-tests"))]
+mod integration_tests;
+mod job_journal;
+mod job_log;
+mod multitrack;
+mod output;
+mod pipeline;
+mod poster;
+mod power;
+mod presets;
+mod project;
+mod proxy;
+mod quick_share;
+mod renditions;
+mod resources;
+mod rules;
+mod safe_areas;
+mod scene_calibration;
+mod scratch;
+mod stems;
+mod sync;
+mod thumbnails;
+mod tier_hints;
+mod undo;
+mod vmaf;
 
-use converter::{convert_file_impl, ConversionResult, Marker};
+use converter::{convert_file_impl, read_encode_settings, ConversionOptions, ConversionResult, EncodeSettings, Marker, OutputEstimate, TargetCalculation, TargetParams};
+use destinations::{DestinationRecommendation, PlatformPreset, SourceProperties};
 use ffmpeg::{get_ffmpeg_path, get_ffprobe_path, get_video_info, get_media_metadata, MediaMetadata};
+use filmstrip::{find_duplicates, frame_hash, static_regions, DedupedFilmstrip, FilmstripFrame};
+use pipeline::{PipelineStep, StepResult};
+use presets::{ImportResult, SharedPreset};
+use rules::{infer_preset, PresetInference, PresetRule};
 use std::fs;
+use std::path::{Path, PathBuf};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 #[tauri::command]
@@ -46,31 +93,61 @@ async fn get_media_metadata_cmd(app: tauri::AppHandle, path: String) -> Result<M
     get_media_metadata(&ffprobe, &path).await
 }
 
-#[tauri::command]
-async fn extract_frame(app: tauri::AppHandle, path: String, timestamp: f64) -> Result<String, String> {
-    let ffmpeg = get_ffmpeg_path(&app);
+/// Output settings for exported stills; previews use the defaults
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+struct FrameExportOptions {
+    /// "jpeg" (default), "webp" or "png"
+    format: Option<String>,
+    /// 1-100, higher is better (ignored for PNG)
+    quality: Option<u32>,
+    /// JPEG chroma subsampling: "420" (default) or "444"
+    chroma: Option<String>,
+    /// PNG only: keep the source's full bit depth as 16-bit RGB (for HDR / 10-bit sources)
+    #[serde(rename = "highBitDepth")]
+    high_bit_depth: bool,
+}
 
-    // Create temp file for the frame with unique name (timestamp + random)
-    let temp_dir = std::env::temp_dir();
-    let unique_id = format!("{}_{}", std::process::id(), std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos());
-    let frame_path = temp_dir.join(format!("frame_{}.jpg", unique_id));
-    let frame_str = frame_path.to_string_lossy().to_string();
+impl FrameExportOptions {
+    /// File extension, data URL mime type and ffmpeg output args for this format
+    fn output_args(&self) -> Result<(&'static str, &'static str, Vec<String>), String> {
+        let quality = self.quality.map(|q| q.clamp(1, 100));
+        match self.format.as_deref().unwrap_or("jpeg") {
+            "jpeg" | "jpg" => {
+                // -q:v runs from 2 (best) to 31 (worst); previews use 5
+                let qscale = quality.map_or(5, |q| 2 + (100 - q) * 29 / 100);
+                let mut args = vec!["-q:v".to_string(), qscale.to_string()];
+                match self.chroma.as_deref() {
+                    Some("444") => args.extend(["-pix_fmt".to_string(), "yuvj444p".to_string()]),
+                    Some("420") => args.extend(["-pix_fmt".to_string(), "yuvj420p".to_string()]),
+                    Some(other) => return Err(format!("Unsupported chroma subsampling: {}", other)),
+                    None => {}
+                }
+                Ok(("jpg", "image/jpeg", args))
+            }
+            "webp" => Ok(("webp", "image/webp", vec!["-c:v".to_string(), "libwebp".to_string(), "-quality".to_string(), quality.unwrap_or(85).to_string()])),
+            "png" => {
+                let pix_fmt = if self.high_bit_depth { "rgb48be" } else { "rgb24" };
+                Ok(("png", "image/png", vec!["-pix_fmt".to_string(), pix_fmt.to_string()]))
+            }
+            other => Err(format!("Unsupported frame format: {}", other)),
+        }
+    }
+}
 
+/// Decode the frame at `timestamp` and write it to `dest` as an image
+async fn write_frame(ffmpeg: &PathBuf, source: &str, timestamp: f64, format_args: &[String], dest: &str) -> Result<(), String> {
     // Extract frame using ffmpeg
     let timestamp_str = format!("{:.3}", timestamp);
 
-    let mut cmd = tokio::process::Command::new(&ffmpeg);
+    let mut cmd = tokio::process::Command::new(ffmpeg);
     cmd.args([
         "-ss", &timestamp_str,
-        "-i", &path,
+        "-i", source,
         "-vframes", "1",
-        "-q:v", "5",
-        "-y",
-        &frame_str,
     ]);
+    cmd.args(format_args);
+    cmd.args(["-y", dest]);
 
     #[cfg(target_os = "windows")]
     {
@@ -84,12 +161,64 @@ async fn extract_frame(app: tauri::AppHandle, path: String, timestamp: f64) -> R
         return Err("Failed to extract frame".to_string());
     }
 
+    Ok(())
+}
+
+#[tauri::command]
+async fn extract_frame(app: tauri::AppHandle, path: String, timestamp: f64, options: Option<FrameExportOptions>) -> Result<String, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+
+    // Exports decode the original at full quality; previews use the proxy if there is one
+    let (source, options) = match options {
+        Some(options) => (path.clone(), options),
+        None => (proxy::preview_source(&app, &path), FrameExportOptions::default()),
+    };
+    let (extension, mime, format_args) = options.output_args()?;
+
+    // Create temp file for the frame with unique name (timestamp + random)
+    let temp_dir = scratch::scratch_dir(&app);
+    let unique_id = format!("{}_{}", std::process::id(), std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos());
+    let frame_path = temp_dir.join(format!("frame_{}.{}", unique_id, extension));
+    let frame_str = frame_path.to_string_lossy().to_string();
+
+    write_frame(&ffmpeg, &source, timestamp, &format_args, &frame_str).await?;
+
     // Read the frame and convert to base64
     let frame_data = fs::read(&frame_path).map_err(|e| format!("Failed to read frame: {}", e))?;
     let _ = fs::remove_file(&frame_path);
 
     let base64_data = BASE64.encode(&frame_data);
-    Ok(format!("data:image/jpeg;base64,{}", base64_data))
+    Ok(format!("data:{};base64,{}", mime, base64_data))
+}
+
+/// Write a still of `timestamp` at the source's full resolution to `output_path`. The format
+/// comes from the options, or else from the output path's extension.
+#[tauri::command]
+async fn save_frame(
+    app: tauri::AppHandle,
+    path: String,
+    timestamp: f64,
+    output_path: String,
+    options: Option<FrameExportOptions>,
+) -> Result<u64, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+
+    let mut options = options.unwrap_or_default();
+    if options.format.is_none() {
+        options.format = Path::new(&output_path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+    }
+    let (_, _, format_args) = options.output_args()?;
+
+    write_frame(&ffmpeg, &path, timestamp, &format_args, &output_path).await?;
+
+    fs::metadata(&output_path)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read saved frame: {}", e))
 }
 
 #[tauri::command]
@@ -99,7 +228,7 @@ async fn extract_filmstrip(app: tauri::AppHandle, path: String, duration: f64, c
 
     for i in 0..count {
         let timestamp = i as f64 * interval;
-        match extract_frame(app.clone(), path.clone(), timestamp).await {
+        match extract_frame(app.clone(), path.clone(), timestamp, None).await {
             Ok(frame) => frames.push(frame),
             Err(_) => frames.push(String::new()), // Empty string for failed frames
         }
@@ -108,13 +237,60 @@ async fn extract_filmstrip(app: tauri::AppHandle, path: String, duration: f64, c
     Ok(frames)
 }
 
+/// Filmstrip variant that skips extracting visually identical frames (e.g. static screen recordings)
+#[tauri::command]
+async fn extract_filmstrip_deduped(app: tauri::AppHandle, path: String, duration: f64, count: u32) -> Result<DedupedFilmstrip, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    let source = proxy::preview_source(&app, &path);
+    let interval = duration / count as f64;
+    let timestamps: Vec<f64> = (0..count).map(|i| i as f64 * interval).collect();
+
+    // Cheap perceptual hashes first, so duplicates never go through full JPEG extraction
+    let mut hashes = Vec::with_capacity(timestamps.len());
+    for &timestamp in &timestamps {
+        hashes.push(frame_hash(&ffmpeg, &source, timestamp).await.ok());
+    }
+    let duplicates = find_duplicates(&hashes);
+
+    let mut frames = Vec::with_capacity(timestamps.len());
+    for (&timestamp, duplicate_of) in timestamps.iter().zip(duplicates) {
+        let image = match duplicate_of {
+            Some(_) => None,
+            None => extract_frame(app.clone(), path.clone(), timestamp, None).await.ok(),
+        };
+        frames.push(FilmstripFrame {
+            timestamp,
+            image,
+            duplicate_of,
+        });
+    }
+
+    let static_regions = static_regions(&frames);
+    Ok(DedupedFilmstrip {
+        frames,
+        static_regions,
+    })
+}
+
+/// One cached thumbnail per marker, extracted in batches for the chapter editor
+#[tauri::command]
+async fn get_marker_thumbnails(app: tauri::AppHandle, path: String, markers: Vec<Marker>) -> Result<Vec<thumbnails::MarkerThumbnail>, String> {
+    thumbnails::get_marker_thumbnails(&app, &path, &markers).await
+}
+
+/// Create a lightweight preview proxy; frame and filmstrip commands use it automatically once present
+#[tauri::command]
+async fn generate_proxy(app: tauri::AppHandle, path: String) -> Result<String, String> {
+    proxy::generate_proxy(&app, &path).await
+}
+
 #[tauri::command]
 async fn detect_scenes(app: tauri::AppHandle, path: String, threshold: Option<f64>) -> Result<Vec<f64>, String> {
     let ffmpeg = get_ffmpeg_path(&app);
     let threshold = threshold.unwrap_or(0.3);
 
-    // Build the scene detection filter
-    let filter = format!("select='gt(scene,{})',showinfo", threshold);
+    // Build the scene detection filter, at the scale calibrate_scene_threshold scores at
+    let filter = format!("{},select='gt(scene,{})',showinfo", scene_calibration::analysis_scale_filter(), threshold);
 
     let mut cmd = tokio::process::Command::new(&ffmpeg);
     cmd.args([
@@ -157,6 +333,13 @@ async fn detect_scenes(app: tauri::AppHandle, path: String, threshold: Option<f6
     Ok(timestamps)
 }
 
+/// Cut counts at several scene thresholds from a single analysis pass, for a live threshold slider
+#[tauri::command]
+async fn calibrate_scene_threshold(app: tauri::AppHandle, path: String, thresholds: Option<Vec<f64>>) -> Result<scene_calibration::SceneCalibration, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    scene_calibration::calibrate(&ffmpeg, &path, thresholds).await
+}
+
 #[tauri::command]
 async fn convert_file(
     app: tauri::AppHandle,
@@ -168,8 +351,309 @@ async fn convert_file(
     trim_start: Option<f64>,
     trim_duration: Option<f64>,
     markers: Option<Vec<Marker>>,
+    options: Option<ConversionOptions>,
 ) -> Result<ConversionResult, String> {
-    convert_file_impl(app, id, input_path, output_name, target_bytes, conversion_type, trim_start, trim_duration, markers).await
+    convert_file_impl(app, id, input_path, output_name, target_bytes, conversion_type, trim_start, trim_duration, markers, options).await
+}
+
+/// Several outputs of one input (e.g. 1080p/720p/480p or 10/25/100 MB) in a single job
+#[tauri::command]
+async fn convert_renditions(
+    app: tauri::AppHandle,
+    id: String,
+    input_path: String,
+    conversion_type: String,
+    renditions: Vec<renditions::Rendition>,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: Option<ConversionOptions>,
+) -> Result<Vec<ConversionResult>, String> {
+    renditions::convert_renditions(app, id, input_path, conversion_type, renditions, trim_start, trim_duration, markers, options).await
+}
+
+/// Bitrates, output size and expected quality for a target, using the converter's own math
+#[tauri::command]
+fn calculate_target(params: TargetParams) -> Result<TargetCalculation, String> {
+    converter::calculate_target(&params)
+}
+
+/// Read the settings of a previous export so it can be reproduced or tweaked: from the
+/// file's metadata tag, or from the export history for formats that can't carry one
+#[tauri::command]
+async fn reconvert_like(app: tauri::AppHandle, path: String) -> Result<EncodeSettings, String> {
+    let ffprobe = get_ffprobe_path(&app);
+    match read_encode_settings(&ffprobe, &path).await {
+        Ok(settings) => Ok(settings),
+        Err(e) => history::settings_for(&app, &path).ok_or(e),
+    }
+}
+
+/// Finished exports with the settings used, oldest first
+#[tauri::command]
+fn get_export_history(app: tauri::AppHandle) -> Vec<history::ExportRecord> {
+    history::load(&app)
+}
+
+/// Run a chain of steps (convert, copy/move output, recycle source) for one input,
+/// stopping dependent steps when one fails
+#[tauri::command]
+async fn run_pipeline(app: tauri::AppHandle, id: String, input_path: String, steps: Vec<PipelineStep>) -> Result<Vec<StepResult>, String> {
+    Ok(pipeline::run_pipeline(&app, &id, &input_path, &steps).await)
+}
+
+/// Pick a default preset for each newly added file from user rules, then built-in ones
+#[tauri::command]
+async fn infer_presets(app: tauri::AppHandle, paths: Vec<String>, rules: Option<Vec<PresetRule>>) -> Result<Vec<PresetInference>, String> {
+    let ffprobe = get_ffprobe_path(&app);
+    let rules = rules.unwrap_or_default();
+
+    let mut results = Vec::with_capacity(paths.len());
+    for path in paths {
+        // A file that can't be probed just gets no suggestion
+        let inference = infer_preset(&ffprobe, &path, &rules).await.unwrap_or(PresetInference {
+            path,
+            preset: None,
+            target_bytes: None,
+            rule: None,
+            source_app: None,
+        });
+        results.push(inference);
+    }
+    Ok(results)
+}
+
+/// Save a grid of thumbnails with timestamps and a file info header next to the source
+#[tauri::command]
+async fn generate_contact_sheet(app: tauri::AppHandle, path: String, output_name: String, columns: Option<u32>, rows: Option<u32>) -> Result<String, String> {
+    contact_sheet::generate_contact_sheet(&app, &path, &output_name, columns, rows).await
+}
+
+/// Forget cached encoder checks and probe the current ffmpeg again, e.g. after swapping
+/// the binary; returns the encoders it lists
+#[tauri::command]
+async fn refresh_capabilities(app: tauri::AppHandle) -> Vec<String> {
+    capabilities::refresh(&get_ffmpeg_path(&app)).await
+}
+
+/// The most recent ffmpeg output lines of a job (stderr plus progress summaries), for
+/// seeing what the encoder is doing when progress seems stuck
+#[tauri::command]
+fn get_job_log_tail(id: String, lines: Option<usize>) -> Vec<String> {
+    job_log::tail(&id, lines.unwrap_or(50))
+}
+
+/// Audio tracks of a (multi-track) recording with their guessed roles and usable presets
+#[tauri::command]
+async fn get_audio_tracks(app: tauri::AppHandle, path: String) -> Result<multitrack::AudioTrackLayout, String> {
+    multitrack::track_layout(&get_ffprobe_path(&app), &path).await
+}
+
+/// Write selected audio tracks (all when none are given) to separate WAV/FLAC files
+#[tauri::command]
+async fn export_audio_stems(app: tauri::AppHandle, path: String, tracks: Option<Vec<u32>>, format: Option<String>) -> Result<Vec<String>, String> {
+    stems::export_stems(&app, &path, tracks, format.as_deref()).await
+}
+
+/// Recommended format, size, resolution and fps for sharing to a destination (e.g. "discord-free", "email")
+#[tauri::command]
+fn recommend_export(destination: String, custom_mb: Option<f64>, duration: f64, source: SourceProperties) -> Result<DestinationRecommendation, String> {
+    destinations::recommend(&destination, custom_mb, duration, &source)
+}
+
+/// Convert a file for a destination (e.g. "discord-free") with automatic settings and copy
+/// the result to the clipboard, reporting progress as "quick-share-progress" events
+#[tauri::command]
+async fn quick_share(app: tauri::AppHandle, id: String, path: String, destination: String) -> Result<quick_share::QuickShareResult, String> {
+    quick_share::quick_share(app, id, path, destination).await
+}
+
+/// Where TikTok/Reels/Shorts UI covers a vertical export of a width x height source
+/// (e.g. "tiktok", "instagram", "youtube-shorts"), in source pixels
+#[tauri::command]
+fn get_safe_areas(platform: String, width: u32, height: u32) -> Result<safe_areas::SafeAreas, String> {
+    safe_areas::safe_areas(&platform, width, height)
+}
+
+/// Whether the user has been away and the CPU is quiet, for running queued jobs only while idle.
+/// The queue polls this and pauses as soon as it reports activity again.
+#[tauri::command]
+async fn get_idle_status(min_idle_seconds: Option<f64>, max_cpu_percent: Option<f64>) -> Result<idle::IdleStatus, String> {
+    Ok(idle::idle_status(min_idle_seconds.unwrap_or(300.0), max_cpu_percent.unwrap_or(25.0)).await)
+}
+
+/// Save presets and rules (plus settings, if provided) to a JSON file for sharing
+#[tauri::command]
+fn export_presets(path: String, presets: Vec<SharedPreset>, rules: Option<Vec<PresetRule>>, settings: Option<serde_json::Value>) -> Result<(), String> {
+    presets::export_presets(&path, presets, rules.unwrap_or_default(), settings)
+}
+
+/// Load a shared preset file and merge it with the current presets
+#[tauri::command]
+fn import_presets(path: String, existing: Vec<SharedPreset>, on_conflict: Option<String>) -> Result<ImportResult, String> {
+    presets::import_presets(&path, existing, on_conflict.as_deref().unwrap_or("rename"))
+}
+
+/// Suggest an audioOffsetMs value by matching audio transients (e.g. a clap) to motion
+#[tauri::command]
+async fn estimate_audio_offset(app: tauri::AppHandle, path: String, start: Option<f64>, duration: Option<f64>) -> Result<sync::AudioOffsetEstimate, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    sync::estimate_audio_offset(&ffmpeg, &path, start, duration).await
+}
+
+/// Count dropped and duplicated frames (optionally over just the first `sample_seconds`)
+#[tauri::command]
+async fn analyze_frame_timing(app: tauri::AppHandle, path: String, sample_seconds: Option<f64>) -> Result<frame_timing::FrameTimingReport, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    let ffprobe = get_ffprobe_path(&app);
+    frame_timing::analyze_frame_timing(&ffmpeg, &ffprobe, &path, sample_seconds).await
+}
+
+/// Exact next/previous frame and its timestamp, for frame-by-frame stepping ("next" or "previous")
+#[tauri::command]
+async fn get_adjacent_frame(app: tauri::AppHandle, path: String, timestamp: f64, direction: String) -> Result<frame_step::AdjacentFrame, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    let ffprobe = get_ffprobe_path(&app);
+    frame_step::adjacent_frame(&ffmpeg, &ffprobe, &scratch::scratch_dir(&app), &path, timestamp, &direction).await
+}
+
+/// Remember the playhead, trim and markers of a file so reopening it restores the cut
+#[tauri::command]
+fn save_file_state(app: tauri::AppHandle, path: String, state: file_state::FileState) -> Result<(), String> {
+    file_state::save_file_state(&app, &path, state)
+}
+
+/// Saved state for a file, or null if it was never saved or has changed since
+#[tauri::command]
+fn load_file_state(app: tauri::AppHandle, path: String) -> Result<Option<file_state::FileState>, String> {
+    file_state::load_file_state(&app, &path)
+}
+
+/// Save the whole session (files, trims, markers, presets, destinations) as a project file
+#[tauri::command]
+fn save_project(path: String, project: project::Project) -> Result<(), String> {
+    project::save_project(&path, project)
+}
+
+/// Open a project, locating moved source files by name in the project folder or `search_dirs`
+#[tauri::command]
+fn open_project(path: String, search_dirs: Option<Vec<String>>) -> Result<project::OpenedProject, String> {
+    project::open_project(&path, &search_dirs.unwrap_or_default())
+}
+
+/// Reverse the most recent removal, overwrite or move made by a conversion or pipeline
+#[tauri::command]
+fn undo_last_action(app: tauri::AppHandle) -> Result<undo::UndoResult, String> {
+    undo::undo_last_action(&app)
+}
+
+/// Version, license, libraries and per-conversion-type support of the bundled ffmpeg
+#[tauri::command]
+async fn get_ffmpeg_build_report(app: tauri::AppHandle) -> Result<build_info::FfmpegBuildReport, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    build_info::build_report(&ffmpeg).await
+}
+
+/// Encoders the conversion types use, whether they work on this machine, their pixel
+/// formats and size limits, and which conversion types are available as a result
+#[tauri::command]
+async fn get_encoder_capabilities(app: tauri::AppHandle) -> Result<build_info::EncoderCapabilities, String> {
+    build_info::encoder_capabilities(&get_ffmpeg_path(&app)).await
+}
+
+/// Encode a short test clip with every usable encoder and report speed and quality,
+/// with the best default encoder per codec for this machine
+#[tauri::command]
+async fn benchmark_encoders(app: tauri::AppHandle) -> Result<benchmark::BenchmarkReport, String> {
+    let ffmpeg = get_ffmpeg_path(&app);
+    benchmark::benchmark_encoders(&app, &ffmpeg).await
+}
+
+/// Projected output size, bitrates and encode time for a conversion of a real input
+#[tauri::command]
+async fn estimate_output(
+    app: tauri::AppHandle,
+    input_path: String,
+    conversion_type: String,
+    target_bytes: u64,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    options: Option<ConversionOptions>,
+) -> Result<OutputEstimate, String> {
+    let options = options.unwrap_or_default();
+    converter::estimate_output(&app, &input_path, &conversion_type, target_bytes, trim_start, trim_duration, &options).await
+}
+
+/// Upload constraints of the built-in platform presets (size, length, resolution, formats)
+#[tauri::command]
+fn get_platform_presets() -> Vec<PlatformPreset> {
+    destinations::platform_presets()
+}
+
+/// Scratch folder override and size quota
+#[tauri::command]
+fn get_scratch_config(app: tauri::AppHandle) -> scratch::ScratchConfig {
+    scratch::load_config(&app)
+}
+
+#[tauri::command]
+fn set_scratch_config(app: tauri::AppHandle, config: scratch::ScratchConfig) -> Result<(), String> {
+    scratch::save_config(&app, &config)
+}
+
+#[tauri::command]
+fn get_whisper_config(app: tauri::AppHandle) -> captions::WhisperConfig {
+    captions::load_config(&app)
+}
+
+#[tauri::command]
+fn set_whisper_config(app: tauri::AppHandle, config: captions::WhisperConfig) -> Result<(), String> {
+    captions::save_config(&app, &config)
+}
+
+/// Transcribe a file with the configured whisper.cpp and save an .srt next to it, to pass
+/// as the `captions` conversion option
+#[tauri::command]
+async fn generate_captions(app: tauri::AppHandle, path: String) -> Result<String, String> {
+    captions::transcribe(&app, &get_ffmpeg_path(&app), &scratch::scratch_dir(&app), &path).await
+}
+
+/// Jobs a crash or power cut interrupted, with the files that were cleaned up or put
+/// back, so they can be offered for resumption
+#[tauri::command]
+fn get_interrupted_jobs(app: tauri::AppHandle) -> Vec<job_journal::InterruptedJob> {
+    job_journal::interrupted_jobs(&app)
+}
+
+/// Run an interrupted job again from the start, with the settings it was submitted with
+#[tauri::command]
+async fn resume_interrupted_job(app: tauri::AppHandle, id: String) -> Result<ConversionResult, String> {
+    let job = job_journal::take_interrupted(&app, &id)?;
+    convert_file_impl(app, job.id, job.input_path, job.output_name, job.target_bytes, job.conversion_type, job.trim_start, job.trim_duration, job.markers, Some(job.options)).await
+}
+
+/// Persist the jobs still waiting in the queue; called whenever the queue changes
+#[tauri::command]
+fn save_queue(app: tauri::AppHandle, jobs: Vec<job_journal::JournaledJob>) -> Result<(), String> {
+    job_journal::save_queue(&app, &jobs)
+}
+
+/// The waiting jobs as last saved, to restore the queue at startup
+#[tauri::command]
+fn get_saved_queue(app: tauri::AppHandle) -> Vec<job_journal::JournaledJob> {
+    job_journal::saved_queue(&app)
+}
+
+/// Forget interrupted jobs: the given ids, or all of them
+#[tauri::command]
+fn dismiss_interrupted_jobs(app: tauri::AppHandle, ids: Option<Vec<String>>) -> Result<(), String> {
+    job_journal::dismiss_interrupted(&app, ids)
+}
+
+/// Trim the scratch folder down to its quota now (otherwise done before each conversion)
+#[tauri::command]
+fn cleanup_scratch(app: tauri::AppHandle) -> scratch::ScratchCleanup {
+    scratch::enforce_quota(&app)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -177,7 +661,14 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_store::Builder::new().build())
-        .invoke_handler(tauri::generate_handler![get_file_size, get_video_duration, get_video_info_cmd, get_media_metadata_cmd, extract_frame, extract_filmstrip, detect_scenes, convert_file])
+        .setup(|app| {
+            // Nothing is running yet: clean up after jobs a crash or power cut interrupted,
+            // and anything left in the scratch folder
+            job_journal::recover(app.handle());
+            scratch::clear_scratch(app.handle());
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![get_file_size, get_video_duration, get_video_info_cmd, get_media_metadata_cmd, extract_frame, save_frame, extract_filmstrip, extract_filmstrip_deduped, generate_proxy, detect_scenes, calibrate_scene_threshold, convert_file, convert_renditions, get_job_log_tail, calculate_target, reconvert_like, get_export_history, infer_presets, run_pipeline, generate_contact_sheet, get_audio_tracks, export_audio_stems, recommend_export, get_idle_status, export_presets, import_presets, get_marker_thumbnails, estimate_audio_offset, analyze_frame_timing, get_adjacent_frame, save_file_state, load_file_state, save_project, open_project, undo_last_action, get_scratch_config, set_scratch_config, cleanup_scratch, get_whisper_config, set_whisper_config, generate_captions, get_ffmpeg_build_report, refresh_capabilities, get_interrupted_jobs, resume_interrupted_job, dismiss_interrupted_jobs, save_queue, get_saved_queue, get_safe_areas, quick_share, get_encoder_capabilities, benchmark_encoders, estimate_output, get_platform_presets])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,133 @@
+use crate::converter::TrackGain;
+use crate::ffmpeg::{get_audio_streams, AudioStreamInfo};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Volumes for the "mix" preset: commentary stays on top of the game audio
+const MIX_MIC_VOLUME: f64 = 0.7;
+const MIX_GAME_VOLUME: f64 = 0.3;
+
+/// Generic names muxers give tracks when the user didn't name them
+const GENERIC_HANDLERS: &[&str] = &["soundhandler", "sound media handler", "core media audio"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioTrack {
+    pub index: u32,
+    pub name: Option<String>,
+    /// "mic", "game", "mix" or "unknown"
+    pub role: String,
+}
+
+/// Audio tracks of a recording and the presets that apply to it
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioTrackLayout {
+    /// More than one audio track, as in OBS multi-track recordings
+    #[serde(rename = "multiTrack")]
+    pub multi_track: bool,
+    pub tracks: Vec<AudioTrack>,
+    /// Presets that resolve for this file: "mic", "game" and/or "mix"
+    pub presets: Vec<String>,
+}
+
+/// The user-visible track name: the stream title, or the MP4 handler name unless it's generic
+fn track_name(stream: &AudioStreamInfo) -> Option<String> {
+    stream
+        .title
+        .clone()
+        .or_else(|| stream.handler.clone())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty() && !GENERIC_HANDLERS.contains(&name.to_lowercase().as_str()))
+}
+
+fn role_from_name(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| name.contains(w));
+    if has(&["mic", "voice", "commentary", "chat", "discord"]) {
+        Some("mic")
+    } else if has(&["game", "desktop", "system", "app"]) {
+        Some("game")
+    } else if has(&["mix", "all", "master"]) {
+        Some("mix")
+    } else {
+        None
+    }
+}
+
+/// Guess each track's role from its name. Unnamed tracks fall back to the common OBS setups:
+/// two tracks are game + mic, three or more are full mix + game + mic.
+fn classify(streams: &[AudioStreamInfo]) -> Vec<AudioTrack> {
+    let fallback: &[&str] = match streams.len() {
+        2 => &["game", "mic"],
+        n if n >= 3 => &["mix", "game", "mic"],
+        _ => &[],
+    };
+
+    let mut tracks: Vec<AudioTrack> = streams
+        .iter()
+        .map(|stream| {
+            let name = track_name(stream);
+            let role = name.as_deref().and_then(role_from_name);
+            AudioTrack {
+                index: stream.index,
+                name,
+                role: role.unwrap_or("unknown").to_string(),
+            }
+        })
+        .collect();
+
+    // Only guess by position when no track could be identified by name
+    if tracks.iter().all(|t| t.role == "unknown") {
+        for (track, role) in tracks.iter_mut().zip(fallback) {
+            track.role = role.to_string();
+        }
+    }
+    tracks
+}
+
+fn find_role(tracks: &[AudioTrack], role: &str) -> Option<u32> {
+    tracks.iter().find(|t| t.role == role).map(|t| t.index)
+}
+
+fn available_presets(tracks: &[AudioTrack]) -> Vec<String> {
+    let mic = find_role(tracks, "mic").is_some();
+    let game = find_role(tracks, "game").is_some();
+    let mut presets = Vec::new();
+    if mic {
+        presets.push("mic".to_string());
+    }
+    if game {
+        presets.push("game".to_string());
+    }
+    if mic && game {
+        presets.push("mix".to_string());
+    }
+    presets
+}
+
+pub async fn track_layout(ffprobe_path: &PathBuf, path: &str) -> Result<AudioTrackLayout, String> {
+    let streams = get_audio_streams(ffprobe_path, path).await?;
+    let tracks = classify(&streams);
+    Ok(AudioTrackLayout {
+        multi_track: tracks.len() > 1,
+        presets: available_presets(&tracks),
+        tracks,
+    })
+}
+
+/// Turn an audio preset ("mic", "game" or "mix") into the tracks to mix for this file
+pub async fn resolve_preset(ffprobe_path: &PathBuf, path: &str, preset: &str) -> Result<Vec<TrackGain>, String> {
+    let tracks = classify(&get_audio_streams(ffprobe_path, path).await?);
+    let track = |role: &str| {
+        find_role(&tracks, role).ok_or_else(|| format!("No {} track found in this recording", role))
+    };
+
+    match preset {
+        "mic" => Ok(vec![TrackGain { track: track("mic")?, volume: 1.0 }]),
+        "game" => Ok(vec![TrackGain { track: track("game")?, volume: 1.0 }]),
+        "mix" => Ok(vec![
+            TrackGain { track: track("mic")?, volume: MIX_MIC_VOLUME },
+            TrackGain { track: track("game")?, volume: MIX_GAME_VOLUME },
+        ]),
+        other => Err(format!("Unknown audio preset: {}", other)),
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tauri::Manager;
+
+/// Error code returned when the destination folder can't be written to
+pub const ERROR_OUTPUT_NOT_WRITABLE: &str = "output_not_writable";
+
+/// Where the output file will be written
+pub struct OutputLocation {
+    pub path: PathBuf,
+    /// Set when the source folder wasn't writable and the fallback folder was used instead
+    pub fallback_dir: Option<PathBuf>,
+}
+
+/// Check that a file can be created in `dir` by creating and removing a probe file
+fn is_dir_writable(dir: &Path) -> bool {
+    let probe = dir.join(format!(".torchio-write-test-{}", std::process::id()));
+    match fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// An existing read-only file can't be overwritten even if its folder is writable
+fn is_file_overwritable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|m| !m.permissions().readonly())
+        .unwrap_or(true)
+}
+
+/// Folder used when the source folder is protected (Videos/Torchio, then Downloads/Torchio)
+pub fn fallback_output_dir(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let base = app
+        .path()
+        .video_dir()
+        .or_else(|_| app.path().download_dir())
+        .or_else(|_| app.path().app_data_dir())
+        .ok()?;
+    Some(base.join("Torchio"))
+}
+
+/// Delete what a failed job left at `path`, but only if it was written since `since`;
+/// an older file there is the user's own and stays. Returns whether a file was removed.
+pub fn remove_partial_output(path: &Path, since: SystemTime) -> bool {
+    let written_by_job = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified >= since);
+    written_by_job && fs::remove_file(path).is_ok()
+}
+
+/// Rename, falling back to copy + delete across volumes (e.g. from the scratch folder)
+pub fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map_err(|e| format!("Failed to move {}: {}", from.to_string_lossy(), e))?;
+    let _ = fs::remove_file(from);
+    Ok(())
+}
+
+/// Resolve the output path next to the input, pre-flighting write access so
+/// permission problems fail before encoding instead of after it.
+pub fn resolve_output_path(
+    app: &tauri::AppHandle,
+    input_path: &str,
+    output_name: &str,
+    allow_fallback: bool,
+) -> Result<OutputLocation, String> {
+    let input_pathbuf = PathBuf::from(input_path);
+    let parent = input_pathbuf.parent().unwrap_or(&input_pathbuf);
+    let output_path = parent.join(output_name);
+
+    if is_dir_writable(parent) && is_file_overwritable(&output_path) {
+        return Ok(OutputLocation {
+            path: output_path,
+            fallback_dir: None,
+        });
+    }
+
+    let fallback = fallback_output_dir(app);
+
+    if allow_fallback {
+        if let Some(dir) = fallback.as_ref() {
+            let _ = fs::create_dir_all(dir);
+            let fallback_path = dir.join(output_name);
+            if is_dir_writable(dir) && is_file_overwritable(&fallback_path) {
+                return Ok(OutputLocation {
+                    path: fallback_path,
+                    fallback_dir: Some(dir.clone()),
+                });
+            }
+        }
+    }
+
+    let mut message = format!("Cannot write to output location: {}", output_path.to_string_lossy());
+    if let Some(dir) = fallback {
+        message.push_str(&format!(" (try saving to {} instead)", dir.to_string_lossy()));
+    }
+    Err(message)
+}
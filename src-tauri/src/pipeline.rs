@@ -0,0 +1,283 @@
+use crate::converter::{convert_file_impl, ConversionOptions, Marker};
+use crate::undo;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+use tokio::process::Command;
+
+/// What a pipeline step does. Upload and webhook steps aren't implemented, here or in
+/// the frontend (the app has no HTTP client); a pipeline ends with its last local step.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind")]
+pub enum PipelineAction {
+    #[serde(rename = "convert")]
+    Convert {
+        #[serde(rename = "outputName")]
+        output_name: String,
+        #[serde(rename = "targetBytes")]
+        target_bytes: u64,
+        #[serde(rename = "conversionType")]
+        conversion_type: String,
+        #[serde(rename = "trimStart")]
+        trim_start: Option<f64>,
+        #[serde(rename = "trimDuration")]
+        trim_duration: Option<f64>,
+        markers: Option<Vec<Marker>>,
+        options: Option<Box<ConversionOptions>>,
+    },
+    /// Copy the converted output into another folder
+    #[serde(rename = "copy_output")]
+    CopyOutput {
+        #[serde(rename = "destDir")]
+        dest_dir: String,
+    },
+    /// Move the converted output into another folder
+    #[serde(rename = "move_output")]
+    MoveOutput {
+        #[serde(rename = "destDir")]
+        dest_dir: String,
+    },
+    /// Remove the source file (undoable; it reaches the system trash once it ages out of the undo history)
+    #[serde(rename = "recycle_source")]
+    RecycleSource,
+}
+
+impl PipelineAction {
+    fn kind(&self) -> &'static str {
+        match self {
+            PipelineAction::Convert { .. } => "convert",
+            PipelineAction::CopyOutput { .. } => "copy_output",
+            PipelineAction::MoveOutput { .. } => "move_output",
+            PipelineAction::RecycleSource => "recycle_source",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PipelineStep {
+    #[serde(flatten)]
+    pub action: PipelineAction,
+    /// Indices of steps that must succeed first (defaults to the previous step)
+    #[serde(rename = "dependsOn", default)]
+    pub depends_on: Option<Vec<usize>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub kind: String,
+    /// "completed", "failed" or "skipped"
+    pub status: String,
+    pub error: Option<String>,
+    #[serde(rename = "outputPath")]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PipelineProgressPayload {
+    id: String,
+    step: usize,
+    kind: String,
+    status: String,
+    error: Option<String>,
+}
+
+fn emit_step(app: &tauri::AppHandle, id: &str, step: usize, kind: &str, status: &str, error: Option<&str>) {
+    let _ = app.emit(
+        "pipeline-progress",
+        PipelineProgressPayload {
+            id: id.to_string(),
+            step,
+            kind: kind.to_string(),
+            status: status.to_string(),
+            error: error.map(String::from),
+        },
+    );
+}
+
+/// Send a file to the platform recycle bin / trash rather than deleting it outright
+pub async fn recycle_file(path: &str) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let script = format!(
+            "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.FileIO.FileSystem]::DeleteFile('{}', 'OnlyErrorDialogs', 'SendToRecycleBin')",
+            path.replace('\'', "''")
+        );
+        let mut cmd = Command::new("powershell");
+        cmd.args(["-NoProfile", "-Command", &script]);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut cmd = {
+        let script = format!(
+            "tell application \"Finder\" to delete POSIX file \"{}\"",
+            path.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.args(["-e", &script]);
+        cmd
+    };
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut cmd = {
+        let mut cmd = Command::new("gio");
+        cmd.args(["trash", path]);
+        cmd
+    };
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to recycle file: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to recycle file: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+fn destination_for(output: &Path, dest_dir: &str) -> Result<PathBuf, String> {
+    let file_name = output.file_name().ok_or("Output has no file name")?;
+    fs::create_dir_all(dest_dir).map_err(|e| format!("Failed to create {}: {}", dest_dir, e))?;
+    Ok(Path::new(dest_dir).join(file_name))
+}
+
+async fn run_step(
+    app: &tauri::AppHandle,
+    id: &str,
+    input_path: &str,
+    action: &PipelineAction,
+    current_output: &mut Option<PathBuf>,
+) -> Result<Option<String>, String> {
+    match action {
+        PipelineAction::Convert {
+            output_name,
+            target_bytes,
+            conversion_type,
+            trim_start,
+            trim_duration,
+            markers,
+            options,
+        } => {
+            let result = convert_file_impl(
+                app.clone(),
+                id.to_string(),
+                input_path.to_string(),
+                output_name.clone(),
+                *target_bytes,
+                conversion_type.clone(),
+                *trim_start,
+                *trim_duration,
+                markers.clone(),
+                options.as_deref().cloned(),
+            )
+            .await?;
+
+            if !result.success {
+                return Err(result.error.unwrap_or_else(|| "Conversion failed".to_string()));
+            }
+            *current_output = result.output_path.as_ref().map(PathBuf::from);
+            Ok(result.output_path)
+        }
+        PipelineAction::CopyOutput { dest_dir } => {
+            let output = current_output.as_ref().ok_or("No converted output to copy")?;
+            let dest = destination_for(output, dest_dir)?;
+            let staged = stage_existing(app, &dest).await?;
+            if let Err(e) = fs::copy(output, &dest) {
+                restore_existing(app, &dest, staged);
+                return Err(format!("Failed to copy output: {}", e));
+            }
+            Ok(Some(dest.to_string_lossy().to_string()))
+        }
+        PipelineAction::MoveOutput { dest_dir } => {
+            let output = current_output.as_ref().ok_or("No converted output to move")?;
+            let dest = destination_for(output, dest_dir)?;
+            let staged = stage_existing(app, &dest).await?;
+            // rename fails across volumes; fall back to copy + delete
+            if fs::rename(output, &dest).is_err() {
+                let moved = fs::copy(output, &dest)
+                    .and_then(|_| fs::remove_file(output))
+                    .map_err(|e| format!("Failed to move output: {}", e));
+                if let Err(e) = moved {
+                    restore_existing(app, &dest, staged);
+                    return Err(e);
+                }
+            }
+            undo::record_move(app, output, &dest).await?;
+            *current_output = Some(dest.clone());
+            Ok(Some(dest.to_string_lossy().to_string()))
+        }
+        PipelineAction::RecycleSource => {
+            // Staged rather than trashed right away, so undo_last_action can bring it back
+            undo::stage_removal(app, input_path).await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Stage a file already at `dest` so the copy or move replacing it can be undone;
+/// whether there was one
+async fn stage_existing(app: &tauri::AppHandle, dest: &Path) -> Result<bool, String> {
+    if !dest.is_file() {
+        return Ok(false);
+    }
+    undo::stage_overwrite(app, dest).await?;
+    Ok(true)
+}
+
+/// After a failed copy or move: drop the partial file at `dest` and put back what was
+/// staged there
+fn restore_existing(app: &tauri::AppHandle, dest: &Path, staged: bool) {
+    if staged {
+        let _ = undo::unstage_overwrite(app, dest);
+    } else {
+        let _ = fs::remove_file(dest);
+    }
+}
+
+/// Run steps in order; a step whose dependencies didn't all complete is skipped
+pub async fn run_pipeline(app: &tauri::AppHandle, id: &str, input_path: &str, steps: &[PipelineStep]) -> Vec<StepResult> {
+    let mut results: Vec<StepResult> = Vec::with_capacity(steps.len());
+    let mut current_output: Option<PathBuf> = None;
+
+    for (i, step) in steps.iter().enumerate() {
+        let kind = step.action.kind();
+        let deps = step
+            .depends_on
+            .clone()
+            .unwrap_or_else(|| if i > 0 { vec![i - 1] } else { Vec::new() });
+        let deps_ok = deps
+            .iter()
+            .all(|&d| d < i && results[d].status == "completed");
+
+        if !deps_ok {
+            emit_step(app, id, i, kind, "skipped", None);
+            results.push(StepResult {
+                kind: kind.to_string(),
+                status: "skipped".to_string(),
+                error: None,
+                output_path: None,
+            });
+            continue;
+        }
+
+        emit_step(app, id, i, kind, "running", None);
+        let result = match run_step(app, id, input_path, &step.action, &mut current_output).await {
+            Ok(output_path) => StepResult {
+                kind: kind.to_string(),
+                status: "completed".to_string(),
+                error: None,
+                output_path,
+            },
+            Err(e) => StepResult {
+                kind: kind.to_string(),
+                status: "failed".to_string(),
+                error: Some(e),
+                output_path: None,
+            },
+        };
+        emit_step(app, id, i, kind, &result.status, result.error.as_deref());
+        results.push(result);
+    }
+
+    results
+}
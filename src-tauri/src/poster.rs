@@ -0,0 +1,150 @@
+use crate::ffmpeg::{get_ffmpeg_path, get_ffprobe_path, get_video_info};
+use crate::job_journal;
+use crate::output::{remove_partial_output, resolve_output_path};
+use crate::scratch;
+use crate::undo;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::process::Command;
+
+/// Values of the poster conversion option
+pub const POSTER_MODES: &[&str] = &["sidecar", "embed", "both"];
+
+/// Poster frames are thumbnails; file managers never show them larger
+const POSTER_MAX_WIDTH: u32 = 1280;
+
+/// Where in the export the poster frame is taken, as a fraction of its length: past
+/// fade-ins and black leaders
+const POSTER_POSITION: f64 = 0.1;
+
+fn command(path: &Path) -> Command {
+    #[allow(unused_mut)]
+    let mut cmd = Command::new(path);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    cmd
+}
+
+async fn run(mut cmd: Command, what: &str) -> Result<(), String> {
+    let output = cmd.output().await.map_err(|e| format!("Failed to run {}: {}", what, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or_default();
+        return Err(format!("{} failed: {}", what, reason));
+    }
+    Ok(())
+}
+
+/// Temporary file the embedding remux writes before replacing the export
+fn embedded_path(output_path: &Path) -> PathBuf {
+    let extension = output_path.extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default();
+    output_path.with_extension(format!("poster.{}", extension))
+}
+
+/// Number of `selector` streams ("v", "t", ...) in a file
+async fn stream_count(ffprobe_path: &Path, path: &str, selector: &str) -> usize {
+    let mut cmd = command(ffprobe_path);
+    cmd.args(["-v", "error", "-select_streams", selector, "-show_entries", "stream=index", "-of", "csv=p=0", path]);
+    match cmd.output().await {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).lines().filter(|l| !l.trim().is_empty()).count(),
+        Err(_) => 0,
+    }
+}
+
+/// Save one frame of the export as a JPEG
+async fn extract_poster(ffmpeg_path: &Path, ffprobe_path: &PathBuf, output_str: &str, poster: &Path) -> Result<(), String> {
+    let duration = get_video_info(ffprobe_path, output_str).await.map(|info| info.duration).unwrap_or(0.0);
+    let mut cmd = command(ffmpeg_path);
+    cmd.args(["-y", "-ss", &format!("{:.3}", duration * POSTER_POSITION), "-i", output_str]);
+    // V skips attached pictures, e.g. a poster embedded by an earlier run
+    cmd.args(["-map", "0:V:0", "-frames:v", "1", "-vf", &format!("scale='min({},iw)':-2", POSTER_MAX_WIDTH), "-q:v", "3"]);
+    cmd.arg(poster);
+    run(cmd, "Extracting the poster frame").await
+}
+
+/// Remux the export with the poster as its thumbnail: a cover.jpg attachment for MKV
+/// (the Matroska convention), an attached picture for MP4/MOV
+async fn embed_poster(ffmpeg_path: &Path, ffprobe_path: &Path, output_path: &Path, poster: &Path) -> Result<(), String> {
+    let extension = output_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let output_str = output_path.to_string_lossy().to_string();
+    let poster_str = poster.to_string_lossy().to_string();
+    let embedded = embedded_path(output_path);
+
+    let mut cmd = command(ffmpeg_path);
+    match extension.as_str() {
+        "mkv" => {
+            // -attach adds the picture after the attachments already there (e.g. fonts)
+            let attachment = stream_count(ffprobe_path, &output_str, "t").await;
+            cmd.args(["-y", "-i", &output_str, "-map", "0", "-c", "copy", "-attach", &poster_str]);
+            cmd.args([&format!("-metadata:s:t:{}", attachment), "mimetype=image/jpeg"]);
+            cmd.args([&format!("-metadata:s:t:{}", attachment), "filename=cover.jpg"]);
+        }
+        "mp4" | "mov" | "m4v" => {
+            let picture = stream_count(ffprobe_path, &output_str, "v").await;
+            cmd.args(["-y", "-i", &output_str, "-i", &poster_str, "-map", "0", "-map", "1", "-c", "copy"]);
+            cmd.args([&format!("-disposition:v:{}", picture), "attached_pic", "-movflags", "+faststart"]);
+        }
+        _ => return Err(format!("Can't embed a thumbnail in .{} files", extension)),
+    }
+    cmd.args(["-map_metadata", "0"]);
+    cmd.arg(&embedded);
+    if let Err(e) = run(cmd, "Embedding the poster").await {
+        let _ = fs::remove_file(&embedded);
+        return Err(e);
+    }
+    fs::rename(&embedded, output_path).map_err(|e| format!("Failed to replace output: {}", e))
+}
+
+/// Embed a poster frame in a finished export, so file managers that ignore its video
+/// still show a thumbnail. The JPEG only lives in the job's scratch workspace.
+pub async fn embed(app: &tauri::AppHandle, id: &str, output_path: &Path) -> Result<(), String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+    let workspace = scratch::JobWorkspace::new(app, id)?;
+    let poster = workspace.file("poster.jpg");
+
+    extract_poster(&ffmpeg, &ffprobe, &output_path.to_string_lossy(), &poster).await?;
+    job_journal::created(app, id, &embedded_path(output_path));
+    embed_poster(&ffmpeg, &ffprobe, output_path, &poster).await
+}
+
+/// Write a poster frame as `clip.jpg` next to `clip.mkv`, where Explorer and Finder-style
+/// browsers look for it. Like any output, write access is checked first and a file
+/// already there is staged so the overwrite can be undone.
+pub async fn write_sidecar(app: &tauri::AppHandle, id: &str, output_path: &Path) -> Result<PathBuf, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+    let output_str = output_path.to_string_lossy().to_string();
+    let stem = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    let sidecar = resolve_output_path(app, &output_str, &format!("{}.jpg", stem), false)?.path;
+
+    let staged = sidecar.is_file();
+    if staged {
+        undo::stage_overwrite(app, &sidecar)
+            .await
+            .map_err(|e| format!("Failed to keep the existing {}: {}", sidecar.to_string_lossy(), e))?;
+        job_journal::staged(app, id, &sidecar);
+    }
+    job_journal::created(app, id, &sidecar);
+    let started = SystemTime::now();
+    if let Err(e) = extract_poster(&ffmpeg, &ffprobe, &output_str, &sidecar).await {
+        remove_partial_output(&sidecar, started);
+        if staged {
+            let _ = undo::unstage_overwrite(app, &sidecar);
+        }
+        return Err(e);
+    }
+    Ok(sidecar)
+}
@@ -0,0 +1,59 @@
+#[cfg(target_os = "windows")]
+mod win {
+    #[repr(C)]
+    #[derive(Default)]
+    pub struct SystemPowerStatus {
+        pub ac_line_status: u8,
+        pub battery_flag: u8,
+        pub battery_life_percent: u8,
+        pub system_status_flag: u8,
+        pub battery_life_time: u32,
+        pub battery_full_life_time: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetSystemPowerStatus(status: *mut SystemPowerStatus) -> i32;
+    }
+}
+
+/// Whether the machine is running on battery; desktops and unknown states report false
+#[cfg(target_os = "windows")]
+pub async fn on_battery() -> bool {
+    let mut status = win::SystemPowerStatus::default();
+    // SAFETY: status is a valid, writable SYSTEM_POWER_STATUS
+    let ok = unsafe { win::GetSystemPowerStatus(&mut status) };
+    // ACLineStatus: 0 = offline, 1 = online, 255 = unknown
+    ok != 0 && status.ac_line_status == 0
+}
+
+#[cfg(target_os = "macos")]
+pub async fn on_battery() -> bool {
+    // First line reads "Now drawing from 'Battery Power'" or "'AC Power'"
+    match tokio::process::Command::new("pmset").args(["-g", "batt"]).output().await {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub async fn on_battery() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+
+    let mut has_battery = false;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let kind = std::fs::read_to_string(dir.join("type")).unwrap_or_default();
+        match kind.trim() {
+            // Any connected charger means we're not draining the battery
+            "Mains" | "USB" if std::fs::read_to_string(dir.join("online")).is_ok_and(|v| v.trim() == "1") => {
+                return false;
+            }
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    has_battery
+}
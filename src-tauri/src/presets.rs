@@ -0,0 +1,143 @@
+use crate::cache::write_atomic;
+use crate::converter::{ConversionOptions, CONVERSION_TYPES};
+use crate::rules::PresetRule;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Format version written into exported files; bump when the layout changes incompatibly
+const PRESET_FILE_VERSION: u32 = 1;
+
+/// A named export profile (format, size and options) users can share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedPreset {
+    pub name: String,
+    #[serde(rename = "conversionType")]
+    pub conversion_type: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    #[serde(default)]
+    pub options: ConversionOptions,
+}
+
+/// Contents of an exported preset file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetBundle {
+    pub version: u32,
+    #[serde(default)]
+    pub presets: Vec<SharedPreset>,
+    #[serde(default)]
+    pub rules: Vec<PresetRule>,
+    /// App settings as stored by the frontend, only present when the user chose to include them
+    #[serde(default)]
+    pub settings: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    /// Existing presets merged with the imported ones
+    pub presets: Vec<SharedPreset>,
+    pub rules: Vec<PresetRule>,
+    pub settings: Option<serde_json::Value>,
+    /// Names of imported presets that clashed with existing ones
+    pub conflicts: Vec<String>,
+    /// Imported presets that were dropped, with the reason
+    pub rejected: Vec<String>,
+}
+
+fn validate_preset(preset: &SharedPreset) -> Result<(), String> {
+    if preset.name.trim().is_empty() {
+        return Err("preset has no name".to_string());
+    }
+    if !CONVERSION_TYPES.contains(&preset.conversion_type.as_str()) {
+        return Err(format!("{}: unknown conversion type {}", preset.name, preset.conversion_type));
+    }
+    if preset.target_bytes == 0 {
+        return Err(format!("{}: target size must be positive", preset.name));
+    }
+    Ok(())
+}
+
+/// First "name (n)" not already taken
+fn unique_name(name: &str, taken: &[SharedPreset]) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken.iter().any(|p| &p.name == candidate))
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Write presets (and optionally settings) to a shareable JSON file
+pub fn export_presets(
+    path: &str,
+    presets: Vec<SharedPreset>,
+    rules: Vec<PresetRule>,
+    settings: Option<serde_json::Value>,
+) -> Result<(), String> {
+    for preset in &presets {
+        validate_preset(preset)?;
+    }
+
+    let bundle = PresetBundle {
+        version: PRESET_FILE_VERSION,
+        presets,
+        rules,
+        settings,
+    };
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| format!("Failed to serialize presets: {}", e))?;
+    write_atomic(Path::new(path), json).map_err(|e| format!("Failed to write preset file: {}", e))
+}
+
+/// Read a preset file and merge it into `existing`.
+/// `on_conflict` decides what happens to same-named presets: "skip", "overwrite" or "rename".
+pub fn import_presets(
+    path: &str,
+    existing: Vec<SharedPreset>,
+    on_conflict: &str,
+) -> Result<ImportResult, String> {
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read preset file: {}", e))?;
+    let bundle: PresetBundle = serde_json::from_str(&json).map_err(|e| format!("Invalid preset file: {}", e))?;
+
+    if bundle.version > PRESET_FILE_VERSION {
+        return Err(format!(
+            "Preset file version {} is newer than this app supports ({})",
+            bundle.version, PRESET_FILE_VERSION
+        ));
+    }
+    if !matches!(on_conflict, "skip" | "overwrite" | "rename") {
+        return Err(format!("Unknown conflict mode: {}", on_conflict));
+    }
+
+    let mut presets = existing;
+    let mut conflicts = Vec::new();
+    let mut rejected = Vec::new();
+
+    for mut preset in bundle.presets {
+        if let Err(e) = validate_preset(&preset) {
+            rejected.push(e);
+            continue;
+        }
+
+        match presets.iter().position(|p| p.name == preset.name) {
+            None => presets.push(preset),
+            Some(index) => {
+                conflicts.push(preset.name.clone());
+                match on_conflict {
+                    "overwrite" => presets[index] = preset,
+                    "rename" => {
+                        preset.name = unique_name(&preset.name, &presets);
+                        presets.push(preset);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok(ImportResult {
+        presets,
+        rules: bundle.rules,
+        settings: bundle.settings,
+        conflicts,
+        rejected,
+    })
+}
@@ -0,0 +1,153 @@
+use crate::converter::{ConversionOptions, Marker};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Format version written into project files
+const PROJECT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTrim {
+    pub start: f64,
+    pub end: f64,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// One queued file with everything needed to export it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectFile {
+    /// Relative to the project file when it lives under the same folder, otherwise absolute
+    pub path: String,
+    /// Absolute path at save time, tried when the relative one doesn't resolve
+    #[serde(rename = "absolutePath", default)]
+    pub absolute_path: Option<String>,
+    #[serde(default)]
+    pub trims: Vec<ProjectTrim>,
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+    #[serde(rename = "conversionType", default)]
+    pub conversion_type: Option<String>,
+    #[serde(rename = "targetBytes", default)]
+    pub target_bytes: Option<u64>,
+    #[serde(default)]
+    pub options: Option<ConversionOptions>,
+    #[serde(rename = "outputDir", default)]
+    pub output_dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    #[serde(default)]
+    pub version: u32,
+    pub files: Vec<ProjectFile>,
+}
+
+/// A project file entry whose source couldn't be found where it was saved
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingFile {
+    pub index: usize,
+    /// Path as stored in the project
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedProject {
+    /// The project with every path made absolute (found files point at their new location)
+    pub project: Project,
+    pub missing: Vec<MissingFile>,
+    /// Indices of files that were found somewhere other than their saved location
+    pub relocated: Vec<usize>,
+}
+
+/// Store `path` relative to `base` when it lives underneath it
+fn relative_to(path: &str, base: &Path) -> String {
+    Path::new(path)
+        .strip_prefix(base)
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_else(|_| path.to_string())
+}
+
+fn project_dir(project_path: &str) -> PathBuf {
+    Path::new(project_path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+pub fn save_project(project_path: &str, mut project: Project) -> Result<(), String> {
+    let base = project_dir(project_path);
+    project.version = PROJECT_VERSION;
+
+    for file in project.files.iter_mut() {
+        file.absolute_path = Some(file.path.clone());
+        file.path = relative_to(&file.path, &base);
+        if let Some(ref dir) = file.output_dir {
+            file.output_dir = Some(relative_to(dir, &base));
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&project).map_err(|e| format!("Failed to serialize project: {}", e))?;
+    fs::write(project_path, json).map_err(|e| format!("Failed to save project: {}", e))
+}
+
+/// Where to look for a file, in order: stored path (relative to the project), saved
+/// absolute path, then the same file name next to the project or in any search folder
+fn candidate_paths(file: &ProjectFile, base: &Path, search_dirs: &[String]) -> Vec<PathBuf> {
+    let mut candidates = vec![base.join(&file.path)];
+    if let Some(ref absolute) = file.absolute_path {
+        candidates.push(PathBuf::from(absolute));
+    }
+    if let Some(name) = Path::new(&file.path).file_name() {
+        candidates.push(base.join(name));
+        candidates.extend(search_dirs.iter().map(|dir| Path::new(dir).join(name)));
+    }
+    candidates
+}
+
+pub fn open_project(project_path: &str, search_dirs: &[String]) -> Result<OpenedProject, String> {
+    let json = fs::read_to_string(project_path).map_err(|e| format!("Failed to read project: {}", e))?;
+    let mut project: Project = serde_json::from_str(&json).map_err(|e| format!("Invalid project file: {}", e))?;
+    if project.version > PROJECT_VERSION {
+        return Err(format!(
+            "Project version {} is newer than this app supports ({})",
+            project.version, PROJECT_VERSION
+        ));
+    }
+
+    let base = project_dir(project_path);
+    let mut missing = Vec::new();
+    let mut relocated = Vec::new();
+
+    for (index, file) in project.files.iter_mut().enumerate() {
+        let candidates = candidate_paths(file, &base, search_dirs);
+        // The stored path, and the absolute path when there is one, are where the file
+        // was saved; a match further down the list means it moved
+        let saved_locations = if file.absolute_path.is_some() { 2 } else { 1 };
+        match candidates.iter().position(|p| p.is_file()) {
+            Some(found) => {
+                if found >= saved_locations {
+                    relocated.push(index);
+                }
+                file.path = candidates[found].to_string_lossy().to_string();
+            }
+            None => {
+                missing.push(MissingFile {
+                    index,
+                    path: file.path.clone(),
+                });
+                file.path = candidates[0].to_string_lossy().to_string();
+            }
+        }
+        file.absolute_path = None;
+        if let Some(ref dir) = file.output_dir {
+            file.output_dir = Some(base.join(dir).to_string_lossy().to_string());
+        }
+    }
+
+    Ok(OpenedProject {
+        project,
+        missing,
+        relocated,
+    })
+}
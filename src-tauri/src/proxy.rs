@@ -0,0 +1,78 @@
+use crate::cache::{cache_subdir, file_cache_key, is_cached};
+use crate::ffmpeg::{get_ffmpeg_path, get_ffprobe_path, get_video_info, run_ffmpeg_with_progress};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use tauri::Emitter;
+
+#[derive(Debug, Clone, Serialize)]
+struct ProxyProgressPayload {
+    path: String,
+    progress: f64,
+}
+
+/// Location of the proxy for a source file (whether or not it exists yet)
+fn proxy_path(app: &tauri::AppHandle, path: &str) -> Result<PathBuf, String> {
+    let dir = cache_subdir(app, "proxies")?;
+    Ok(dir.join(format!("{}.mp4", file_cache_key(path))))
+}
+
+/// The file preview commands should decode: the proxy if one was generated, otherwise the source
+pub fn preview_source(app: &tauri::AppHandle, path: &str) -> String {
+    match proxy_path(app, path) {
+        Ok(proxy) if is_cached(&proxy) => proxy.to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Create a small 480p all-intra proxy so seeking for previews is cheap
+pub async fn generate_proxy(app: &tauri::AppHandle, path: &str) -> Result<String, String> {
+    let proxy = proxy_path(app, path)?;
+    let proxy_str = proxy.to_string_lossy().to_string();
+
+    if is_cached(&proxy) {
+        return Ok(proxy_str);
+    }
+
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+    let info = get_video_info(&ffprobe, path).await?;
+
+    // Encode to a temp name first so a half-written proxy is never picked up
+    let partial = proxy.with_extension("partial.mp4");
+    let partial_str = partial.to_string_lossy().to_string();
+
+    let args = vec![
+        "-y",
+        "-i", path,
+        "-vf", "scale=-2:'min(480,ih)'",
+        "-c:v", "libx264",
+        "-preset", "ultrafast",
+        "-crf", "28",
+        "-g", "1", // Every frame is a keyframe
+        "-pix_fmt", "yuv420p",
+        "-an",
+        &partial_str,
+    ];
+
+    let app_clone = app.clone();
+    let path_clone = path.to_string();
+    let result = run_ffmpeg_with_progress(&ffmpeg, args, info.duration, None, |progress| {
+        let _ = app_clone.emit(
+            "proxy-progress",
+            ProxyProgressPayload {
+                path: path_clone.clone(),
+                progress,
+            },
+        );
+    })
+    .await;
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    fs::rename(&partial, &proxy).map_err(|e| format!("Failed to finalize proxy: {}", e))?;
+    Ok(proxy_str)
+}
@@ -0,0 +1,140 @@
+use crate::clipboard;
+use crate::converter::{convert_file_impl, output_extension, ConversionOptions, ConversionResult};
+use crate::destinations::{self, DestinationRecommendation, SourceProperties};
+use crate::ffmpeg::{get_ffprobe_path, get_media_metadata};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tauri::{Emitter, Listener};
+
+/// Share of the quick-share progress bar the conversion fills
+const CONVERT_START: f64 = 2.0;
+const CONVERT_END: f64 = 90.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickShareResult {
+    /// The format, size and resolution picked for the destination
+    pub recommendation: DestinationRecommendation,
+    pub conversion: ConversionResult,
+    /// Whether the output is on the clipboard, ready to paste
+    #[serde(rename = "copiedToClipboard")]
+    pub copied_to_clipboard: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct QuickShareProgress {
+    id: String,
+    progress: f64,
+    /// "analyzing", "converting", "copying", "completed" or "failed"
+    stage: String,
+}
+
+/// conversion-progress as emitted by the converter
+#[derive(Deserialize)]
+struct ConversionProgress {
+    id: String,
+    progress: f64,
+}
+
+fn emit_stage(app: &tauri::AppHandle, id: &str, progress: f64, stage: &str) {
+    let _ = app.emit(
+        "quick-share-progress",
+        QuickShareProgress {
+            id: id.to_string(),
+            progress,
+            stage: stage.to_string(),
+        },
+    );
+}
+
+/// One-click sharing: pick the format and size for `destination` (e.g. "discord-free"),
+/// convert under its limits, check the result against them and put the file on the
+/// clipboard. Progress for the whole run comes as "quick-share-progress" events.
+/// A file that converted but couldn't be copied is still a success; the UI can offer
+/// to reveal it instead.
+pub async fn quick_share(app: tauri::AppHandle, id: String, path: String, destination: String) -> Result<QuickShareResult, String> {
+    emit_stage(&app, &id, 0.0, "analyzing");
+    let outcome = share(&app, &id, &path, &destination).await;
+    match outcome {
+        Ok(ref result) if result.conversion.success => emit_stage(&app, &id, 100.0, "completed"),
+        _ => emit_stage(&app, &id, 100.0, "failed"),
+    }
+    outcome
+}
+
+async fn share(app: &tauri::AppHandle, id: &str, path: &str, destination: &str) -> Result<QuickShareResult, String> {
+    let source = get_media_metadata(&get_ffprobe_path(app), path).await?;
+    let properties = SourceProperties {
+        width: source.width,
+        height: source.height,
+        fps: source.frame_rate_decimal,
+        has_audio: source.audio_codec.is_some(),
+    };
+    let recommendation = destinations::recommend(destination, None, source.duration, &properties)?;
+
+    // The platform option makes the converter apply the destination's format fix-ups and
+    // check the output against its limits
+    let options = ConversionOptions {
+        platform: Some(destination.to_string()),
+        strict_size: true,
+        max_height: Some(recommendation.height),
+        ..Default::default()
+    };
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "share".to_string());
+    let output_name = format!("{}_{}.{}", stem, destination, output_extension(&recommendation.conversion_type));
+
+    // Fold the conversion's own progress events into this job's stream
+    let conversion_id = format!("{}_convert", id);
+    let relay = {
+        let app = app.clone();
+        let id = id.to_string();
+        let conversion_id = conversion_id.clone();
+        app.clone().listen("conversion-progress", move |event| {
+            if let Ok(progress) = serde_json::from_str::<ConversionProgress>(event.payload()) {
+                if progress.id == conversion_id {
+                    let overall = CONVERT_START + progress.progress / 100.0 * (CONVERT_END - CONVERT_START);
+                    emit_stage(&app, &id, overall, "converting");
+                }
+            }
+        })
+    };
+    let conversion = convert_file_impl(
+        app.clone(),
+        conversion_id,
+        path.to_string(),
+        output_name,
+        recommendation.target_bytes,
+        recommendation.conversion_type.clone(),
+        None,
+        None,
+        None,
+        Some(options),
+    )
+    .await;
+    app.unlisten(relay);
+    let mut conversion = conversion?;
+
+    let mut copied_to_clipboard = false;
+    if conversion.success {
+        // The converter has checked the output against the destination's limits; a miss
+        // is in platformChecks and the warnings, and the file is still copied
+        emit_stage(app, id, CONVERT_END, "copying");
+        if let Some(ref output) = conversion.output_path {
+            match clipboard::copy_file(Path::new(output)).await {
+                Ok(()) => copied_to_clipboard = true,
+                Err(e) => conversion.warnings.push(e),
+            }
+        }
+    }
+    if let Some(ref warning) = recommendation.warning {
+        conversion.warnings.push(warning.clone());
+    }
+
+    Ok(QuickShareResult {
+        recommendation,
+        conversion,
+        copied_to_clipboard,
+    })
+}
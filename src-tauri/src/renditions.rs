@@ -0,0 +1,89 @@
+use crate::converter::{convert_file_impl, ConversionOptions, ConversionResult, Marker};
+use crate::ffmpeg::{get_ffprobe_path, get_video_info};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+/// One output of a renditions job; unset fields follow the job
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rendition {
+    #[serde(rename = "outputName")]
+    pub output_name: String,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: u64,
+    #[serde(rename = "conversionType", default)]
+    pub conversion_type: Option<String>,
+    /// e.g. 720 for a 720p rendition
+    #[serde(rename = "maxHeight", default)]
+    pub max_height: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RenditionProgressPayload {
+    id: String,
+    rendition: usize,
+    /// conversion-progress events for this rendition carry this id
+    #[serde(rename = "conversionId")]
+    conversion_id: String,
+    status: String,
+}
+
+fn emit_rendition(app: &tauri::AppHandle, id: &str, rendition: usize, conversion_id: &str, status: &str) {
+    let _ = app.emit(
+        "rendition-progress",
+        RenditionProgressPayload {
+            id: id.to_string(),
+            rendition,
+            conversion_id: conversion_id.to_string(),
+            status: status.to_string(),
+        },
+    );
+}
+
+/// Convert one input into several outputs (sizes or resolutions), one after another.
+/// The source is probed once up front and the converters reuse that probe. Rendition
+/// `i` reports progress as conversion "{id}_{i}"; a failed rendition doesn't stop the rest.
+pub async fn convert_renditions(
+    app: tauri::AppHandle,
+    id: String,
+    input_path: String,
+    conversion_type: String,
+    renditions: Vec<Rendition>,
+    trim_start: Option<f64>,
+    trim_duration: Option<f64>,
+    markers: Option<Vec<Marker>>,
+    options: Option<ConversionOptions>,
+) -> Result<Vec<ConversionResult>, String> {
+    if renditions.is_empty() {
+        return Err("No renditions requested".to_string());
+    }
+    get_video_info(&get_ffprobe_path(&app), &input_path).await?;
+
+    let options = options.unwrap_or_default();
+    let mut results = Vec::with_capacity(renditions.len());
+    for (i, rendition) in renditions.into_iter().enumerate() {
+        let conversion_id = format!("{}_{}", id, i);
+        emit_rendition(&app, &id, i, &conversion_id, "started");
+
+        let mut rendition_options = options.clone();
+        if rendition.max_height.is_some() {
+            rendition_options.max_height = rendition.max_height;
+        }
+        let result = convert_file_impl(
+            app.clone(),
+            conversion_id.clone(),
+            input_path.clone(),
+            rendition.output_name,
+            rendition.target_bytes,
+            rendition.conversion_type.unwrap_or_else(|| conversion_type.clone()),
+            trim_start,
+            trim_duration,
+            markers.clone(),
+            Some(rendition_options),
+        )
+        .await?;
+
+        emit_rendition(&app, &id, i, &conversion_id, if result.success { "completed" } else { "failed" });
+        results.push(result);
+    }
+    Ok(results)
+}
@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, ChildStdout, Command};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+/// How soon the monitor notices the process ended
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a job's ffmpeg runs cost, summed over all of them (e.g. both passes)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobResources {
+    /// Time ffmpeg ran for
+    #[serde(rename = "wallSeconds")]
+    pub wall_seconds: f64,
+    /// User + system CPU time; above wallSeconds means several cores were busy.
+    /// None if the process couldn't be read
+    #[serde(rename = "cpuSeconds")]
+    pub cpu_seconds: Option<f64>,
+    /// Highest resident memory seen in a single ffmpeg run
+    #[serde(rename = "peakMemoryBytes")]
+    pub peak_memory_bytes: Option<u64>,
+    /// NVIDIA GPU utilization (%) while ffmpeg ran, from nvidia-smi; it covers the whole
+    /// GPU, so other apps using it count too
+    #[serde(rename = "peakGpuPercent")]
+    pub peak_gpu_percent: Option<f64>,
+    #[serde(rename = "averageGpuPercent")]
+    pub average_gpu_percent: Option<f64>,
+    #[serde(skip)]
+    gpu_samples: u32,
+}
+
+/// Usage of each job so far, by job id
+static USAGE: OnceLock<Mutex<HashMap<String, JobResources>>> = OnceLock::new();
+
+fn usage() -> &'static Mutex<HashMap<String, JobResources>> {
+    USAGE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Forget a job's usage, e.g. when its id is reused for a new run
+pub fn clear(id: &str) {
+    if let Ok(mut usage) = usage().lock() {
+        usage.remove(id);
+    }
+}
+
+/// The job's usage, removed from the tracker
+pub fn take(id: &str) -> Option<JobResources> {
+    usage().lock().ok().and_then(|mut usage| usage.remove(id))
+}
+
+/// One reading of a running process
+#[derive(Default)]
+struct ProcessSample {
+    cpu_seconds: Option<f64>,
+    memory_bytes: Option<u64>,
+}
+
+/// Read a process's CPU time and resident memory; `system` is reused across readings
+fn sample_process(system: &mut System, pid: Pid) -> ProcessSample {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        false,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+    system
+        .process(pid)
+        .map(|process| ProcessSample {
+            cpu_seconds: Some(process.accumulated_cpu_time() as f64 / 1000.0),
+            memory_bytes: Some(process.memory()),
+        })
+        .unwrap_or_default()
+}
+
+static NVIDIA_SMI: OnceLock<bool> = OnceLock::new();
+
+/// GPU utilization readings over one ffmpeg run
+#[derive(Default)]
+struct GpuReadings {
+    peak: Option<f64>,
+    total: f64,
+    count: u32,
+}
+
+impl GpuReadings {
+    fn add(&mut self, percent: f64) {
+        self.peak = Some(self.peak.map_or(percent, |peak| peak.max(percent)));
+        self.total += percent;
+        self.count += 1;
+    }
+}
+
+/// Start one nvidia-smi that prints utilization every SAMPLE_INTERVAL, rather than
+/// spawning it per reading; None without nvidia-smi
+fn spawn_nvidia_smi() -> Option<Child> {
+    if NVIDIA_SMI.get() == Some(&false) {
+        return None;
+    }
+    #[allow(unused_mut)]
+    let mut cmd = Command::new("nvidia-smi");
+    cmd.args([
+        "--query-gpu=timestamp,utilization.gpu",
+        "--format=csv,noheader,nounits",
+        &format!("--loop-ms={}", SAMPLE_INTERVAL.as_millis()),
+    ]);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::null()).kill_on_drop(true);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let child = cmd.spawn().ok();
+    if child.is_none() {
+        let _ = NVIDIA_SMI.set(false);
+    }
+    child
+}
+
+/// Collect nvidia-smi's readings until its output closes. Each interval prints one line
+/// per GPU with the same timestamp; the busiest GPU's line is the reading.
+async fn read_gpu(stdout: ChildStdout) -> GpuReadings {
+    let mut readings = GpuReadings::default();
+    let mut interval: Option<(String, f64)> = None;
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Some((timestamp, percent)) = line.rsplit_once(',') else {
+            continue;
+        };
+        let Ok(percent) = percent.trim().parse::<f64>() else {
+            continue;
+        };
+        match &mut interval {
+            Some((current, busiest)) if current == timestamp => *busiest = busiest.max(percent),
+            _ => {
+                if let Some((_, busiest)) = interval.replace((timestamp.to_string(), percent)) {
+                    readings.add(busiest);
+                }
+            }
+        }
+    }
+    if let Some((_, busiest)) = interval {
+        readings.add(busiest);
+    }
+    readings
+}
+
+/// Sample an ffmpeg process until `done` is set, then add its usage to the job's
+pub async fn monitor(id: String, pid: u32, done: Arc<AtomicBool>) {
+    let started = Instant::now();
+    let mut run = JobResources::default();
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    let mut nvidia_smi = spawn_nvidia_smi();
+    let gpu_reader = nvidia_smi
+        .as_mut()
+        .and_then(|child| child.stdout.take())
+        .map(|stdout| tauri::async_runtime::spawn(read_gpu(stdout)));
+
+    while !done.load(Ordering::Relaxed) {
+        let sample = sample_process(&mut system, pid);
+        // The last reading before exit stands in for the total
+        if sample.cpu_seconds.is_some() {
+            run.cpu_seconds = sample.cpu_seconds;
+        }
+        run.peak_memory_bytes = run.peak_memory_bytes.max(sample.memory_bytes);
+        let next_sample = Instant::now() + SAMPLE_INTERVAL;
+        while !done.load(Ordering::Relaxed) && Instant::now() < next_sample {
+            tokio::time::sleep(STOP_CHECK_INTERVAL).await;
+        }
+    }
+    run.wall_seconds = started.elapsed().as_secs_f64();
+
+    let mut gpu = GpuReadings::default();
+    if let Some(mut child) = nvidia_smi {
+        // Having quit by itself with a failure means there's no usable driver
+        if matches!(child.try_wait(), Ok(Some(status)) if !status.success()) {
+            let _ = NVIDIA_SMI.set(false);
+        }
+        let _ = child.start_kill();
+        if let Some(reader) = gpu_reader {
+            gpu = reader.await.unwrap_or_default();
+        }
+        let _ = child.wait().await;
+    }
+    run.peak_gpu_percent = gpu.peak;
+    run.gpu_samples = gpu.count;
+    let gpu_total = gpu.total;
+
+    let Ok(mut usage) = usage().lock() else {
+        return;
+    };
+    let job = usage.entry(id).or_default();
+    let job_gpu_total = job.average_gpu_percent.unwrap_or(0.0) * job.gpu_samples as f64 + gpu_total;
+    job.wall_seconds += run.wall_seconds;
+    if let Some(cpu) = run.cpu_seconds {
+        job.cpu_seconds = Some(job.cpu_seconds.unwrap_or(0.0) + cpu);
+    }
+    job.peak_memory_bytes = job.peak_memory_bytes.max(run.peak_memory_bytes);
+    if let Some(peak) = run.peak_gpu_percent {
+        job.peak_gpu_percent = Some(job.peak_gpu_percent.map_or(peak, |p| p.max(peak)));
+    }
+    job.gpu_samples += run.gpu_samples;
+    if job.gpu_samples > 0 {
+        job.average_gpu_percent = Some(job_gpu_total / job.gpu_samples as f64);
+    }
+}
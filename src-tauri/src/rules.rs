@@ -0,0 +1,188 @@
+use crate::ffmpeg::{get_format_tags, get_media_metadata, MediaMetadata};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Conditions a file must satisfy for a rule to apply (unset conditions always match)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConditions {
+    /// Case-insensitive substring of the detected source app (e.g. "obs", "iphone")
+    #[serde(rename = "sourceApp")]
+    pub source_app: Option<String>,
+    /// Video codec name as reported by ffprobe (e.g. "h264", "hevc")
+    #[serde(rename = "videoCodec")]
+    pub video_codec: Option<String>,
+    #[serde(rename = "minHeight")]
+    pub min_height: Option<u32>,
+    #[serde(rename = "maxHeight")]
+    pub max_height: Option<u32>,
+    #[serde(rename = "minDuration")]
+    pub min_duration: Option<f64>,
+    #[serde(rename = "maxDuration")]
+    pub max_duration: Option<f64>,
+    /// "portrait" or "landscape", after applying rotation
+    pub orientation: Option<String>,
+    #[serde(rename = "hasAudio")]
+    pub has_audio: Option<bool>,
+}
+
+/// Maps matching files to the preset that should be pre-selected for them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetRule {
+    pub name: String,
+    #[serde(default)]
+    pub conditions: RuleConditions,
+    /// Preset / format id to select (e.g. "mp4_hevc", "webp")
+    pub preset: String,
+    #[serde(rename = "targetBytes", default)]
+    pub target_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetInference {
+    pub path: String,
+    pub preset: Option<String>,
+    #[serde(rename = "targetBytes")]
+    pub target_bytes: Option<u64>,
+    /// Name of the rule that matched
+    pub rule: Option<String>,
+    #[serde(rename = "sourceApp")]
+    pub source_app: Option<String>,
+}
+
+/// Properties of a file that rules are evaluated against
+struct FileFacts {
+    source_app: Option<String>,
+    metadata: MediaMetadata,
+}
+
+impl FileFacts {
+    /// Displayed dimensions, swapping width/height for 90/270 degree rotations
+    fn display_size(&self) -> (u32, u32) {
+        let (w, h) = (self.metadata.width, self.metadata.height);
+        match self.metadata.rotation.map(|r| r.rem_euclid(180)) {
+            Some(90) => (h, w),
+            _ => (w, h),
+        }
+    }
+}
+
+/// Guess which app produced a file from its container tags
+fn detect_source_app(tags: &[(String, String)]) -> Option<String> {
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    };
+
+    if let Some(model) = tag("com.apple.quicktime.model") {
+        return Some(model);
+    }
+    if tag("com.android.version").is_some() {
+        return Some("Android".to_string());
+    }
+    tag("encoder").or_else(|| tag("software"))
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn matches(conditions: &RuleConditions, facts: &FileFacts) -> bool {
+    let meta = &facts.metadata;
+    let (width, height) = facts.display_size();
+
+    if let Some(ref app) = conditions.source_app {
+        match facts.source_app {
+            Some(ref source) if contains_ignore_case(source, app) => {}
+            _ => return false,
+        }
+    }
+    if let Some(ref codec) = conditions.video_codec {
+        if !meta.video_codec.as_deref().is_some_and(|c| c.eq_ignore_ascii_case(codec)) {
+            return false;
+        }
+    }
+    if conditions.min_height.is_some_and(|min| height < min) {
+        return false;
+    }
+    if conditions.max_height.is_some_and(|max| height > max) {
+        return false;
+    }
+    if conditions.min_duration.is_some_and(|min| meta.duration < min) {
+        return false;
+    }
+    if conditions.max_duration.is_some_and(|max| meta.duration > max) {
+        return false;
+    }
+    if let Some(ref orientation) = conditions.orientation {
+        let actual = if height > width { "portrait" } else { "landscape" };
+        if !orientation.eq_ignore_ascii_case(actual) {
+            return false;
+        }
+    }
+    if let Some(has_audio) = conditions.has_audio {
+        if meta.audio_codec.is_some() != has_audio {
+            return false;
+        }
+    }
+    true
+}
+
+/// Rules applied after any user rules
+fn builtin_rules() -> Vec<PresetRule> {
+    vec![
+        PresetRule {
+            name: "Short silent clip".to_string(),
+            conditions: RuleConditions {
+                max_duration: Some(15.0),
+                has_audio: Some(false),
+                ..Default::default()
+            },
+            preset: "webp".to_string(),
+            target_bytes: None,
+        },
+        PresetRule {
+            name: "4K recording".to_string(),
+            conditions: RuleConditions {
+                min_height: Some(2160),
+                ..Default::default()
+            },
+            preset: "mp4_hevc".to_string(),
+            target_bytes: None,
+        },
+        PresetRule {
+            name: "Phone portrait video".to_string(),
+            conditions: RuleConditions {
+                orientation: Some("portrait".to_string()),
+                ..Default::default()
+            },
+            preset: "mp4".to_string(),
+            target_bytes: None,
+        },
+    ]
+}
+
+/// Evaluate user rules (in order), then the built-in ones, and return the first match
+pub async fn infer_preset(ffprobe_path: &PathBuf, path: &str, user_rules: &[PresetRule]) -> Result<PresetInference, String> {
+    let metadata = get_media_metadata(ffprobe_path, path).await?;
+    let tags = get_format_tags(ffprobe_path, path).await.unwrap_or_default();
+    let facts = FileFacts {
+        source_app: detect_source_app(&tags),
+        metadata,
+    };
+
+    let builtin = builtin_rules();
+    let matched = user_rules
+        .iter()
+        .chain(builtin.iter())
+        .find(|rule| matches(&rule.conditions, &facts));
+
+    Ok(PresetInference {
+        path: path.to_string(),
+        preset: matched.map(|r| r.preset.clone()),
+        target_bytes: matched.and_then(|r| r.target_bytes),
+        rule: matched.map(|r| r.name.clone()),
+        source_app: facts.source_app,
+    })
+}
@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+/// Platform overlays are laid out on a 1080x1920 frame
+const REFERENCE_WIDTH: f64 = 1080.0;
+const REFERENCE_HEIGHT: f64 = 1920.0;
+
+/// A rectangle in source pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Part of the frame covered by platform chrome
+#[derive(Debug, Clone, Serialize)]
+pub struct CoveredArea {
+    /// e.g. "caption" or "actions"
+    pub label: String,
+    #[serde(flatten)]
+    pub rect: Rect,
+}
+
+/// Where a vertical export's platform UI sits, in source pixels
+#[derive(Debug, Clone, Serialize)]
+pub struct SafeAreas {
+    pub platform: String,
+    /// The 9:16 window of the source that becomes the export (centered)
+    pub frame: Rect,
+    /// Inside the frame and clear of every covered area
+    pub safe: Rect,
+    pub covered: Vec<CoveredArea>,
+}
+
+/// (x, y, width, height) on the reference frame
+type RefRect = (u32, u32, u32, u32);
+
+/// Labelled covered areas on the reference frame
+type Overlays = &'static [(&'static str, RefRect)];
+
+/// Safe rectangle and covered areas per platform, from the platforms' published
+/// safe-zone guides. The right-hand strip is the like/comment/share button column.
+fn platform_layout(platform: &str) -> Result<(RefRect, Overlays), String> {
+    match platform {
+        "tiktok" => Ok((
+            (60, 150, 880, 1330),
+            &[("top", (0, 0, 1080, 150)), ("caption", (0, 1480, 1080, 440)), ("actions", (940, 600, 140, 880))],
+        )),
+        // Reels
+        "instagram" => Ok((
+            (60, 220, 900, 1280),
+            &[("top", (0, 0, 1080, 220)), ("caption", (0, 1500, 1080, 420)), ("actions", (960, 860, 120, 640))],
+        )),
+        "youtube-shorts" => Ok((
+            (60, 140, 830, 1360),
+            &[("top", (0, 0, 1080, 140)), ("caption", (0, 1500, 1080, 420)), ("actions", (890, 860, 190, 640))],
+        )),
+        _ => Err(format!("No safe-area layout for platform: {}", platform)),
+    }
+}
+
+/// Largest centered 9:16 window that fits the source
+fn vertical_frame(width: u32, height: u32) -> Rect {
+    let frame_width = (width as f64).min(height as f64 * 9.0 / 16.0);
+    let frame_height = frame_width * 16.0 / 9.0;
+    Rect {
+        x: ((width as f64 - frame_width) / 2.0).round() as u32,
+        y: ((height as f64 - frame_height) / 2.0).round() as u32,
+        width: frame_width.round() as u32,
+        height: frame_height.round() as u32,
+    }
+}
+
+/// Map a reference-frame rectangle into the source window
+fn scale_rect(frame: Rect, (x, y, width, height): RefRect) -> Rect {
+    let sx = frame.width as f64 / REFERENCE_WIDTH;
+    let sy = frame.height as f64 / REFERENCE_HEIGHT;
+    Rect {
+        x: frame.x + (x as f64 * sx).round() as u32,
+        y: frame.y + (y as f64 * sy).round() as u32,
+        width: (width as f64 * sx).round() as u32,
+        height: (height as f64 * sy).round() as u32,
+    }
+}
+
+/// Safe and covered areas of a platform's vertical player, scaled onto a width x height
+/// source, so the UI can show what its chrome will hide before exporting
+pub fn safe_areas(platform: &str, width: u32, height: u32) -> Result<SafeAreas, String> {
+    if width == 0 || height == 0 {
+        return Err("Source dimensions must be positive".to_string());
+    }
+    let (safe, covered) = platform_layout(platform)?;
+    let frame = vertical_frame(width, height);
+    Ok(SafeAreas {
+        platform: platform.to_string(),
+        frame,
+        safe: scale_rect(frame, safe),
+        covered: covered
+            .iter()
+            .map(|(label, rect)| CoveredArea {
+                label: label.to_string(),
+                rect: scale_rect(frame, *rect),
+            })
+            .collect(),
+    })
+}
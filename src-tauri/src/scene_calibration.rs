@@ -0,0 +1,105 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Thresholds sampled when the caller doesn't pass its own
+const DEFAULT_THRESHOLDS: &[f64] = &[0.1, 0.15, 0.2, 0.25, 0.3, 0.4, 0.5, 0.6];
+
+/// Suggest the lowest threshold that averages no more than one cut per this many seconds
+const SUGGESTED_SECONDS_PER_CUT: f64 = 5.0;
+
+/// Scores are computed on a small copy of each frame; cut detection doesn't need detail.
+/// detect_scenes scales the same way, or the counts here wouldn't match its cuts.
+const ANALYSIS_WIDTH: u32 = 320;
+
+/// Scale filter both scene analyses run through before scoring
+pub fn analysis_scale_filter() -> String {
+    format!("scale={}:-2", ANALYSIS_WIDTH)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdCuts {
+    pub threshold: f64,
+    pub cuts: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SceneCalibration {
+    pub thresholds: Vec<ThresholdCuts>,
+    /// A reasonable starting point for the slider
+    pub suggested: f64,
+}
+
+/// Scene change score (0-1) of every frame after the first, with its timestamp
+async fn scene_scores(ffmpeg_path: &PathBuf, path: &str) -> Result<Vec<(f64, f64)>, String> {
+    let filter = format!(
+        "{},select='gte(scene,0)',metadata=print:key=lavfi.scene_score",
+        analysis_scale_filter()
+    );
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-i", path, "-an", "-vf", &filter, "-f", "null", "-"]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to analyse scene changes".to_string());
+    }
+
+    // metadata=print writes a "frame:N pts:... pts_time:T" line followed by "lavfi.scene_score=S"
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut scores = Vec::new();
+    let mut current_time = None;
+    for line in stderr.lines() {
+        if let Some(pos) = line.find("pts_time:") {
+            let after = &line[pos + 9..];
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            current_time = after[..end].parse::<f64>().ok();
+        } else if let Some(pos) = line.find("lavfi.scene_score=") {
+            let score = line[pos + 18..].trim().parse::<f64>().ok();
+            if let (Some(time), Some(score)) = (current_time.take(), score) {
+                scores.push((time, score));
+            }
+        }
+    }
+    Ok(scores)
+}
+
+/// Analyse the file once and count the cuts each threshold would produce, using the
+/// same rules as detect_scenes (cuts in the first 0.1s are ignored)
+pub async fn calibrate(ffmpeg_path: &PathBuf, path: &str, thresholds: Option<Vec<f64>>) -> Result<SceneCalibration, String> {
+    let mut thresholds = thresholds.unwrap_or_else(|| DEFAULT_THRESHOLDS.to_vec());
+    thresholds.retain(|t| *t > 0.0 && *t < 1.0);
+    if thresholds.is_empty() {
+        return Err("Thresholds must be between 0 and 1".to_string());
+    }
+    thresholds.sort_by(|a, b| a.total_cmp(b));
+
+    let scores = scene_scores(ffmpeg_path, path).await?;
+    let duration = scores.last().map_or(0.0, |&(time, _)| time);
+
+    let counts: Vec<ThresholdCuts> = thresholds
+        .iter()
+        .map(|&threshold| ThresholdCuts {
+            threshold,
+            cuts: scores.iter().filter(|&&(time, score)| time > 0.1 && score > threshold).count(),
+        })
+        .collect();
+
+    let max_cuts = (duration / SUGGESTED_SECONDS_PER_CUT).ceil() as usize;
+    let suggested = counts
+        .iter()
+        .find(|c| c.cuts <= max_cuts)
+        .or(counts.last())
+        .map_or(0.3, |c| c.threshold);
+
+    Ok(SceneCalibration {
+        thresholds: counts,
+        suggested,
+    })
+}
@@ -0,0 +1,239 @@
+use crate::cache::app_data_file;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+
+/// Scratch space allowed before old files are cleaned up, unless configured
+const DEFAULT_QUOTA_MB: u64 = 2048;
+
+/// Files untouched for less than this may belong to a running job and are never removed
+/// by the quota check
+const MIN_IDLE: Duration = Duration::from_secs(60 * 60);
+
+/// Subfolder created in an override folder, so cleanup never touches the user's own files
+const OVERRIDE_SUBDIR: &str = "torchio-scratch";
+
+/// Prefix of per-job workspace folders inside the scratch folder
+const WORKSPACE_PREFIX: &str = "job_";
+
+/// Makes workspace names unique within a session
+static NEXT_WORKSPACE: AtomicU64 = AtomicU64::new(0);
+
+/// Where temporary frames, chapter files, palettes and pass logs go
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScratchConfig {
+    /// Override for the scratch folder; unset uses the app cache dir
+    pub dir: Option<String>,
+    #[serde(rename = "quotaMb")]
+    pub quota_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScratchCleanup {
+    #[serde(rename = "removedFiles")]
+    pub removed_files: u32,
+    #[serde(rename = "freedBytes")]
+    pub freed_bytes: u64,
+}
+
+fn config_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "scratch.json")
+}
+
+pub fn load_config(app: &tauri::AppHandle) -> ScratchConfig {
+    config_file(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Save a new config after checking the override folder is writable
+pub fn save_config(app: &tauri::AppHandle, config: &ScratchConfig) -> Result<(), String> {
+    if let Some(ref dir) = config.dir {
+        let dir = Path::new(dir);
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create scratch folder: {}", e))?;
+        let probe = dir.join(".torchio-write-test");
+        fs::write(&probe, b"").map_err(|e| format!("Scratch folder is not writable: {}", e))?;
+        let _ = fs::remove_file(probe);
+    }
+    let json = serde_json::to_string(config).map_err(|e| format!("Failed to serialize scratch settings: {}", e))?;
+    fs::write(config_file(app)?, json).map_err(|e| format!("Failed to save scratch settings: {}", e))
+}
+
+/// Folder for this app's temporary files. Inside the app cache dir so it stays within
+/// sandboxed apps' container and away from other apps' temp files.
+pub fn scratch_dir(app: &tauri::AppHandle) -> PathBuf {
+    let dir = match load_config(app).dir {
+        Some(dir) => Path::new(&dir).join(OVERRIDE_SUBDIR),
+        None => app
+            .path()
+            .app_cache_dir()
+            .map(|d| d.join("scratch"))
+            .unwrap_or_else(|_| std::env::temp_dir().join("torchio")),
+    };
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+/// Files in the scratch folder with their size and last modification, oldest first
+fn scratch_files(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let mut files: Vec<(PathBuf, u64, SystemTime)> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let meta = entry.metadata().ok()?;
+                    meta.is_file()
+                        .then(|| (entry.path(), meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    files
+}
+
+fn remove_files(files: impl IntoIterator<Item = (PathBuf, u64, SystemTime)>) -> ScratchCleanup {
+    let mut cleanup = ScratchCleanup::default();
+    for (path, size, _) in files {
+        if fs::remove_file(path).is_ok() {
+            cleanup.removed_files += 1;
+            cleanup.freed_bytes += size;
+        }
+    }
+    cleanup
+}
+
+/// Total size of the files in a folder tree
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| match entry.metadata() {
+                    Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+                    Ok(meta) => meta.len(),
+                    Err(_) => 0,
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Remove everything left in the scratch folder; only safe when no job is running
+/// (at startup, files there are leftovers of an earlier session)
+pub fn clear_scratch(app: &tauri::AppHandle) -> ScratchCleanup {
+    let dir = scratch_dir(app);
+    let mut cleanup = remove_files(scratch_files(&dir));
+
+    // Workspaces of jobs that never got to drop theirs (crash, force quit)
+    let workspaces = fs::read_dir(&dir).into_iter().flatten().flatten().filter(|entry| {
+        entry.file_type().is_ok_and(|t| t.is_dir()) && entry.file_name().to_string_lossy().starts_with(WORKSPACE_PREFIX)
+    });
+    for workspace in workspaces {
+        let size = dir_size(&workspace.path());
+        if fs::remove_dir_all(workspace.path()).is_ok() {
+            cleanup.removed_files += 1;
+            cleanup.freed_bytes += size;
+        }
+    }
+    cleanup
+}
+
+/// A job's own folder in the scratch space, deleted with everything in it when dropped.
+/// The name adds the process id and a session counter to the job id, so a retried job
+/// or an id reused after a restart never shares temp files with another run.
+#[derive(Debug)]
+pub struct JobWorkspace {
+    dir: PathBuf,
+}
+
+impl JobWorkspace {
+    pub fn new(app: &tauri::AppHandle, id: &str) -> Result<Self, String> {
+        Self::create_in(&scratch_dir(app), id)
+    }
+
+    pub fn create_in(parent: &Path, id: &str) -> Result<Self, String> {
+        // Job ids come from the frontend; keep them from naming anything outside the parent
+        let safe_id: String = id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let dir = parent.join(format!(
+            "{}{}_{}_{}",
+            WORKSPACE_PREFIX,
+            safe_id,
+            std::process::id(),
+            NEXT_WORKSPACE.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create job workspace: {}", e))?;
+        Ok(JobWorkspace { dir })
+    }
+
+    /// Path for a temp file of this job
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for JobWorkspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Remove the oldest idle files until the scratch folder is back under its quota
+pub fn enforce_quota(app: &tauri::AppHandle) -> ScratchCleanup {
+    let quota = load_config(app).quota_mb.unwrap_or(DEFAULT_QUOTA_MB) * 1024 * 1024;
+    let files = scratch_files(&scratch_dir(app));
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+
+    let mut expendable = Vec::new();
+    for file in files {
+        if total <= quota {
+            break;
+        }
+        let idle = file.2.elapsed().map(|age| age >= MIN_IDLE).unwrap_or(false);
+        if idle {
+            total = total.saturating_sub(file.1);
+            expendable.push(file);
+        }
+    }
+    remove_files(expendable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspaces_for_the_same_id_are_distinct() {
+        let parent = tempfile::tempdir().unwrap();
+        let first = JobWorkspace::create_in(parent.path(), "job1").unwrap();
+        let retry = JobWorkspace::create_in(parent.path(), "job1").unwrap();
+        assert_ne!(first.dir, retry.dir);
+        assert!(first.dir.is_dir() && retry.dir.is_dir());
+    }
+
+    #[test]
+    fn workspace_is_removed_on_drop() {
+        let parent = tempfile::tempdir().unwrap();
+        let workspace = JobWorkspace::create_in(parent.path(), "job1").unwrap();
+        let dir = workspace.dir.clone();
+        fs::write(workspace.file("chapters.txt"), ";FFMETADATA1\n").unwrap();
+        drop(workspace);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn workspace_stays_inside_its_parent() {
+        let parent = tempfile::tempdir().unwrap();
+        let workspace = JobWorkspace::create_in(parent.path(), "../../etc/x").unwrap();
+        assert_eq!(workspace.dir.parent(), Some(parent.path()));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::converter::audio_format;
+use crate::ffmpeg::{get_audio_streams, get_ffmpeg_path, get_ffprobe_path};
+use crate::output::resolve_output_path;
+use std::path::Path;
+use tokio::process::Command;
+
+/// Keep track titles usable as part of a file name
+fn sanitize_title(title: &str) -> String {
+    title
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Write each selected audio track (all of them when `tracks` is None) to its own file next
+/// to the source, e.g. "recording_track2_Mic.wav". Stems are for editing, so only the
+/// lossless formats ("wav", the default, or "flac") are offered.
+pub async fn export_stems(
+    app: &tauri::AppHandle,
+    path: &str,
+    tracks: Option<Vec<u32>>,
+    format: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let ffprobe = get_ffprobe_path(app);
+
+    let format_name = format.unwrap_or("wav");
+    let format = audio_format(format_name)
+        .filter(|f| f.bitrate_range_k.is_none())
+        .ok_or_else(|| format!("Unsupported stem format: {}", format_name))?;
+
+    let streams = get_audio_streams(&ffprobe, path).await?;
+    if streams.is_empty() {
+        return Err("The file has no audio tracks".to_string());
+    }
+    let tracks = tracks.unwrap_or_else(|| streams.iter().map(|s| s.index).collect());
+    if tracks.is_empty() {
+        return Err("No audio tracks selected".to_string());
+    }
+
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "audio".to_string());
+
+    // One ffmpeg run with an output per track, so the source is only read once
+    let mut args: Vec<String> = vec!["-y".to_string(), "-i".to_string(), path.to_string()];
+    let mut outputs = Vec::new();
+    for track in tracks {
+        let stream = streams
+            .iter()
+            .find(|s| s.index == track)
+            .ok_or_else(|| format!("Audio track {} doesn't exist", track))?;
+
+        let mut name = format!("{}_track{}", stem, track + 1);
+        if let Some(title) = stream.title.as_deref().map(sanitize_title).filter(|t| !t.is_empty()) {
+            name.push('_');
+            name.push_str(&title);
+        }
+        let location = resolve_output_path(app, path, &format!("{}.{}", name, format_name), true)?;
+        let output_str = location.path.to_string_lossy().to_string();
+
+        args.extend([
+            "-map".to_string(), format!("0:a:{}", track),
+            "-c:a".to_string(), format.codec_for(Some(stream)).to_string(),
+        ]);
+        args.extend(format.args.iter().map(|s| s.to_string()));
+        args.push(output_str.clone());
+        outputs.push(output_str);
+    }
+
+    let mut cmd = Command::new(&ffmpeg);
+    cmd.args(&args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        for path in &outputs {
+            let _ = std::fs::remove_file(path);
+        }
+        return Err("Failed to export audio stems".to_string());
+    }
+
+    Ok(outputs)
+}
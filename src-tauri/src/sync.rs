@@ -0,0 +1,172 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Both signals are reduced to this many samples per second before correlating
+const ANALYSIS_RATE: u32 = 50;
+
+/// Audio sample rate used for the energy envelope
+const AUDIO_RATE: u32 = 8000;
+
+/// Largest offset searched in either direction (seconds)
+const MAX_OFFSET_SECONDS: f64 = 1.0;
+
+/// Default stretch of the file analysed when no duration is given (seconds)
+const DEFAULT_ANALYSIS_SECONDS: f64 = 30.0;
+
+/// Thumbnail size for the motion signal; a clap shows up as a burst of change
+const MOTION_WIDTH: usize = 32;
+const MOTION_HEIGHT: usize = 18;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioOffsetEstimate {
+    /// Value to pass as audioOffsetMs to bring audio back in sync
+    #[serde(rename = "offsetMs")]
+    pub offset_ms: i64,
+    /// Normalized correlation at the best lag (0-1); low values mean no clear transient was found
+    pub confidence: f64,
+}
+
+async fn run_ffmpeg_raw(ffmpeg_path: &PathBuf, args: &[String]) -> Result<Vec<u8>, String> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to decode media for sync analysis".to_string());
+    }
+    Ok(output.stdout)
+}
+
+/// Positive frame-to-frame increases of a signal: where something suddenly starts
+fn onset_strength(signal: &[f64]) -> Vec<f64> {
+    let mut onsets = vec![0.0; signal.len()];
+    for i in 1..signal.len() {
+        onsets[i] = (signal[i] - signal[i - 1]).max(0.0);
+    }
+    onsets
+}
+
+/// Audio energy per analysis bin
+async fn audio_envelope(ffmpeg_path: &PathBuf, path: &str, start: f64, duration: f64) -> Result<Vec<f64>, String> {
+    let args: Vec<String> = vec![
+        "-ss".to_string(), format!("{:.3}", start),
+        "-i".to_string(), path.to_string(),
+        "-t".to_string(), format!("{:.3}", duration),
+        "-vn".to_string(),
+        "-ac".to_string(), "1".to_string(),
+        "-ar".to_string(), AUDIO_RATE.to_string(),
+        "-f".to_string(), "s16le".to_string(),
+        "pipe:1".to_string(),
+    ];
+    let raw = run_ffmpeg_raw(ffmpeg_path, &args).await?;
+
+    let samples: Vec<f64> = raw
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64 / i16::MAX as f64)
+        .collect();
+    let bin = (AUDIO_RATE / ANALYSIS_RATE) as usize;
+
+    Ok(samples
+        .chunks(bin)
+        .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f64>() / chunk.len() as f64).sqrt())
+        .collect())
+}
+
+/// Mean absolute difference between consecutive tiny grayscale frames per analysis bin
+async fn video_motion(ffmpeg_path: &PathBuf, path: &str, start: f64, duration: f64) -> Result<Vec<f64>, String> {
+    let filter = format!(
+        "fps={},scale={}:{}:flags=area,format=gray",
+        ANALYSIS_RATE, MOTION_WIDTH, MOTION_HEIGHT
+    );
+    let args: Vec<String> = vec![
+        "-ss".to_string(), format!("{:.3}", start),
+        "-i".to_string(), path.to_string(),
+        "-t".to_string(), format!("{:.3}", duration),
+        "-an".to_string(),
+        "-vf".to_string(), filter,
+        "-f".to_string(), "rawvideo".to_string(),
+        "pipe:1".to_string(),
+    ];
+    let raw = run_ffmpeg_raw(ffmpeg_path, &args).await?;
+
+    let frame_size = MOTION_WIDTH * MOTION_HEIGHT;
+    let frames: Vec<&[u8]> = raw.chunks_exact(frame_size).collect();
+    let mut motion = vec![0.0; frames.len()];
+    for i in 1..frames.len() {
+        let diff: u32 = frames[i]
+            .iter()
+            .zip(frames[i - 1])
+            .map(|(&a, &b)| (a as i32 - b as i32).unsigned_abs())
+            .sum();
+        motion[i] = diff as f64 / frame_size as f64;
+    }
+    Ok(motion)
+}
+
+/// Zero-mean, unit-length copy of a signal so correlations are comparable
+fn normalize(signal: &[f64]) -> Vec<f64> {
+    let mean = signal.iter().sum::<f64>() / signal.len().max(1) as f64;
+    let centered: Vec<f64> = signal.iter().map(|v| v - mean).collect();
+    let norm = centered.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        return centered;
+    }
+    centered.iter().map(|v| v / norm).collect()
+}
+
+/// Lag (in bins) at which `audio` best matches `video`, with its correlation.
+/// A positive lag means the audio transient comes after the visual one.
+fn best_lag(video: &[f64], audio: &[f64], max_lag: i64) -> (i64, f64) {
+    let mut best = (0, f64::MIN);
+    for lag in -max_lag..=max_lag {
+        let score: f64 = video
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| {
+                let j = i as i64 + lag;
+                (j >= 0 && (j as usize) < audio.len()).then(|| v * audio[j as usize])
+            })
+            .sum();
+        if score > best.1 {
+            best = (lag, score);
+        }
+    }
+    best
+}
+
+/// Estimate a fixed audio/video offset by correlating audio onsets with bursts of motion
+/// (a clap, slate or other sharp transient near the start works best)
+pub async fn estimate_audio_offset(
+    ffmpeg_path: &PathBuf,
+    path: &str,
+    start: Option<f64>,
+    duration: Option<f64>,
+) -> Result<AudioOffsetEstimate, String> {
+    let start = start.unwrap_or(0.0).max(0.0);
+    let duration = duration.unwrap_or(DEFAULT_ANALYSIS_SECONDS);
+
+    let audio = audio_envelope(ffmpeg_path, path, start, duration).await?;
+    let video = video_motion(ffmpeg_path, path, start, duration).await?;
+    if audio.is_empty() || video.is_empty() {
+        return Err("File needs both audio and video to estimate sync".to_string());
+    }
+
+    let audio_onsets = normalize(&onset_strength(&audio));
+    let video_onsets = normalize(&onset_strength(&video));
+    let max_lag = (MAX_OFFSET_SECONDS * ANALYSIS_RATE as f64) as i64;
+    let (lag, score) = best_lag(&video_onsets, &audio_onsets, max_lag);
+
+    // Audio arriving `lag` bins late is fixed by pulling it earlier by the same amount
+    let bin_ms = 1000 / ANALYSIS_RATE as i64;
+    Ok(AudioOffsetEstimate {
+        offset_ms: -lag * bin_ms,
+        confidence: score.clamp(0.0, 1.0),
+    })
+}
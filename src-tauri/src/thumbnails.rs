@@ -0,0 +1,103 @@
+use crate::cache::{cache_subdir, file_cache_key, is_cached};
+use crate::converter::Marker;
+use crate::ffmpeg::get_ffmpeg_path;
+use crate::proxy;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Frames extracted per ffmpeg invocation (each is a separate fast-seeked input)
+const THUMBNAIL_BATCH_SIZE: usize = 16;
+
+/// Marker thumbnails are small; the chapter editor shows them as list icons
+const THUMBNAIL_FILTER: &str = "scale=-2:'min(180,ih)'";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerThumbnail {
+    #[serde(rename = "markerId")]
+    pub marker_id: u32,
+    pub time: f64,
+    /// JPEG data URL, empty if the frame couldn't be extracted
+    pub image: String,
+}
+
+/// Cache file for the frame at `time`, keyed by file identity and millisecond timestamp
+fn thumbnail_path(dir: &Path, key: &str, time: f64) -> PathBuf {
+    dir.join(format!("{}_{}.jpg", key, (time * 1000.0).round() as i64))
+}
+
+/// Extract several frames with one ffmpeg call: one seeked input and one output per frame
+async fn extract_batch(ffmpeg: &PathBuf, source: &str, frames: &[(f64, PathBuf)]) -> Result<(), String> {
+    let mut args: Vec<String> = vec!["-y".to_string()];
+    for (time, _) in frames {
+        args.extend([
+            "-ss".to_string(), format!("{:.3}", time),
+            "-i".to_string(), source.to_string(),
+        ]);
+    }
+    for (i, (_, path)) in frames.iter().enumerate() {
+        args.extend([
+            "-map".to_string(), format!("{}:v:0", i),
+            "-frames:v".to_string(), "1".to_string(),
+            "-vf".to_string(), THUMBNAIL_FILTER.to_string(),
+            "-q:v".to_string(), "5".to_string(),
+            path.to_string_lossy().to_string(),
+        ]);
+    }
+
+    let mut cmd = Command::new(ffmpeg);
+    cmd.args(&args);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err("Failed to extract marker thumbnails".to_string());
+    }
+    Ok(())
+}
+
+/// One thumbnail per marker, extracting only the frames that aren't cached yet
+pub async fn get_marker_thumbnails(app: &tauri::AppHandle, path: &str, markers: &[Marker]) -> Result<Vec<MarkerThumbnail>, String> {
+    let ffmpeg = get_ffmpeg_path(app);
+    let source = proxy::preview_source(app, path);
+    let dir = cache_subdir(app, "thumbnails")?;
+    let key = file_cache_key(path);
+
+    let mut missing: Vec<(f64, PathBuf)> = markers
+        .iter()
+        .map(|m| (m.time, thumbnail_path(&dir, &key, m.time)))
+        .filter(|(_, p)| !is_cached(p))
+        .collect();
+    missing.sort_by(|a, b| a.0.total_cmp(&b.0));
+    missing.dedup_by(|a, b| a.1 == b.1);
+
+    for batch in missing.chunks(THUMBNAIL_BATCH_SIZE) {
+        // A bad timestamp fails the whole batch; fall back to one frame at a time
+        if extract_batch(&ffmpeg, &source, batch).await.is_err() {
+            for frame in batch {
+                let _ = extract_batch(&ffmpeg, &source, std::slice::from_ref(frame)).await;
+            }
+        }
+    }
+
+    Ok(markers
+        .iter()
+        .map(|m| {
+            let image = fs::read(thumbnail_path(&dir, &key, m.time))
+                .map(|data| format!("data:image/jpeg;base64,{}", BASE64.encode(data)))
+                .unwrap_or_default();
+            MarkerThumbnail {
+                marker_id: m.id,
+                time: m.time,
+                image,
+            }
+        })
+        .collect())
+}
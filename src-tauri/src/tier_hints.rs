@@ -0,0 +1,63 @@
+use crate::cache::{cache_subdir, file_cache_key};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Output sizes measured per tier index, keyed by (format, input, trim, target)
+type TierMeasurements = HashMap<String, BTreeMap<usize, u64>>;
+
+// Serializes read-modify-write of the hints file
+static HINTS_LOCK: Mutex<()> = Mutex::new(());
+
+fn hints_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(cache_subdir(app, "tiers")?.join("tier_hints.json"))
+}
+
+fn load_all(path: &PathBuf) -> TierMeasurements {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Key identifying a tiered conversion so retries of the same job share measurements
+pub fn hint_key(format: &str, input_path: &str, trim_start: Option<f64>, trim_duration: Option<f64>, target_bytes: u64) -> String {
+    format!(
+        "{}|{}|{:.3}|{:.3}|{}",
+        format,
+        file_cache_key(input_path),
+        trim_start.unwrap_or(0.0),
+        trim_duration.unwrap_or(0.0),
+        target_bytes
+    )
+}
+
+/// Sizes measured by previous attempts of this job
+pub fn known_sizes(app: &tauri::AppHandle, key: &str) -> BTreeMap<usize, u64> {
+    let Ok(path) = hints_file(app) else {
+        return BTreeMap::new();
+    };
+    let _guard = HINTS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    load_all(&path).remove(key).unwrap_or_default()
+}
+
+/// Persist the size a tier produced so an interrupted job can resume past it
+pub fn record_size(app: &tauri::AppHandle, key: &str, tier: usize, size: u64) {
+    let Ok(path) = hints_file(app) else {
+        return;
+    };
+    let _guard = HINTS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut all = load_all(&path);
+    all.entry(key.to_string()).or_default().insert(tier, size);
+    if let Ok(json) = serde_json::to_string(&all) {
+        let _ = fs::write(&path, json);
+    }
+}
+
+/// First tier not already known to overshoot `limit` (the last tier if all of them did)
+pub fn first_promising_tier(known: &BTreeMap<usize, u64>, tier_count: usize, limit: u64) -> usize {
+    (0..tier_count)
+        .find(|i| !matches!(known.get(i), Some(&size) if size > limit))
+        .unwrap_or(tier_count.saturating_sub(1))
+}
@@ -0,0 +1,278 @@
+use crate::cache::app_data_file;
+use crate::output::move_file;
+use crate::pipeline::recycle_file;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Actions kept undoable; older staged files are handed to the system trash
+const MAX_UNDO_ENTRIES: usize = 20;
+
+/// Staged copies of user files sit next to the originals, so beyond this many bytes
+/// the oldest are handed to the system trash early (the newest is always kept)
+const MAX_STAGED_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Hidden folder next to a staged file, so staging is a same-volume rename instead of a
+/// copy; MAX_STAGED_BYTES bounds how much it holds
+const STAGING_DIR: &str = ".torchio-trash";
+
+// Serializes read-modify-write of the journal
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// A destructive action with enough information to reverse it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum UndoAction {
+    /// A file was removed; it sits in the staging folder until it ages out
+    #[serde(rename = "remove")]
+    Remove { original: String, staged: String },
+    /// A file was replaced by a new one; the old version is staged
+    #[serde(rename = "overwrite")]
+    Overwrite { original: String, staged: String },
+    /// A file was moved
+    #[serde(rename = "move")]
+    Move { from: String, to: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UndoEntry {
+    #[serde(flatten)]
+    action: UndoAction,
+    timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoResult {
+    pub kind: String,
+    /// Path of the restored file
+    #[serde(rename = "restoredPath")]
+    pub restored_path: String,
+    /// Where the file that replaced it was moved, for an undone overwrite
+    #[serde(rename = "keptPath")]
+    pub kept_path: Option<String>,
+}
+
+impl UndoAction {
+    /// Bytes held in the staging folder for this action
+    fn staged_bytes(&self) -> u64 {
+        match self {
+            UndoAction::Remove { staged, .. } | UndoAction::Overwrite { staged, .. } => {
+                fs::metadata(staged).map(|m| m.len()).unwrap_or(0)
+            }
+            UndoAction::Move { .. } => 0,
+        }
+    }
+}
+
+fn journal_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "undo_journal.json")
+}
+
+fn load_journal(path: &PathBuf) -> Vec<UndoEntry> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_journal(path: &PathBuf, entries: &[UndoEntry]) -> Result<(), String> {
+    let json = serde_json::to_string(entries).map_err(|e| format!("Failed to serialize undo journal: {}", e))?;
+    fs::write(path, json).map_err(|e| format!("Failed to save undo journal: {}", e))
+}
+
+/// Move a file into the staging folder beside it under a unique name
+fn stage(path: &str) -> Result<PathBuf, String> {
+    let source = Path::new(path);
+    let parent = source.parent().ok_or("File has no parent folder")?;
+    let name = source.file_name().ok_or("File has no name")?.to_string_lossy();
+
+    let dir = parent.join(STAGING_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create staging folder: {}", e))?;
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let staged = dir.join(format!("{}_{}", nanos, name));
+    move_file(source, &staged)?;
+    Ok(staged)
+}
+
+/// First free "name (n).ext" beside `path`
+fn free_sibling(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Add an entry; entries pushed past the count or size limit are finalized (staged
+/// files go to the trash)
+async fn record(app: &tauri::AppHandle, action: UndoAction) -> Result<(), String> {
+    let expired = {
+        let path = journal_file(app)?;
+        let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries = load_journal(&path);
+        entries.push(UndoEntry {
+            action,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+        let mut keep_from = entries.len().saturating_sub(MAX_UNDO_ENTRIES);
+        let mut staged_bytes = 0;
+        for index in (keep_from..entries.len()).rev() {
+            staged_bytes += entries[index].action.staged_bytes();
+            if staged_bytes > MAX_STAGED_BYTES && index + 1 < entries.len() {
+                keep_from = index + 1;
+                break;
+            }
+        }
+        let expired: Vec<UndoEntry> = entries.drain(..keep_from).collect();
+        save_journal(&path, &entries)?;
+        expired
+    };
+
+    for entry in expired {
+        if let UndoAction::Remove { staged, .. } | UndoAction::Overwrite { staged, .. } = entry.action {
+            let _ = recycle_file(&staged).await;
+        }
+    }
+    Ok(())
+}
+
+/// Remove a file in an undoable way (replaces sending it straight to the trash)
+pub async fn stage_removal(app: &tauri::AppHandle, path: &str) -> Result<(), String> {
+    let staged = stage(path)?;
+    let recorded = record(
+        app,
+        UndoAction::Remove {
+            original: path.to_string(),
+            staged: staged.to_string_lossy().to_string(),
+        },
+    )
+    .await;
+    // Without a journal entry nothing would ever restore or clean up the staged file
+    if recorded.is_err() {
+        let _ = move_file(&staged, Path::new(path));
+    }
+    recorded
+}
+
+/// Stage an existing file that is about to be overwritten; no-op if it doesn't exist
+pub async fn stage_overwrite(app: &tauri::AppHandle, path: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let original = path.to_string_lossy().to_string();
+    let staged = stage(&original)?;
+    let recorded = record(
+        app,
+        UndoAction::Overwrite {
+            original,
+            staged: staged.to_string_lossy().to_string(),
+        },
+    )
+    .await;
+    if recorded.is_err() {
+        let _ = move_file(&staged, path);
+    }
+    recorded
+}
+
+/// Put back a file staged by stage_overwrite when the write that replaced it failed
+pub fn unstage_overwrite(app: &tauri::AppHandle, path: &Path) -> Result<(), String> {
+    let journal = journal_file(app)?;
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load_journal(&journal);
+    let original = path.to_string_lossy().to_string();
+
+    let index = entries
+        .iter()
+        .rposition(|e| matches!(&e.action, UndoAction::Overwrite { original: o, .. } if *o == original))
+        .ok_or("No staged file to restore")?;
+    if let UndoAction::Overwrite { staged, .. } = &entries[index].action {
+        let _ = fs::remove_file(path);
+        move_file(Path::new(staged), path)?;
+    }
+
+    entries.remove(index);
+    save_journal(&journal, &entries)
+}
+
+/// Record a completed move so it can be reversed
+pub async fn record_move(app: &tauri::AppHandle, from: &Path, to: &Path) -> Result<(), String> {
+    record(
+        app,
+        UndoAction::Move {
+            from: from.to_string_lossy().to_string(),
+            to: to.to_string_lossy().to_string(),
+        },
+    )
+    .await
+}
+
+/// Reverse the most recent recorded action
+pub fn undo_last_action(app: &tauri::AppHandle) -> Result<UndoResult, String> {
+    let path = journal_file(app)?;
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load_journal(&path);
+    let entry = entries.last().cloned().ok_or("Nothing to undo")?;
+
+    let result = match entry.action {
+        UndoAction::Remove { original, staged } => {
+            if Path::new(&original).exists() {
+                return Err(format!("Cannot restore {}: a file with that name exists", original));
+            }
+            move_file(Path::new(&staged), Path::new(&original))?;
+            UndoResult {
+                kind: "remove".to_string(),
+                restored_path: original,
+                kept_path: None,
+            }
+        }
+        UndoAction::Overwrite { original, staged } => {
+            // The newer file moves aside under a free name so the previous version can
+            // take its place; undo only ever restores, it never deletes
+            let original_path = Path::new(&original);
+            let kept = if original_path.exists() {
+                let kept = free_sibling(original_path);
+                move_file(original_path, &kept)?;
+                Some(kept.to_string_lossy().to_string())
+            } else {
+                None
+            };
+            if let Err(e) = move_file(Path::new(&staged), original_path) {
+                if let Some(ref kept) = kept {
+                    let _ = move_file(Path::new(kept), original_path);
+                }
+                return Err(e);
+            }
+            UndoResult {
+                kind: "overwrite".to_string(),
+                restored_path: original,
+                kept_path: kept,
+            }
+        }
+        UndoAction::Move { from, to } => {
+            if Path::new(&from).exists() {
+                return Err(format!("Cannot move back to {}: a file with that name exists", from));
+            }
+            move_file(Path::new(&to), Path::new(&from))?;
+            UndoResult {
+                kind: "move".to_string(),
+                restored_path: from,
+                kept_path: None,
+            }
+        }
+    };
+
+    entries.pop();
+    save_journal(&path, &entries)?;
+    Ok(result)
+}
@@ -0,0 +1,184 @@
+use crate::ffmpeg::run_ffmpeg_parallel;
+use crate::scratch::JobWorkspace;
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// Probe samples: a few short stretches spread over the clip
+const SAMPLE_COUNT: usize = 3;
+const SAMPLE_SECONDS: f64 = 4.0;
+
+/// The search stops once the bitrate bracket is this tight (high / low), or after
+/// MAX_PROBES bitrates
+const BRACKET_RATIO: f64 = 1.1;
+pub const MAX_PROBES: u32 = 7;
+
+/// Scores a target VMAF can be set to; below 50 the picture is visibly broken anyway
+pub const MIN_TARGET: f64 = 50.0;
+pub const MAX_TARGET: f64 = 100.0;
+
+/// What the source is probed as: the part of it being exported, scaled like the export
+pub struct ProbeSource<'a> {
+    pub input_path: &'a str,
+    pub scale_filter: &'a str,
+    pub trim_start: Option<f64>,
+    pub duration: f64,
+}
+
+/// Lowest bitrate found that reaches the target score
+#[derive(Debug, Clone)]
+pub struct VmafSearch {
+    /// Video bits per second
+    pub bitrate: f64,
+    /// Mean VMAF of the samples at that bitrate
+    pub score: f64,
+    /// False when even the highest allowed bitrate scored below the target
+    pub reached: bool,
+}
+
+/// CPU encoder the probes use for a conversion type, with its app settings. GPU encodes
+/// score a little lower at the same bitrate, so probing with these is slightly optimistic.
+pub fn probe_encoder(conversion_type: &str) -> Option<(&'static str, &'static [&'static str])> {
+    match conversion_type {
+        "mp4" | "mov" | "mkv" | "ts" => Some(("libx264", &["-preset", "slow"])),
+        "mp4_hevc" => Some(("libx265", &["-preset", "medium"])),
+        "mp4_av1" | "webm_av1" => Some(("libsvtav1", &["-preset", "6"])),
+        "webm_vp9" => Some(("libvpx-vp9", &["-deadline", "good", "-cpu-used", "2", "-row-mt", "1"])),
+        "webm_vp8" => Some(("libvpx", &["-deadline", "good", "-cpu-used", "2"])),
+        _ => None,
+    }
+}
+
+/// (start, length) of each sample; a clip too short to sample is probed whole
+fn sample_windows(trim_start: Option<f64>, duration: f64) -> Vec<(f64, f64)> {
+    let start = trim_start.unwrap_or(0.0);
+    if duration < SAMPLE_COUNT as f64 * SAMPLE_SECONDS * 2.0 {
+        return vec![(start, duration)];
+    }
+    let slice = duration / SAMPLE_COUNT as f64;
+    (0..SAMPLE_COUNT)
+        .map(|k| (start + slice * (k as f64 + 0.5) - SAMPLE_SECONDS / 2.0, SAMPLE_SECONDS))
+        .collect()
+}
+
+/// "VMAF score: 93.456789" from libvmaf's log
+fn parse_score(stderr: &str) -> Option<f64> {
+    stderr
+        .lines()
+        .find_map(|line| line.split("VMAF score:").nth(1))
+        .and_then(|score| score.trim().parse().ok())
+}
+
+/// VMAF of an encoded sample against the same stretch of the source at the same size
+async fn score_sample(ffmpeg_path: &PathBuf, source: &ProbeSource<'_>, (start, length): (f64, f64), encoded: &str) -> Result<f64, String> {
+    let graph = format!(
+        "[0:v]setpts=PTS-STARTPTS[dist];[1:v]{},setpts=PTS-STARTPTS[ref];[dist][ref]libvmaf",
+        source.scale_filter
+    );
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(["-hide_banner", "-i", encoded]);
+    cmd.args(["-ss", &format!("{:.3}", start), "-t", &format!("{:.3}", length), "-i", source.input_path]);
+    cmd.args(["-lavfi", &graph, "-f", "null", "-"]);
+
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().await.map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("No such filter: 'libvmaf'") {
+        return Err("Target VMAF needs an ffmpeg built with libvmaf".to_string());
+    }
+    if !output.status.success() {
+        return Err(format!("VMAF scoring failed: {}", stderr.lines().last().unwrap_or_default()));
+    }
+    parse_score(&stderr).ok_or_else(|| "VMAF scoring failed: no score in ffmpeg output".to_string())
+}
+
+/// Encode every sample at `bitrate` (in parallel) and return their mean VMAF
+async fn score_at(
+    ffmpeg_path: &PathBuf,
+    workspace: &JobWorkspace,
+    source: &ProbeSource<'_>,
+    (encoder, encoder_args): (&str, &[&str]),
+    bitrate: f64,
+) -> Result<f64, String> {
+    let windows = sample_windows(source.trim_start, source.duration);
+    let samples: Vec<String> = (0..windows.len())
+        .map(|k| workspace.file(&format!("vmaf_probe_{}.mkv", k)).to_string_lossy().to_string())
+        .collect();
+
+    let bitrate_str = format!("{}k", (bitrate / 1000.0) as u32);
+    let maxrate_str = format!("{}k", (bitrate * 1.5 / 1000.0) as u32);
+    let arg_sets = windows
+        .iter()
+        .zip(&samples)
+        .map(|((start, length), sample)| {
+            let mut args: Vec<String> = vec!["-y".to_string(), "-ss".to_string(), format!("{:.3}", start)];
+            args.extend(["-t".to_string(), format!("{:.3}", length), "-i".to_string(), source.input_path.to_string()]);
+            args.extend(["-vf".to_string(), source.scale_filter.to_string(), "-c:v".to_string(), encoder.to_string()]);
+            args.extend(encoder_args.iter().map(|s| s.to_string()));
+            args.extend(["-b:v".to_string(), bitrate_str.clone(), "-maxrate".to_string(), maxrate_str.clone()]);
+            args.extend(["-bufsize".to_string(), bitrate_str.clone(), "-an".to_string(), sample.clone()]);
+            args
+        })
+        .collect();
+    if run_ffmpeg_parallel(ffmpeg_path, arg_sets).await.contains(&false) {
+        return Err(format!("Encoding a VMAF probe with {} failed", encoder));
+    }
+
+    // Scored one at a time; libvmaf already uses every core
+    let mut total = 0.0;
+    for (window, sample) in windows.iter().zip(&samples) {
+        total += score_sample(ffmpeg_path, source, *window, sample).await?;
+    }
+    Ok(total / windows.len() as f64)
+}
+
+/// Lowest video bitrate between `min_bitrate` and `max_bitrate` whose probe samples reach
+/// `target` VMAF, by bisecting in log space. `on_probe` is called with the number of
+/// bitrates tried so far, for progress.
+pub async fn bitrate_for_score(
+    ffmpeg_path: &PathBuf,
+    workspace: &JobWorkspace,
+    source: &ProbeSource<'_>,
+    encoder: (&str, &[&str]),
+    target: f64,
+    min_bitrate: f64,
+    max_bitrate: f64,
+    mut on_probe: impl FnMut(u32),
+) -> Result<VmafSearch, String> {
+    let mut probes = 0;
+    let mut probe = |bitrate: f64| {
+        probes += 1;
+        on_probe(probes);
+        score_at(ffmpeg_path, workspace, source, encoder, bitrate)
+    };
+
+    let high_score = probe(max_bitrate).await?;
+    if high_score < target || max_bitrate <= min_bitrate {
+        return Ok(VmafSearch { bitrate: max_bitrate, score: high_score, reached: high_score >= target });
+    }
+    let low_score = probe(min_bitrate).await?;
+    if low_score >= target {
+        return Ok(VmafSearch { bitrate: min_bitrate, score: low_score, reached: true });
+    }
+
+    let (mut low, mut high) = (min_bitrate, max_bitrate);
+    let mut best = (max_bitrate, high_score);
+    for _ in 2..MAX_PROBES {
+        if high / low <= BRACKET_RATIO {
+            break;
+        }
+        let mid = (low * high).sqrt();
+        let score = probe(mid).await?;
+        if score >= target {
+            high = mid;
+            best = (mid, score);
+        } else {
+            low = mid;
+        }
+    }
+    Ok(VmafSearch { bitrate: best.0, score: best.1, reached: true })
+}